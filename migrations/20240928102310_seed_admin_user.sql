@@ -0,0 +1,8 @@
+-- Initial admin account, password: everythinghastostartsomewhere
+-- It must be changed right after the first login
+INSERT INTO users (user_id, username, password_hash)
+VALUES (
+    'ddf8994f-d522-4659-8d02-c1d479057be6',
+    'admin',
+    '$argon2id$v=19$m=15000,t=2,p=1$oiaM5khBO2EhDEJqWHM//w$b9Gq06w6V2NxiSV1iu7b7mpB2saToHJsbonisPDNEMU'
+);
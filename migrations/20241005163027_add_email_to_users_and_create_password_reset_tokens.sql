@@ -0,0 +1,11 @@
+ALTER TABLE users ADD COLUMN email TEXT NULL UNIQUE;
+
+-- Only a hash of each token is stored, so a leaked table cannot be used to reset passwords
+CREATE TABLE password_reset_tokens(
+    token_hash TEXT PRIMARY KEY,
+    user_id uuid NOT NULL
+        REFERENCES users (user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    consumed_at timestamptz NULL
+);
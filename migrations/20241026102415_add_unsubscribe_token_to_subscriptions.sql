@@ -0,0 +1,10 @@
+ALTER TABLE subscriptions ADD COLUMN unsubscribe_token TEXT NULL;
+BEGIN;
+    -- Existing subscribers get a random 32 characters token, the same shape as the ones
+    -- generated by the application
+    UPDATE subscriptions
+        SET unsubscribe_token = md5(random()::text || id::text)
+        WHERE unsubscribe_token IS NULL;
+    ALTER TABLE subscriptions ALTER COLUMN unsubscribe_token SET NOT NULL;
+    ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_unsubscribe_token_key UNIQUE (unsubscribe_token);
+COMMIT;
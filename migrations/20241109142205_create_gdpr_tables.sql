@@ -0,0 +1,30 @@
+-- Every delivery attempt of an issue, kept so that subscribers can be told what was sent to them
+-- and so that counts survive the erasure of a subscriber
+CREATE TABLE newsletter_deliveries(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_id uuid NULL
+        REFERENCES subscriptions (id) ON DELETE SET NULL,
+    outcome TEXT NOT NULL,
+    attempted_at timestamptz NOT NULL
+);
+CREATE INDEX newsletter_deliveries_subscriber_id_idx ON newsletter_deliveries (subscriber_id);
+
+-- Only a hash of each token is stored, like for password reset tokens
+CREATE TABLE data_export_tokens(
+    token_hash TEXT PRIMARY KEY,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    consumed_at timestamptz NULL
+);
+
+-- No personal data is stored here, the subscriber is only referenced by id
+CREATE TABLE gdpr_audit_log(
+    id uuid PRIMARY KEY,
+    subscriber_id uuid NOT NULL,
+    action TEXT NOT NULL,
+    actor TEXT NOT NULL,
+    occurred_at timestamptz NOT NULL
+);
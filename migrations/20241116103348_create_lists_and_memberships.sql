@@ -0,0 +1,40 @@
+CREATE TABLE lists(
+    id uuid PRIMARY KEY,
+    slug TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- Everybody subscribed before lists existed keeps receiving the same newsletter
+INSERT INTO lists (id, slug, name, created_at)
+VALUES ('6f1f8a4e-3b57-4c3e-9a56-2f0d1c6b9e01', 'default', 'Newsletter', now());
+
+-- The status of a membership is independent from the status of the subscription, which tracks
+-- whether the address itself was confirmed
+CREATE TABLE list_memberships(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    list_id uuid NOT NULL
+        REFERENCES lists (id),
+    status TEXT NOT NULL,
+    created_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, list_id)
+);
+
+INSERT INTO list_memberships (subscriber_id, list_id, status, created_at)
+SELECT id, '6f1f8a4e-3b57-4c3e-9a56-2f0d1c6b9e01', status, subscribed_at
+FROM subscriptions
+WHERE status IN ('pending_confirmation', 'confirmed', 'unsubscribed');
+
+CREATE TABLE newsletter_issue_lists(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    list_id uuid NOT NULL
+        REFERENCES lists (id),
+    PRIMARY KEY (newsletter_issue_id, list_id)
+);
+
+-- Issues published so far went to the only list there was
+INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+SELECT newsletter_issue_id, '6f1f8a4e-3b57-4c3e-9a56-2f0d1c6b9e01'
+FROM newsletter_issues;
@@ -0,0 +1,11 @@
+-- Issues published before the archive existed are reachable by their id
+ALTER TABLE newsletter_issues ADD COLUMN slug TEXT;
+UPDATE newsletter_issues SET slug = newsletter_issue_id::text;
+ALTER TABLE newsletter_issues ALTER COLUMN slug SET NOT NULL;
+ALTER TABLE newsletter_issues ADD CONSTRAINT newsletter_issues_slug_key UNIQUE (slug);
+
+-- Unknown for issues published before authors were recorded
+ALTER TABLE newsletter_issues ADD COLUMN author_id uuid REFERENCES users (user_id);
+
+-- Subscriber-only issues are kept out of the public archive
+ALTER TABLE newsletter_issues ADD COLUMN subscribers_only BOOLEAN NOT NULL DEFAULT false;
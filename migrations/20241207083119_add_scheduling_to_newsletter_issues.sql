@@ -0,0 +1,13 @@
+-- Issues are either published straight away or scheduled to be published at `send_at`.
+-- Scheduled issues get their slug and publication date once they are published
+ALTER TABLE newsletter_issues ADD COLUMN status TEXT NOT NULL DEFAULT 'published';
+ALTER TABLE newsletter_issues ALTER COLUMN status DROP DEFAULT;
+ALTER TABLE newsletter_issues ADD COLUMN send_at timestamptz;
+ALTER TABLE newsletter_issues ADD COLUMN created_at timestamptz;
+UPDATE newsletter_issues SET created_at = published_at;
+ALTER TABLE newsletter_issues ALTER COLUMN created_at SET NOT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN published_at DROP NOT NULL;
+ALTER TABLE newsletter_issues ALTER COLUMN slug DROP NOT NULL;
+
+CREATE INDEX newsletter_issues_scheduled_idx ON newsletter_issues (send_at)
+    WHERE status = 'scheduled';
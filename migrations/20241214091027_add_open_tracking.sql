@@ -0,0 +1,17 @@
+-- Open tracking is opted into per issue. Opens are counted per subscriber, so that the number of
+-- rows for an issue is its number of unique opens
+ALTER TABLE newsletter_issues ADD COLUMN track_opens BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE newsletter_opens(
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    first_opened_at timestamptz NOT NULL,
+    last_opened_at timestamptz NOT NULL,
+    open_count INTEGER NOT NULL,
+    -- Of the latest open
+    user_agent TEXT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_id)
+);
+CREATE INDEX newsletter_opens_subscriber_id_idx ON newsletter_opens (subscriber_id);
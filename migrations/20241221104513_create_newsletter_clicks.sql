@@ -0,0 +1,12 @@
+-- Every click on a tracked link, the destination being the original URL of the link
+CREATE TABLE newsletter_clicks(
+    id uuid PRIMARY KEY,
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id) ON DELETE CASCADE,
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    clicked_at timestamptz NOT NULL
+);
+CREATE INDEX newsletter_clicks_newsletter_issue_id_idx ON newsletter_clicks (newsletter_issue_id);
+CREATE INDEX newsletter_clicks_subscriber_id_idx ON newsletter_clicks (subscriber_id);
@@ -0,0 +1,15 @@
+-- Addresses nothing is ever sent to, whatever the state of their subscription. They are stored
+-- lowercased, so that changing the case of an address is not enough to get around the list
+CREATE TABLE suppressions(
+    email TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    source TEXT NOT NULL,
+    created_at timestamptz NOT NULL
+);
+
+-- Addresses that bounced or complained before the list existed
+INSERT INTO suppressions (email, reason, source, created_at)
+SELECT lower(email), status, 'postmark', now()
+FROM subscriptions
+WHERE status IN ('bounced', 'complained')
+ON CONFLICT (email) DO NOTHING;
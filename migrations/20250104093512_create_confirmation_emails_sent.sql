@@ -0,0 +1,10 @@
+-- Confirmation emails sent to each subscriber, used to cap how many an address receives per day.
+-- Rows older than a day are useless and removed as new ones come in
+CREATE TABLE confirmation_emails_sent(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    sent_at timestamptz NOT NULL
+);
+CREATE INDEX confirmation_emails_sent_subscriber_id_idx
+    ON confirmation_emails_sent (subscriber_id, sent_at);
+CREATE INDEX confirmation_emails_sent_sent_at_idx ON confirmation_emails_sent (sent_at);
@@ -0,0 +1,12 @@
+-- Chosen by subscribers from their preference center. Issues for subscribers who do not want
+-- them straight away wait in the digest queue until their next digest is due
+ALTER TABLE subscriptions ADD COLUMN digest_frequency TEXT NOT NULL DEFAULT 'immediate';
+
+CREATE TABLE digest_queue(
+    subscriber_id uuid NOT NULL
+        REFERENCES subscriptions (id) ON DELETE CASCADE,
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    queued_at timestamptz NOT NULL,
+    PRIMARY KEY (subscriber_id, newsletter_issue_id)
+);
@@ -0,0 +1,64 @@
+use anyhow::Context;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+// Operations on personal data that must be traceable. Entries only reference the subscriber by
+// id, so they survive an erasure without keeping any personal data
+#[derive(Debug, Clone, Copy)]
+pub enum AuditAction {
+    DataExportRequested,
+    DataExported,
+    Erased,
+    Deleted,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::DataExportRequested => "data_export_requested",
+            AuditAction::DataExported => "data_exported",
+            AuditAction::Erased => "erased",
+            AuditAction::Deleted => "deleted",
+        }
+    }
+}
+
+// Who triggered the operation, either the subscriber through an emailed link or an admin
+#[derive(Debug)]
+pub enum AuditActor {
+    Subscriber,
+    Admin(Uuid),
+}
+
+impl std::fmt::Display for AuditActor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuditActor::Subscriber => write!(f, "subscriber"),
+            AuditActor::Admin(user_id) => write!(f, "admin:{}", user_id),
+        }
+    }
+}
+
+#[tracing::instrument(skip(db_transaction))]
+pub async fn record_audit_event(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    action: AuditAction,
+    actor: AuditActor,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO gdpr_audit_log (id, subscriber_id, action, actor, occurred_at)
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        Uuid::new_v4(),
+        subscriber_id,
+        action.as_str(),
+        actor.to_string()
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to record an audit event")?;
+
+    Ok(())
+}
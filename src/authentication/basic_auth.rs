@@ -0,0 +1,31 @@
+use actix_web::http::header::HeaderMap;
+use anyhow::Context;
+use base64::Engine;
+use secrecy::SecretString;
+
+use crate::authentication::Credentials;
+
+pub fn basic_authentication(headers: &HeaderMap) -> Result<Credentials, anyhow::Error> {
+    let header_value = headers
+        .get("Authorization")
+        .context("The 'Authorization' header was missing")?
+        .to_str()
+        .context("The 'Authorization' header was not a valid UTF8 string")?;
+    let base64_encoded_credentials = header_value
+        .strip_prefix("Basic ")
+        .context("The authorization scheme was not 'Basic'")?;
+    let decoded_credentials = base64::engine::general_purpose::STANDARD
+        .decode(base64_encoded_credentials)
+        .context("Failed to base64-decode 'Basic' credentials")?;
+    let decoded_credentials = String::from_utf8(decoded_credentials)
+        .context("The decoded credential string is not valid UTF8")?;
+
+    let (username, password) = decoded_credentials
+        .split_once(':')
+        .context("A password must be provided in 'Basic' auth")?;
+
+    Ok(Credentials {
+        username: username.to_string(),
+        password: SecretString::new(password.to_string()),
+    })
+}
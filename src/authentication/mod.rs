@@ -0,0 +1,7 @@
+mod basic_auth;
+mod middleware;
+mod password;
+
+pub use basic_auth::*;
+pub use middleware::*;
+pub use password::*;
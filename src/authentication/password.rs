@@ -0,0 +1,122 @@
+use anyhow::Context;
+use argon2::{
+    password_hash::SaltString, Algorithm, Argon2, Params, PasswordHash, PasswordHasher,
+    PasswordVerifier, Version,
+};
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{errors::AuthError, models::NewPassword, telemetry::spawn_blocking_with_tracing};
+
+pub struct Credentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
+// Verified against when the username does not exist, so that both failure paths take the same
+// amount of time and usernames cannot be enumerated through response timings
+const FALLBACK_PASSWORD_HASH: &str = "$argon2id$v=19$m=15000,t=2,p=1$\
+    gZiV/M1gPc22ElAH/Jh1Hw$\
+    CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno";
+
+#[tracing::instrument(name = "Validate credentials", skip(credentials, db_connection_pool))]
+pub async fn validate_credentials(
+    credentials: Credentials,
+    db_connection_pool: &PgPool,
+) -> Result<Uuid, AuthError> {
+    let mut user_id = None;
+    let mut expected_password_hash = SecretString::new(FALLBACK_PASSWORD_HASH.to_string());
+
+    if let Some((stored_user_id, stored_password_hash)) =
+        get_stored_credentials(&credentials.username, db_connection_pool).await?
+    {
+        user_id = Some(stored_user_id);
+        expected_password_hash = stored_password_hash;
+    }
+
+    spawn_blocking_with_tracing(move || {
+        verify_password_hash(expected_password_hash, credentials.password)
+    })
+    .await
+    .context("Failed to spawn blocking task")??;
+
+    user_id
+        .ok_or_else(|| anyhow::anyhow!("Unknown username"))
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(
+    name = "Verify password hash",
+    skip(expected_password_hash, password_candidate)
+)]
+fn verify_password_hash(
+    expected_password_hash: SecretString,
+    password_candidate: SecretString,
+) -> Result<(), AuthError> {
+    let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
+        .context("Failed to parse hash in PHC string format")?;
+
+    Argon2::default()
+        .verify_password(
+            password_candidate.expose_secret().as_bytes(),
+            &expected_password_hash,
+        )
+        .context("Invalid password")
+        .map_err(AuthError::InvalidCredentials)
+}
+
+#[tracing::instrument(name = "Get stored credentials", skip(username, db_connection_pool))]
+async fn get_stored_credentials(
+    username: &str,
+    db_connection_pool: &PgPool,
+) -> Result<Option<(Uuid, SecretString)>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id, password_hash FROM users WHERE username = $1"#,
+        username
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve stored credentials")?
+    .map(|row| (row.user_id, SecretString::new(row.password_hash)));
+
+    Ok(row)
+}
+
+pub fn compute_password_hash(password: SecretString) -> Result<SecretString, anyhow::Error> {
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let password_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(15000, 2, 1, None).context("Failed to build Argon2 parameters")?,
+    )
+    .hash_password(password.expose_secret().as_bytes(), &salt)
+    .context("Failed to hash password")?
+    .to_string();
+
+    Ok(SecretString::new(password_hash))
+}
+
+// Takes any executor so the update can be part of a larger transaction
+#[tracing::instrument(name = "Change password", skip(password, db_executor))]
+pub async fn change_password(
+    user_id: Uuid,
+    password: NewPassword,
+    db_executor: impl PgExecutor<'_>,
+) -> Result<(), anyhow::Error> {
+    let password_hash =
+        spawn_blocking_with_tracing(move || compute_password_hash(password.into_inner()))
+            .await?
+            .context("Failed to hash password")?;
+
+    sqlx::query!(
+        r#"UPDATE users SET password_hash = $1 WHERE user_id = $2"#,
+        password_hash.expose_secret(),
+        user_id
+    )
+    .execute(db_executor)
+    .await
+    .context("Failed to change user's password in the database")?;
+
+    Ok(())
+}
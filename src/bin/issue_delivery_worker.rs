@@ -0,0 +1,16 @@
+use rust_zero2prod::{configuration, issue_delivery_worker, telemetry};
+
+// Standalone newsletter delivery worker, to scale deliveries independently of the API
+#[tokio::main]
+async fn main() -> Result<(), anyhow::Error> {
+    let configuration = configuration::get_configuration().expect("Failed to read configuration");
+
+    let tracing_subsriber = telemetry::get_tracing_subscriber(
+        "issue_delivery_worker".into(),
+        "info".into(),
+        std::io::stdout,
+    );
+    telemetry::init_tracing_subscriber(tracing_subsriber);
+
+    issue_delivery_worker::run_worker_until_stopped(configuration).await
+}
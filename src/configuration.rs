@@ -1,8 +1,17 @@
+use std::{path::PathBuf, sync::Arc};
+
 use secrecy::{ExposeSecret, SecretString};
-use serde_aux::field_attributes::deserialize_number_from_string;
+use serde_aux::field_attributes::{
+    deserialize_number_from_string, deserialize_option_number_from_string,
+};
 use sqlx::postgres::{PgConnectOptions, PgSslMode};
 
-use crate::models::SubscriberEmail;
+use crate::{
+    email_client::{
+        EmailSender, FileEmailSender, PostmarkEmailClient, RetryPolicy, SmtpEmailSender, SmtpTls,
+    },
+    models::SubscriberEmail,
+};
 
 #[derive(serde::Deserialize)]
 pub struct Settings {
@@ -28,20 +37,148 @@ pub struct ApplicationSettings {
     pub port: u16,
     pub host: String,
     pub base_url: String,
+    pub hmac_secret: SecretString,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub password_reset_token_ttl_minutes: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub subscription_token_ttl_minutes: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub data_export_token_ttl_minutes: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub preferences_link_ttl_minutes: i64,
+    // When disabled, newsletter issues are only delivered by the standalone
+    // `issue_delivery_worker` binary
+    pub run_delivery_worker_in_process: bool,
+    pub subscription_rate_limit: SubscriptionRateLimitSettings,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SubscriptionRateLimitSettings {
+    // Header the reverse proxy puts the client address in, e.g. `X-Forwarded-For`. Only set it
+    // when the application cannot be reached without going through that proxy
+    pub trusted_proxy_header: Option<String>,
+    pub per_ip: TokenBucketSettings,
+    pub per_email: TokenBucketSettings,
+    // Hard limit on the confirmation, preference and data export emails an address receives,
+    // whatever the token buckets allow
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_confirmation_emails_per_day: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TokenBucketSettings {
+    // Requests allowed in a burst
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub capacity: u32,
+    // One more request is allowed every interval, up to the capacity
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub refill_interval_seconds: u64,
+}
+
+impl ApplicationSettings {
+    pub fn get_password_reset_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.password_reset_token_ttl_minutes)
+    }
+
+    pub fn get_subscription_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.subscription_token_ttl_minutes)
+    }
+
+    pub fn get_data_export_token_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.data_export_token_ttl_minutes)
+    }
+
+    pub fn get_preferences_link_ttl(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.preferences_link_ttl_minutes)
+    }
+}
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailProvider {
+    Postmark,
+    Smtp,
+    File,
 }
 
 #[derive(serde::Deserialize)]
 pub struct EmailClientSettings {
-    pub base_url: String,
+    pub provider: EmailProvider,
     pub sender_email: SubscriberEmail,
-    pub api_token: SecretString,
     pub timeout_ms: u64,
+    // Postmark settings, the only ones required when `provider` is `postmark`
+    pub base_url: String,
+    pub api_token: SecretString,
+    pub retry_policy: RetryPolicy,
+    // Basic auth credentials Postmark sends with its bounce and complaint webhooks
+    pub webhook_username: String,
+    pub webhook_password: SecretString,
+    pub smtp: Option<SmtpSettings>,
+    pub file: Option<FileSinkSettings>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(default, deserialize_with = "deserialize_option_number_from_string")]
+    pub port: Option<u16>,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<SecretString>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct FileSinkSettings {
+    pub directory: PathBuf,
 }
 
 impl EmailClientSettings {
     pub fn get_timeout(&self) -> std::time::Duration {
         std::time::Duration::from_millis(self.timeout_ms)
     }
+
+    pub fn client(&self) -> Arc<dyn EmailSender> {
+        match self.provider {
+            EmailProvider::Postmark => Arc::new(PostmarkEmailClient::new(
+                &self.base_url,
+                self.sender_email.clone(),
+                self.api_token.clone(),
+                self.get_timeout(),
+                self.retry_policy.clone(),
+            )),
+            EmailProvider::Smtp => {
+                let smtp = self
+                    .smtp
+                    .as_ref()
+                    .expect("Missing `email_client.smtp` settings for the smtp provider");
+                let credentials = match (&smtp.username, &smtp.password) {
+                    (Some(username), Some(password)) => Some((username.clone(), password.clone())),
+                    _ => None,
+                };
+                Arc::new(
+                    SmtpEmailSender::new(
+                        &smtp.host,
+                        smtp.port,
+                        smtp.tls,
+                        credentials,
+                        self.sender_email.clone(),
+                        self.get_timeout(),
+                    )
+                    .expect("Failed to build the SMTP email sender"),
+                )
+            }
+            EmailProvider::File => {
+                let file = self
+                    .file
+                    .as_ref()
+                    .expect("Missing `email_client.file` settings for the file provider");
+                Arc::new(
+                    FileEmailSender::new(&file.directory, self.sender_email.clone())
+                        .expect("Failed to build the file email sender"),
+                )
+            }
+        }
+    }
 }
 
 enum Environment {
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use anyhow::Context;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use super::{build_mime_message, EmailHeader, EmailSender};
+use crate::models::SubscriberEmail;
+
+// Writes every email as an .eml file in a directory instead of sending it, which is handy to
+// inspect emails during development
+pub struct FileEmailSender {
+    transport: AsyncFileTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+}
+
+impl FileEmailSender {
+    pub fn new(directory: &Path, sender: SubscriberEmail) -> Result<Self, anyhow::Error> {
+        std::fs::create_dir_all(directory).with_context(|| {
+            format!(
+                "Failed to create the email output directory {}",
+                directory.display()
+            )
+        })?;
+
+        Ok(Self {
+            transport: AsyncFileTransport::new(directory),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for FileEmailSender {
+    #[tracing::instrument(name = "Write email to file", skip_all, fields(recipient = %recipient))]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error> {
+        let message = build_mime_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        let message_id = self
+            .transport
+            .send(message)
+            .await
+            .context("Failed to write email to file")?;
+        tracing::info!(%message_id, "Email written to file");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::assert_ok;
+    use fake::{faker::internet::en::SafeEmail, Fake};
+    use uuid::Uuid;
+
+    use super::FileEmailSender;
+    use crate::{email_client::EmailSender, models::SubscriberEmail};
+
+    fn get_email_address() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_file_email_sender_writes_an_eml_file() {
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        let email_sender = FileEmailSender::new(&directory, get_email_address()).unwrap();
+        let recipient = get_email_address();
+
+        let send_email_result = email_sender
+            .send_email(&recipient, "Subject", "<p>Html body</p>", "Text body")
+            .await;
+        assert_ok!(send_email_result);
+
+        let files = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+
+        let content = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(content.contains(&format!("To: {}", recipient)));
+        assert!(content.contains("Subject: Subject"));
+        assert!(content.contains("Text body"));
+        assert!(content.contains("<p>Html body</p>"));
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+}
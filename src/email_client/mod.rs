@@ -0,0 +1,84 @@
+mod file;
+mod postmark;
+mod smtp;
+
+pub use file::FileEmailSender;
+pub use postmark::{PostmarkEmailClient, RetryPolicy};
+pub use smtp::{SmtpEmailSender, SmtpTls};
+
+use anyhow::Context;
+use lettre::{
+    message::{
+        header::{HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    Message,
+};
+
+use crate::models::SubscriberEmail;
+
+// Extra header added on top of the ones every backend sets, e.g. `List-Unsubscribe`
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "PascalCase")]
+pub struct EmailHeader {
+    pub name: &'static str,
+    pub value: String,
+}
+
+// Routes and workers only depend on this trait, the backend in use is picked through the
+// `email_client.provider` configuration key
+#[async_trait::async_trait]
+pub trait EmailSender: Send + Sync {
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error>;
+
+    async fn send_email(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+    ) -> Result<(), anyhow::Error> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+}
+
+// Builds the MIME message shared by the backends that do not go through an HTTP API
+fn build_mime_message(
+    sender: &SubscriberEmail,
+    recipient: &SubscriberEmail,
+    subject: &str,
+    html_content: &str,
+    text_content: &str,
+    headers: &[EmailHeader],
+) -> Result<Message, anyhow::Error> {
+    let from: Mailbox = sender
+        .as_ref()
+        .parse()
+        .context("Failed to parse the sender address")?;
+    let to: Mailbox = recipient
+        .as_ref()
+        .parse()
+        .context("Failed to parse the recipient address")?;
+
+    let mut message_builder = Message::builder().from(from).to(to).subject(subject);
+    for header in headers {
+        let name = HeaderName::new_from_ascii(header.name.to_string())
+            .with_context(|| format!("Invalid email header name {}", header.name))?;
+        message_builder = message_builder.raw_header(HeaderValue::new(name, header.value.clone()));
+    }
+
+    message_builder
+        .multipart(MultiPart::alternative_plain_html(
+            text_content.to_string(),
+            html_content.to_string(),
+        ))
+        .context("Failed to build the email message")
+}
@@ -0,0 +1,522 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::{header::HeaderMap, Client, StatusCode, Url};
+use secrecy::{ExposeSecret, SecretString};
+
+use super::{EmailHeader, EmailSender};
+use crate::models::SubscriberEmail;
+
+pub struct PostmarkEmailClient {
+    http_client: Client,
+    base_url: Url,
+    sender: SubscriberEmail,
+    api_token: SecretString,
+    retry_policy: RetryPolicy,
+}
+
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct RetryPolicy {
+    // Includes the first attempt, so 1 disables retries
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+    pub jitter: bool,
+}
+
+impl RetryPolicy {
+    pub fn no_retries() -> Self {
+        Self {
+            max_attempts: 1,
+            base_delay_ms: 0,
+            max_delay_ms: 0,
+            jitter: false,
+        }
+    }
+
+    fn get_max_delay(&self) -> Duration {
+        Duration::from_millis(self.max_delay_ms)
+    }
+
+    // Exponential backoff capped at the maximum delay. With jitter, a random delay between zero
+    // and the backoff is used instead, so that clients failing together do not retry together
+    fn get_backoff(&self, attempt: u32) -> Duration {
+        let backoff_ms = self
+            .base_delay_ms
+            .saturating_mul(2u64.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay_ms);
+
+        if self.jitter {
+            Duration::from_millis(rand::thread_rng().gen_range(0..=backoff_ms))
+        } else {
+            Duration::from_millis(backoff_ms)
+        }
+    }
+}
+
+// Rate limiting and server side failures are transient, any other error status means the request
+// itself is wrong and sending it again would fail the same way
+fn is_retryable_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+fn is_retryable_error(e: &reqwest::Error) -> bool {
+    e.is_timeout() || e.is_connect()
+}
+
+// Supports both forms of the header: a number of seconds and an HTTP date
+fn parse_retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let retry_at = chrono::DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    let delay = retry_at.with_timezone(&chrono::Utc) - chrono::Utc::now();
+    Some(delay.to_std().unwrap_or(Duration::ZERO))
+}
+
+#[derive(serde::Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct EmailRequestBody<'a> {
+    from: &'a str,
+    to: &'a str,
+    subject: &'a str,
+    html_body: &'a str,
+    text_body: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader],
+}
+
+impl PostmarkEmailClient {
+    pub fn new(
+        base_url: &str,
+        sender: SubscriberEmail,
+        api_token: SecretString,
+        timeout: std::time::Duration,
+        retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            http_client: Client::builder()
+                .timeout(timeout)
+                .build()
+                .expect("Failed to build HTTP client for email client."),
+            base_url: Url::parse(base_url).expect("Failed to parse email client's base url"),
+            sender,
+            api_token,
+            retry_policy,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for PostmarkEmailClient {
+    #[tracing::instrument(
+        name = "Send email through Postmark",
+        skip_all,
+        fields(recipient = %recipient)
+    )]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error> {
+        let url = self
+            .base_url
+            .join("/email")
+            .expect("Failed to parse email client's url");
+        let body = EmailRequestBody {
+            from: self.sender.as_ref(),
+            to: recipient.as_ref(),
+            subject,
+            html_body: html_content,
+            text_body: text_content,
+            headers,
+        };
+
+        let mut attempt = 1;
+        loop {
+            let outcome = self
+                .http_client
+                .post(url.clone())
+                .header("X-Postmark-Server-Token", self.api_token.expose_secret())
+                .json(&body)
+                .send()
+                .await;
+
+            let (error, retry_after) = match outcome {
+                Ok(response) if is_retryable_status(response.status()) => {
+                    let retry_after = parse_retry_after(response.headers());
+                    (response.error_for_status().unwrap_err(), retry_after)
+                }
+                Ok(response) => {
+                    response.error_for_status()?;
+                    return Ok(());
+                }
+                Err(e) if is_retryable_error(&e) => (e, None),
+                Err(e) => return Err(e.into()),
+            };
+
+            if attempt >= self.retry_policy.max_attempts {
+                return Err(error.into());
+            }
+            // Waiting longer than the maximum delay because the server asked for it would hold
+            // the caller for too long, so the error is surfaced instead
+            let delay = match retry_after {
+                Some(delay) if delay > self.retry_policy.get_max_delay() => {
+                    return Err(error.into())
+                }
+                Some(delay) => delay,
+                None => self.retry_policy.get_backoff(attempt),
+            };
+
+            tracing::warn!(
+                error.message = %error,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "Failed to send email, retrying"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use fake::{
+        faker::{
+            internet::en::SafeEmail,
+            lorem::en::{Paragraph, Sentence},
+        },
+        Fake, Faker,
+    };
+    use secrecy::Secret;
+    use wiremock::{
+        matchers::{any, header, header_exists, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    use super::{parse_retry_after, PostmarkEmailClient, RetryPolicy};
+    use crate::{email_client::EmailSender, models::SubscriberEmail};
+
+    fn get_email_subject() -> String {
+        Sentence(1..10).fake()
+    }
+    fn get_email_content() -> String {
+        Paragraph(1..10).fake()
+    }
+    fn get_email_address() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+    fn get_email_client(base_url: String) -> PostmarkEmailClient {
+        PostmarkEmailClient::new(
+            &base_url,
+            get_email_address(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            RetryPolicy::no_retries(),
+        )
+    }
+    fn get_retrying_email_client(base_url: String) -> PostmarkEmailClient {
+        PostmarkEmailClient::new(
+            &base_url,
+            get_email_address(),
+            Secret::new(Faker.fake()),
+            std::time::Duration::from_millis(200),
+            RetryPolicy {
+                max_attempts: 3,
+                base_delay_ms: 10,
+                max_delay_ms: 2000,
+                jitter: true,
+            },
+        )
+    }
+
+    struct EmailRequestBodyMatcher;
+
+    impl wiremock::Match for EmailRequestBodyMatcher {
+        fn matches(&self, request: &wiremock::Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+
+            if let Ok(body) = result {
+                body.get("From").is_some()
+                    && body.get("To").is_some()
+                    && body.get("Subject").is_some()
+                    && body.get("HtmlBody").is_some()
+                    && body.get("TextBody").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_email_client_fires_request_to_base_url() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_email_client(mock_server.uri());
+
+        Mock::given(header_exists("X-Postmark-Server-Token"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(EmailRequestBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let _ = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_email_client_send_function_returns_ok_if_response_is_200() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_ok!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_send_function_returns_err_if_response_is_500() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_err!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_send_function_returns_err_if_server_takes_more_than_10s_to_respond()
+    {
+        let mock_server = MockServer::start().await;
+        let email_client = get_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(20)))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_err!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_retries_server_errors_until_success() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_ok!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_gives_up_after_max_attempts() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_err!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_does_not_retry_validation_errors() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_err!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_retries_timeouts() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200).set_delay(std::time::Duration::from_secs(1)))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_ok!(send_email_result);
+    }
+
+    #[tokio::test]
+    async fn test_email_client_honours_retry_after_on_429() {
+        let mock_server = MockServer::start().await;
+        let email_client = get_retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let start = std::time::Instant::now();
+        let send_email_result = email_client
+            .send_email(
+                &get_email_address(),
+                &get_email_subject(),
+                &get_email_content(),
+                &get_email_content(),
+            )
+            .await;
+
+        assert_ok!(send_email_result);
+        assert!(start.elapsed() >= std::time::Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_retry_after_is_parsed_from_seconds_and_http_dates() {
+        let mut headers = reqwest::header::HeaderMap::new();
+        headers.insert("Retry-After", "120".parse().unwrap());
+        assert_eq!(
+            parse_retry_after(&headers),
+            Some(std::time::Duration::from_secs(120))
+        );
+
+        headers.insert(
+            "Retry-After",
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(parse_retry_after(&headers), Some(std::time::Duration::ZERO));
+
+        headers.insert("Retry-After", "soon".parse().unwrap());
+        assert_eq!(parse_retry_after(&headers), None);
+    }
+
+    #[test]
+    fn test_backoff_grows_exponentially_up_to_the_maximum_delay() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 10,
+            base_delay_ms: 100,
+            max_delay_ms: 500,
+            jitter: false,
+        };
+
+        assert_eq!(retry_policy.get_backoff(1).as_millis(), 100);
+        assert_eq!(retry_policy.get_backoff(2).as_millis(), 200);
+        assert_eq!(retry_policy.get_backoff(3).as_millis(), 400);
+        assert_eq!(retry_policy.get_backoff(4).as_millis(), 500);
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::Context;
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport,
+    Tokio1Executor,
+};
+use secrecy::{ExposeSecret, SecretString};
+
+use super::{build_mime_message, EmailHeader, EmailSender};
+use crate::models::SubscriberEmail;
+
+#[derive(serde::Deserialize, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    // Plain connection upgraded with STARTTLS, which is required to succeed
+    Starttls,
+    // TLS from the start of the connection
+    Tls,
+    // Unencrypted, only meant for local SMTP catchers
+    None,
+}
+
+pub struct SmtpEmailSender {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    sender: SubscriberEmail,
+}
+
+impl SmtpEmailSender {
+    pub fn new(
+        host: &str,
+        port: Option<u16>,
+        tls: SmtpTls,
+        credentials: Option<(String, SecretString)>,
+        sender: SubscriberEmail,
+        timeout: std::time::Duration,
+    ) -> Result<Self, anyhow::Error> {
+        let mut transport_builder = match tls {
+            SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)
+                .context("Failed to configure the SMTP STARTTLS relay")?,
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)
+                .context("Failed to configure the SMTP TLS relay")?,
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+        };
+        if let Some(port) = port {
+            transport_builder = transport_builder.port(port);
+        }
+        if let Some((username, password)) = credentials {
+            transport_builder = transport_builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+
+        Ok(Self {
+            transport: transport_builder.timeout(Some(timeout)).build(),
+            sender,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl EmailSender for SmtpEmailSender {
+    #[tracing::instrument(
+        name = "Send email through SMTP",
+        skip_all,
+        fields(recipient = %recipient)
+    )]
+    async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader],
+    ) -> Result<(), anyhow::Error> {
+        let message = build_mime_message(
+            &self.sender,
+            recipient,
+            subject,
+            html_content,
+            text_content,
+            headers,
+        )?;
+
+        self.transport
+            .send(message)
+            .await
+            .context("Failed to send email through SMTP")?;
+
+        Ok(())
+    }
+}
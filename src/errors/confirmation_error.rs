@@ -8,6 +8,8 @@ pub enum ConfirmationError {
     TokenError(String),
     #[error("There is no subscriber associated with the provided token")]
     UnknownToken,
+    #[error("The token has expired or has already been used")]
+    ExpiredToken,
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -17,6 +19,7 @@ impl ResponseError for ConfirmationError {
         match self {
             ConfirmationError::TokenError(_) => StatusCode::BAD_REQUEST,
             ConfirmationError::UnknownToken => StatusCode::UNAUTHORIZED,
+            ConfirmationError::ExpiredToken => StatusCode::GONE,
             ConfirmationError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
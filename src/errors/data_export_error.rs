@@ -0,0 +1,26 @@
+use actix_web::{http::StatusCode, ResponseError};
+
+use crate::errors::format_error_chain;
+
+#[derive(thiserror::Error)]
+pub enum DataExportError {
+    #[error("The link is invalid, has expired or has already been used")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for DataExportError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            DataExportError::InvalidToken => StatusCode::UNAUTHORIZED,
+            DataExportError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for DataExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        format_error_chain(self, f)
+    }
+}
@@ -1,9 +1,21 @@
+mod auth_error;
 mod confirmation_error;
+mod data_export_error;
 mod helpers;
+mod login_error;
 mod newsletter_error;
+mod preferences_error;
 mod subscribe_error;
+mod unsubscribe_error;
+mod webhook_error;
 
+pub use auth_error::*;
 pub use confirmation_error::*;
+pub use data_export_error::*;
 pub use helpers::*;
+pub use login_error::*;
 pub use newsletter_error::*;
+pub use preferences_error::*;
 pub use subscribe_error::*;
+pub use unsubscribe_error::*;
+pub use webhook_error::*;
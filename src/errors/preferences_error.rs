@@ -0,0 +1,26 @@
+use actix_web::{http::StatusCode, ResponseError};
+
+use crate::errors::format_error_chain;
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("The link is invalid or has expired")]
+    InvalidToken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ResponseError for PreferencesError {
+    fn status_code(&self) -> actix_web::http::StatusCode {
+        match self {
+            PreferencesError::InvalidToken => StatusCode::UNAUTHORIZED,
+            PreferencesError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        format_error_chain(self, f)
+    }
+}
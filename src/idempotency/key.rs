@@ -0,0 +1,49 @@
+const MAX_LENGTH: usize = 50;
+
+#[derive(Debug)]
+pub struct IdempotencyKey(String);
+
+impl IdempotencyKey {
+    pub fn parse(key: String) -> Result<IdempotencyKey, String> {
+        if key.trim().is_empty() {
+            return Err("The idempotency key cannot be empty".to_string());
+        }
+        if key.len() >= MAX_LENGTH {
+            return Err(format!(
+                "The idempotency key must be shorter than {} characters",
+                MAX_LENGTH
+            ));
+        }
+
+        Ok(Self(key))
+    }
+}
+
+impl AsRef<str> for IdempotencyKey {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::IdempotencyKey;
+
+    #[test]
+    fn test_empty_key_is_rejected() {
+        assert_err!(IdempotencyKey::parse("".to_string()));
+        assert_err!(IdempotencyKey::parse("  ".to_string()));
+    }
+
+    #[test]
+    fn test_too_long_key_is_rejected() {
+        assert_err!(IdempotencyKey::parse("a".repeat(50)));
+    }
+
+    #[test]
+    fn test_valid_key_is_accepted() {
+        assert_ok!(IdempotencyKey::parse("a".repeat(49)));
+    }
+}
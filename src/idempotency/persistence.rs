@@ -0,0 +1,149 @@
+use actix_web::{body::to_bytes, http::StatusCode, HttpResponse};
+use anyhow::Context;
+use chrono::Utc;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use super::IdempotencyKey;
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "header_pair")]
+struct HeaderPairRecord {
+    name: String,
+    value: Vec<u8>,
+}
+
+pub enum NextAction {
+    // The request has to be processed, and its response saved with `save_response` using the
+    // returned transaction
+    StartProcessing(Transaction<'static, Postgres>),
+    ReturnSavedResponse(HttpResponse),
+}
+
+// A concurrent request with the same key blocks on the insert until the first one commits, at
+// which point it finds the saved response instead of processing the request again
+#[tracing::instrument(
+    name = "Try processing idempotent request",
+    skip(db_connection_pool, idempotency_key)
+)]
+pub async fn try_processing(
+    db_connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let inserted_rows = sqlx::query!(
+        r#"
+        INSERT INTO idempotency (user_id, idempotency_key, created_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        Utc::now()
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to insert idempotency key")?
+    .rows_affected();
+
+    if inserted_rows > 0 {
+        return Ok(NextAction::StartProcessing(db_transaction));
+    }
+
+    let saved_response = get_saved_response(db_connection_pool, idempotency_key, user_id)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
+
+    Ok(NextAction::ReturnSavedResponse(saved_response))
+}
+
+#[tracing::instrument(name = "Get saved response", skip(db_connection_pool, idempotency_key))]
+pub async fn get_saved_response(
+    db_connection_pool: &PgPool,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<HttpResponse>, anyhow::Error> {
+    let saved_response = sqlx::query!(
+        r#"
+        SELECT
+            response_status_code as "response_status_code!",
+            response_headers as "response_headers!: Vec<HeaderPairRecord>",
+            response_body as "response_body!"
+        FROM idempotency
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref()
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve saved response")?;
+
+    let Some(saved_response) = saved_response else {
+        return Ok(None);
+    };
+
+    let status_code = StatusCode::from_u16(saved_response.response_status_code.try_into()?)?;
+    let mut response = HttpResponse::build(status_code);
+    for HeaderPairRecord { name, value } in saved_response.response_headers {
+        response.append_header((name, value));
+    }
+
+    Ok(Some(response.body(saved_response.response_body)))
+}
+
+#[tracing::instrument(
+    name = "Save response",
+    skip(db_transaction, idempotency_key, http_response)
+)]
+pub async fn save_response(
+    mut db_transaction: Transaction<'static, Postgres>,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: HttpResponse,
+) -> Result<HttpResponse, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+    // The body of the responses we store is small and already in memory, so buffering it does
+    // not cost anything
+    let body = to_bytes(body).await.map_err(|e| anyhow::anyhow!("{}", e))?;
+    let status_code = response_head.status().as_u16() as i16;
+    let headers = response_head
+        .headers()
+        .iter()
+        .map(|(name, value)| HeaderPairRecord {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect::<Vec<_>>();
+
+    sqlx::query_unchecked!(
+        r#"
+        UPDATE idempotency
+        SET
+            response_status_code = $3,
+            response_headers = $4,
+            response_body = $5
+        WHERE user_id = $1 AND idempotency_key = $2
+        "#,
+        user_id,
+        idempotency_key.as_ref(),
+        status_code,
+        headers,
+        body.as_ref()
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to save response")?;
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to save the response")?;
+
+    Ok(response_head.set_body(body).map_into_boxed_body())
+}
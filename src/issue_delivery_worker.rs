@@ -0,0 +1,626 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::Context;
+use askama_actix::Template;
+use secrecy::SecretString;
+use sqlx::{PgPool, Postgres, Transaction};
+use tracing::{field::display, Span};
+use uuid::Uuid;
+
+use crate::{
+    configuration::Settings,
+    email_client::{EmailHeader, EmailSender},
+    issues::body_content,
+    models::{DigestFrequency, SubscriberEmail},
+    personalization::{personalize_html, personalize_text, Recipient},
+    routes::{archive_link, click_link, open_pixel_link, unsubscribe_link},
+    startup::get_db_connection_pool,
+    suppressions::is_suppressed,
+    templates::{DigestEmailTemplate, DigestIssue},
+    tracking::{
+        create_click_token, create_open_token, inject_open_pixel, rewrite_html_links,
+        rewrite_text_links,
+    },
+};
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+struct ConfirmedSubscriber {
+    id: Uuid,
+    name: String,
+    unsubscribe_token: String,
+}
+
+struct NewsletterIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+    slug: Option<String>,
+    subscribers_only: bool,
+    track_opens: bool,
+}
+
+struct IssueContent {
+    title: String,
+    html_content: String,
+    text_content: String,
+}
+
+struct DigestSubscriber {
+    id: Uuid,
+    email: String,
+    digest_frequency: String,
+}
+
+pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+    let db_connection_pool = get_db_connection_pool(&configuration.database);
+    let email_client = configuration.email_client.client();
+
+    worker_loop(
+        db_connection_pool,
+        email_client,
+        configuration.application.base_url,
+        configuration.application.hmac_secret,
+    )
+    .await
+}
+
+async fn worker_loop(
+    db_connection_pool: PgPool,
+    email_client: Arc<dyn EmailSender>,
+    application_base_url: String,
+    hmac_secret: SecretString,
+) -> Result<(), anyhow::Error> {
+    loop {
+        match try_execute_task(
+            &db_connection_pool,
+            email_client.as_ref(),
+            &application_base_url,
+            &hmac_secret,
+        )
+        .await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => {}
+            Err(_) => {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => continue,
+        }
+        // Digests are only sent once single deliveries have caught up
+        match try_execute_digest_task(
+            &db_connection_pool,
+            email_client.as_ref(),
+            &application_base_url,
+            &hmac_secret,
+        )
+        .await
+        {
+            Ok(ExecutionOutcome::EmptyQueue) => tokio::time::sleep(Duration::from_secs(10)).await,
+            Err(_) => tokio::time::sleep(Duration::from_secs(1)).await,
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+}
+
+// Delivers the newsletter issue to a single subscriber. The task is removed from the queue even
+// when sending fails, so that one bad address cannot block the delivery to everybody else
+#[tracing::instrument(
+    skip_all,
+    fields(newsletter_issue_id = tracing::field::Empty, subscriber_email = tracing::field::Empty),
+    err
+)]
+pub async fn try_execute_task(
+    db_connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    application_base_url: &str,
+    hmac_secret: &SecretString,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut db_transaction, newsletter_issue_id, subscriber_email)) =
+        dequeue_task(db_connection_pool).await?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current()
+        .record("newsletter_issue_id", display(newsletter_issue_id))
+        .record("subscriber_email", display(&subscriber_email));
+
+    // Subscribers who left after the issue was published must not receive it
+    let Some(subscriber) =
+        get_confirmed_subscriber(db_connection_pool, newsletter_issue_id, &subscriber_email)
+            .await?
+    else {
+        tracing::info!("Skipping a subscriber that is no longer confirmed");
+        delete_task(db_transaction, newsletter_issue_id, &subscriber_email).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    };
+    if is_suppressed(db_connection_pool, &subscriber_email).await? {
+        tracing::info!("Skipping a suppressed subscriber");
+        delete_task(db_transaction, newsletter_issue_id, &subscriber_email).await?;
+        return Ok(ExecutionOutcome::TaskCompleted);
+    }
+
+    let outcome = match SubscriberEmail::parse(subscriber_email.clone()) {
+        Ok(email) => {
+            let issue = get_issue(db_connection_pool, newsletter_issue_id).await?;
+            let content = personalize_issue(
+                &issue,
+                newsletter_issue_id,
+                &subscriber,
+                application_base_url,
+                hmac_secret,
+            );
+            match email_client
+                .send_email_with_headers(
+                    &email,
+                    &content.title,
+                    &content.html_content,
+                    &content.text_content,
+                    &list_unsubscribe_headers(application_base_url, &subscriber),
+                )
+                .await
+            {
+                Ok(()) => "delivered",
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to deliver issue to a confirmed subscriber. Skipping."
+                    );
+                    "failed"
+                }
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a confirmed subscriber. Their stored contact details are invalid"
+            );
+            "failed"
+        }
+    };
+
+    record_delivery(
+        &mut db_transaction,
+        newsletter_issue_id,
+        subscriber.id,
+        outcome,
+    )
+    .await?;
+    delete_task(db_transaction, newsletter_issue_id, &subscriber_email).await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+// Sends a subscriber every issue queued for their digest, once the oldest of them has waited a
+// full period. Like single deliveries, the queue is emptied even when sending fails
+#[tracing::instrument(skip_all, fields(subscriber_email = tracing::field::Empty), err)]
+pub async fn try_execute_digest_task(
+    db_connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    application_base_url: &str,
+    hmac_secret: &SecretString,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((mut db_transaction, digest_subscriber)) = dequeue_digest(db_connection_pool).await?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    Span::current().record("subscriber_email", display(&digest_subscriber.email));
+
+    let newsletter_issue_ids =
+        get_digest_issue_ids(&mut db_transaction, digest_subscriber.id).await?;
+    let mut subscriber = None;
+    let mut issues = vec![];
+    if is_suppressed(db_connection_pool, &digest_subscriber.email).await? {
+        tracing::info!("Skipping the digest of a suppressed subscriber");
+    } else {
+        for newsletter_issue_id in newsletter_issue_ids.iter().copied() {
+            // Issues of lists the subscriber left since they were published are dropped
+            let Some(confirmed_subscriber) = get_confirmed_subscriber(
+                db_connection_pool,
+                newsletter_issue_id,
+                &digest_subscriber.email,
+            )
+            .await?
+            else {
+                continue;
+            };
+            let issue = get_issue(db_connection_pool, newsletter_issue_id).await?;
+            issues.push((newsletter_issue_id, issue));
+            subscriber = Some(confirmed_subscriber);
+        }
+    }
+
+    match subscriber {
+        Some(subscriber) => {
+            let outcome = send_digest(
+                email_client,
+                &digest_subscriber,
+                &subscriber,
+                &issues,
+                application_base_url,
+                hmac_secret,
+            )
+            .await;
+            for (newsletter_issue_id, _) in &issues {
+                record_delivery(
+                    &mut db_transaction,
+                    *newsletter_issue_id,
+                    subscriber.id,
+                    outcome,
+                )
+                .await?;
+            }
+        }
+        None => tracing::info!("Skipping a digest with nothing left to send"),
+    }
+    delete_digest_items(db_transaction, digest_subscriber.id, &newsletter_issue_ids).await?;
+
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+async fn send_digest(
+    email_client: &dyn EmailSender,
+    digest_subscriber: &DigestSubscriber,
+    subscriber: &ConfirmedSubscriber,
+    issues: &[(Uuid, NewsletterIssue)],
+    application_base_url: &str,
+    hmac_secret: &SecretString,
+) -> &'static str {
+    let email = match SubscriberEmail::parse(digest_subscriber.email.clone()) {
+        Ok(email) => email,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Skipping a confirmed subscriber. Their stored contact details are invalid"
+            );
+            return "failed";
+        }
+    };
+    let contents: Vec<_> = issues
+        .iter()
+        .map(|(newsletter_issue_id, issue)| {
+            personalize_issue(
+                issue,
+                *newsletter_issue_id,
+                subscriber,
+                application_base_url,
+                hmac_secret,
+            )
+        })
+        .collect();
+    let digest_issues: Vec<_> = contents
+        .iter()
+        .map(|content| DigestIssue {
+            title: content.title.clone(),
+            body: body_content(&content.html_content).to_owned(),
+        })
+        .collect();
+    let html_content = DigestEmailTemplate {
+        issues: &digest_issues,
+    }
+    .render()
+    .expect("Failed to render html for digest email");
+    let text_content = contents
+        .iter()
+        .map(|content| format!("{}\n\n{}", content.title, content.text_content))
+        .collect::<Vec<_>>()
+        .join("\n\n----\n\n");
+    // Subscribers switching back to immediate delivery still get what was queued in one go
+    let subject = match DigestFrequency::parse(digest_subscriber.digest_frequency.clone()) {
+        Ok(DigestFrequency::Daily) => "Your daily digest",
+        Ok(DigestFrequency::Weekly) => "Your weekly digest",
+        _ => "Your digest",
+    };
+
+    match email_client
+        .send_email_with_headers(
+            &email,
+            subject,
+            &html_content,
+            &text_content,
+            &list_unsubscribe_headers(application_base_url, subscriber),
+        )
+        .await
+    {
+        Ok(()) => "delivered",
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to deliver a digest to a confirmed subscriber. Skipping."
+            );
+            "failed"
+        }
+    }
+}
+
+// Fills in the subscriber's details, sends links through the click tracker and, when the issue
+// asks for it, adds the open pixel
+fn personalize_issue(
+    issue: &NewsletterIssue,
+    newsletter_issue_id: Uuid,
+    subscriber: &ConfirmedSubscriber,
+    application_base_url: &str,
+    hmac_secret: &SecretString,
+) -> IssueContent {
+    let unsubscribe_url = unsubscribe_link(application_base_url, &subscriber.unsubscribe_token);
+    let archive_url = archive_link(
+        application_base_url,
+        issue.slug.as_deref().filter(|_| !issue.subscribers_only),
+    );
+    let recipient = Recipient {
+        name: &subscriber.name,
+        unsubscribe_url: &unsubscribe_url,
+        archive_url: &archive_url,
+    };
+    // The unsubscribe link is left alone, unsubscribing must not depend on the tracker
+    let track_click = |url: &str| {
+        (url != unsubscribe_url).then(|| {
+            click_link(
+                application_base_url,
+                &create_click_token(hmac_secret, newsletter_issue_id, subscriber.id, url),
+            )
+        })
+    };
+    let mut html_content = rewrite_html_links(
+        &personalize_html(&issue.html_content, &recipient),
+        track_click,
+    );
+    let text_content = rewrite_text_links(
+        &personalize_text(&issue.text_content, &recipient),
+        track_click,
+    );
+    if issue.track_opens {
+        let open_token = create_open_token(hmac_secret, newsletter_issue_id, subscriber.id);
+        html_content = inject_open_pixel(
+            &html_content,
+            &open_pixel_link(application_base_url, &open_token),
+        );
+    }
+
+    IssueContent {
+        title: personalize_text(&issue.title, &recipient),
+        html_content,
+        text_content,
+    }
+}
+
+fn list_unsubscribe_headers(
+    application_base_url: &str,
+    subscriber: &ConfirmedSubscriber,
+) -> [EmailHeader; 2] {
+    [
+        EmailHeader {
+            name: "List-Unsubscribe",
+            value: format!(
+                "<{}>",
+                unsubscribe_link(application_base_url, &subscriber.unsubscribe_token)
+            ),
+        },
+        EmailHeader {
+            name: "List-Unsubscribe-Post",
+            value: "List-Unsubscribe=One-Click".into(),
+        },
+    ]
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscriber(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+) -> Result<Option<ConfirmedSubscriber>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        ConfirmedSubscriber,
+        r#"
+        SELECT s.id, s.name, s.unsubscribe_token
+        FROM subscriptions s
+        WHERE s.email = $1 AND s.status = 'confirmed' AND EXISTS (
+            SELECT 1
+            FROM list_memberships m
+            JOIN newsletter_issue_lists l ON l.list_id = m.list_id
+            WHERE m.subscriber_id = s.id
+                AND m.status = 'confirmed'
+                AND l.newsletter_issue_id = $2
+        )
+        "#,
+        subscriber_email,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the confirmed subscriber")?;
+
+    Ok(subscriber)
+}
+
+// The delivery history is exposed to subscribers through their data export
+#[tracing::instrument(skip(db_transaction))]
+async fn record_delivery(
+    db_transaction: &mut Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    outcome: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_deliveries
+            (newsletter_issue_id, subscriber_id, outcome, attempted_at)
+        VALUES ($1, $2, $3, now())
+        "#,
+        newsletter_issue_id,
+        subscriber_id,
+        outcome
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to record a delivery attempt")?;
+
+    Ok(())
+}
+
+// The returned transaction holds a lock on the task row, so concurrent workers skip it
+#[tracing::instrument(skip_all)]
+async fn dequeue_task(
+    db_connection_pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, Uuid, String)>, anyhow::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let task = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id, subscriber_email
+        FROM issue_delivery_queue
+        FOR UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to dequeue a delivery task")?;
+
+    Ok(task.map(|t| (db_transaction, t.newsletter_issue_id, t.subscriber_email)))
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    mut db_transaction: Transaction<'static, Postgres>,
+    newsletter_issue_id: Uuid,
+    subscriber_email: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM issue_delivery_queue
+        WHERE newsletter_issue_id = $1 AND subscriber_email = $2
+        "#,
+        newsletter_issue_id,
+        subscriber_email
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete a delivery task")?;
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to delete a delivery task")?;
+
+    Ok(())
+}
+
+// A subscriber's digest is due once their oldest queued issue has waited a full period. The
+// returned transaction holds a lock on the subscriber, so concurrent workers skip their digest
+#[tracing::instrument(skip_all)]
+async fn dequeue_digest(
+    db_connection_pool: &PgPool,
+) -> Result<Option<(Transaction<'static, Postgres>, DigestSubscriber)>, anyhow::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let digest_subscriber = sqlx::query_as!(
+        DigestSubscriber,
+        r#"
+        SELECT s.id, s.email, s.digest_frequency
+        FROM subscriptions s
+        WHERE EXISTS (
+            SELECT 1
+            FROM digest_queue d
+            WHERE d.subscriber_id = s.id
+                AND d.queued_at <= now() - CASE s.digest_frequency
+                    WHEN 'daily' THEN interval '1 day'
+                    WHEN 'weekly' THEN interval '7 days'
+                    ELSE interval '0'
+                END
+        )
+        FOR NO KEY UPDATE
+        SKIP LOCKED
+        LIMIT 1
+        "#,
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to dequeue a digest")?;
+
+    Ok(digest_subscriber.map(|s| (db_transaction, s)))
+}
+
+#[tracing::instrument(skip(db_transaction))]
+async fn get_digest_issue_ids(
+    db_transaction: &mut Transaction<'static, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<Uuid>, anyhow::Error> {
+    let newsletter_issue_ids = sqlx::query_scalar!(
+        r#"
+        SELECT newsletter_issue_id
+        FROM digest_queue
+        WHERE subscriber_id = $1
+        ORDER BY queued_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the issues queued for a digest")?;
+
+    Ok(newsletter_issue_ids)
+}
+
+// Only the issues read when the digest was dequeued are removed, anything published since then
+// waits for the next digest
+#[tracing::instrument(skip(db_transaction))]
+async fn delete_digest_items(
+    mut db_transaction: Transaction<'static, Postgres>,
+    subscriber_id: Uuid,
+    newsletter_issue_ids: &[Uuid],
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM digest_queue
+        WHERE subscriber_id = $1 AND newsletter_issue_id = ANY($2)
+        "#,
+        subscriber_id,
+        newsletter_issue_ids
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the issues queued for a digest")?;
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to delete a digest")?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issue(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+) -> Result<NewsletterIssue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT title, text_content, html_content, slug, subscribers_only, track_opens
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(db_connection_pool)
+    .await
+    .context("Failed to retrieve the newsletter issue")?;
+
+    Ok(issue)
+}
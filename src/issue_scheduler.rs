@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use sqlx::PgPool;
+
+use crate::{issues::publish_issue, routes::delete_expired_subscription_tokens};
+
+// Expired confirmation tokens are cleaned up along the way
+pub async fn run_scheduler_until_stopped(
+    db_connection_pool: PgPool,
+    subscription_token_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    loop {
+        // Errors are already logged by the instrumentation, the next run tries again
+        let _ = publish_due_issues(&db_connection_pool).await;
+        let _ =
+            delete_expired_subscription_tokens(&db_connection_pool, subscription_token_ttl).await;
+        tokio::time::sleep(Duration::from_secs(10)).await;
+    }
+}
+
+// Publishes every scheduled issue whose send time has passed and returns how many there were.
+// Each issue gets its own transaction, so one that fails to publish only holds back itself: it is
+// left scheduled and tried again on the next run. Locked rows are skipped, so concurrent
+// schedulers never publish an issue twice, and an issue being edited or cancelled is picked up on
+// the next run if it is still scheduled
+#[tracing::instrument(skip_all, err)]
+pub async fn publish_due_issues(db_connection_pool: &PgPool) -> Result<usize, anyhow::Error> {
+    let mut published = 0;
+    let mut failed = vec![];
+    loop {
+        let mut db_transaction = db_connection_pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?;
+        let due_issue = sqlx::query!(
+            r#"
+            SELECT newsletter_issue_id
+            FROM newsletter_issues
+            WHERE status = 'scheduled'
+                AND send_at <= now()
+                AND newsletter_issue_id <> ALL($1)
+            ORDER BY send_at
+            FOR UPDATE
+            SKIP LOCKED
+            LIMIT 1
+            "#,
+            &failed
+        )
+        .fetch_optional(&mut *db_transaction)
+        .await
+        .context("Failed to retrieve a scheduled issue that is due")?;
+        let Some(due_issue) = due_issue else {
+            return Ok(published);
+        };
+
+        let newsletter_issue_id = due_issue.newsletter_issue_id;
+        tracing::info!(%newsletter_issue_id, "Publishing a scheduled issue");
+        let outcome = match publish_issue(&mut db_transaction, newsletter_issue_id).await {
+            Ok(()) => db_transaction
+                .commit()
+                .await
+                .context("Failed to commit the SQL transaction to publish a scheduled issue"),
+            Err(e) => Err(e),
+        };
+        match outcome {
+            Ok(()) => published += 1,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    %newsletter_issue_id,
+                    "Failed to publish a scheduled issue"
+                );
+                failed.push(newsletter_issue_id);
+            }
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::models::IssueSlug;
+
+pub struct NewIssue<'a> {
+    pub title: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub author_id: Uuid,
+    pub subscribers_only: bool,
+    pub track_opens: bool,
+    // `draft`, `scheduled` or `published`. Published issues still need `publish_issue` to get
+    // their slug and be queued for delivery
+    pub status: &'a str,
+    pub send_at: Option<DateTime<Utc>>,
+}
+
+// Issues written in Markdown, and possibly some written in HTML, are stored as a complete
+// document. Pages and emails that embed issues in their own document only keep what is inside
+// the body
+pub fn body_content(html: &str) -> &str {
+    let lowercase_html = html.to_ascii_lowercase();
+    let Some(body_start) = lowercase_html.find("<body") else {
+        return html;
+    };
+    let Some(tag_length) = lowercase_html[body_start..].find('>') else {
+        return html;
+    };
+    let content_start = body_start + tag_length + 1;
+    let content_end = lowercase_html[content_start..]
+        .rfind("</body>")
+        .map_or(html.len(), |offset| content_start + offset);
+    &html[content_start..content_end]
+}
+
+#[tracing::instrument(name = "Insert newsletter issue", skip_all)]
+pub async fn insert_issue(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    issue: &NewIssue<'_>,
+) -> Result<Uuid, anyhow::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, author_id,
+            subscribers_only, track_opens, status, send_at, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, now())
+        "#,
+        newsletter_issue_id,
+        issue.title,
+        issue.text_content,
+        issue.html_content,
+        issue.author_id,
+        issue.subscribers_only,
+        issue.track_opens,
+        issue.status,
+        issue.send_at
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to store newsletter issue details")?;
+
+    Ok(newsletter_issue_id)
+}
+
+// Replaces the lists an issue is sent to
+#[tracing::instrument(name = "Attach newsletter issue to lists", skip(db_transaction))]
+pub async fn set_issue_lists(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"DELETE FROM newsletter_issue_lists WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to detach the newsletter issue from its lists")?;
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id)
+        SELECT DISTINCT $1::uuid, list_id FROM UNNEST($2::uuid[]) AS list_id
+        "#,
+        newsletter_issue_id,
+        list_ids
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to attach the newsletter issue to its lists")?;
+
+    Ok(())
+}
+
+// Gives the issue its archive slug and publication date, then queues it for delivery. Shared by
+// issues published straight away and scheduled issues reaching their send time
+#[tracing::instrument(name = "Publish newsletter issue", skip(db_transaction))]
+pub async fn publish_issue(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    let title = sqlx::query!(
+        r#"SELECT title FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_one(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the newsletter issue to publish")?
+    .title;
+    let slug = generate_issue_slug(db_transaction, &title).await?;
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET status = 'published', published_at = now(), slug = $2
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        slug.as_ref()
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to mark the newsletter issue as published")?;
+
+    enqueue_delivery_tasks(db_transaction, newsletter_issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?;
+
+    Ok(())
+}
+
+// Arbitrary key of the advisory lock taken while picking a slug
+const ISSUE_SLUG_LOCK_KEY: i64 = 0x736c7567;
+
+// Issues sharing a title get a numbered slug, so that archive URLs never change once published.
+// Publishing transactions pick their slugs one at a time, otherwise two issues with the same
+// title published together would both see the same slug as free. The lock is released when the
+// transaction ends
+#[tracing::instrument(name = "Generate newsletter issue slug", skip(db_transaction))]
+async fn generate_issue_slug(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+) -> Result<IssueSlug, anyhow::Error> {
+    sqlx::query!("SELECT pg_advisory_xact_lock($1)", ISSUE_SLUG_LOCK_KEY)
+        .execute(&mut **db_transaction)
+        .await
+        .context("Failed to lock the issue slugs")?;
+
+    let base_slug = IssueSlug::from_title(title);
+    let mut slug = base_slug.clone();
+    for suffix in 2.. {
+        let is_taken = sqlx::query!(
+            r#"SELECT EXISTS(SELECT 1 FROM newsletter_issues WHERE slug = $1) AS "is_taken!""#,
+            slug.as_ref()
+        )
+        .fetch_one(&mut **db_transaction)
+        .await
+        .context("Failed to check whether an issue slug is taken")?
+        .is_taken;
+        if !is_taken {
+            break;
+        }
+        slug = base_slug.with_suffix(suffix);
+    }
+
+    Ok(slug)
+}
+
+// Every subscriber confirmed on one of the target lists at publishing time gets one task,
+// consumed by the delivery worker. Members of several target lists only get the issue once.
+// Subscribers who chose a digest frequency get the issue queued for their next digest instead
+#[tracing::instrument(name = "Enqueue delivery tasks", skip(db_transaction))]
+async fn enqueue_delivery_tasks(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (newsletter_issue_id, subscriber_email)
+        SELECT DISTINCT $1::uuid, s.email
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        JOIN newsletter_issue_lists l ON l.list_id = m.list_id
+        WHERE s.status = 'confirmed'
+            AND s.digest_frequency = 'immediate'
+            AND m.status = 'confirmed'
+            AND l.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(&mut **db_transaction)
+    .await?;
+    sqlx::query!(
+        r#"
+        INSERT INTO digest_queue (subscriber_id, newsletter_issue_id, queued_at)
+        SELECT DISTINCT s.id, $1::uuid, now()
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        JOIN newsletter_issue_lists l ON l.list_id = m.list_id
+        WHERE s.status = 'confirmed'
+            AND s.digest_frequency <> 'immediate'
+            AND m.status = 'confirmed'
+            AND l.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+    )
+    .execute(&mut **db_transaction)
+    .await?;
+
+    Ok(())
+}
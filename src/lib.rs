@@ -1,8 +1,23 @@
+pub mod audit;
+pub mod authentication;
 pub mod configuration;
 pub mod email_client;
 pub mod errors;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod issues;
+pub mod lists;
+pub mod markdown;
 pub mod models;
+pub mod personalization;
+pub mod rate_limit;
 pub mod routes;
+pub mod session;
+pub mod signing;
 pub mod startup;
+pub mod suppressions;
 pub mod telemetry;
 pub mod templates;
+pub mod tracking;
+pub mod utils;
@@ -0,0 +1,124 @@
+use anyhow::Context;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::models::ListSlug;
+
+// Lists targeted by an issue, the default list when none are given
+pub fn parse_list_slugs(lists: Option<Vec<String>>) -> Result<Vec<ListSlug>, String> {
+    let Some(lists) = lists else {
+        return Ok(vec![ListSlug::default_list()]);
+    };
+    if lists.is_empty() {
+        return Err("At least one list must be targeted".to_string());
+    }
+
+    lists.into_iter().map(ListSlug::parse).collect()
+}
+
+// Returns the ids of the given lists, or the first slug that does not match any list
+#[tracing::instrument(skip(db_transaction))]
+pub async fn get_list_ids(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    slugs: &[ListSlug],
+) -> Result<Result<Vec<Uuid>, ListSlug>, anyhow::Error> {
+    let mut list_ids = Vec::with_capacity(slugs.len());
+    for slug in slugs {
+        let list = sqlx::query!(r#"SELECT id FROM lists WHERE slug = $1"#, slug.as_ref())
+            .fetch_optional(&mut **db_transaction)
+            .await
+            .context("Failed to look up a list by slug")?;
+        match list {
+            Some(list) => list_ids.push(list.id),
+            None => return Ok(Err(slug.clone())),
+        }
+    }
+
+    Ok(Ok(list_ids))
+}
+
+#[tracing::instrument(skip(db_transaction))]
+pub async fn get_membership_status(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+) -> Result<Option<String>, anyhow::Error> {
+    let membership = sqlx::query!(
+        r#"
+        SELECT status FROM list_memberships
+        WHERE subscriber_id = $1 AND list_id = $2
+        "#,
+        subscriber_id,
+        list_id
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve a list membership")?;
+
+    Ok(membership.map(|m| m.status))
+}
+
+#[tracing::instrument(skip(db_transaction))]
+pub async fn set_membership_status(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    list_id: Uuid,
+    status: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO list_memberships (subscriber_id, list_id, status, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (subscriber_id, list_id) DO UPDATE SET status = EXCLUDED.status
+        "#,
+        subscriber_id,
+        list_id,
+        status
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to store a list membership")?;
+
+    Ok(())
+}
+
+// Memberships requested before the address was confirmed become active with the confirmation
+#[tracing::instrument(skip(db_transaction))]
+pub async fn confirm_pending_memberships(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'confirmed'
+        WHERE subscriber_id = $1 AND status = 'pending_confirmation'
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to confirm pending list memberships")?;
+
+    Ok(())
+}
+
+// Leaving through an unsubscribe link removes the address from every list, so that subscribing
+// again to one list does not silently restore the others
+#[tracing::instrument(skip(db_transaction))]
+pub async fn leave_all_lists(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE list_memberships SET status = 'unsubscribed'
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to leave every list")?;
+
+    Ok(())
+}
@@ -1,7 +1,11 @@
-use rust_zero2prod::{configuration, startup, telemetry};
+use std::fmt::{Debug, Display};
+
+use tokio::task::JoinError;
+
+use rust_zero2prod::{configuration, issue_delivery_worker, issue_scheduler, startup, telemetry};
 
 #[actix_web::main]
-async fn main() -> Result<(), std::io::Error> {
+async fn main() -> Result<(), anyhow::Error> {
     let configuration = configuration::get_configuration().expect("Failed to read configuration");
 
     let tracing_subsriber =
@@ -9,7 +13,54 @@ async fn main() -> Result<(), std::io::Error> {
     telemetry::init_tracing_subscriber(tracing_subsriber);
 
     let server = startup::Application::build_application(&configuration).await?;
-    server.run_server().await?;
+    let server_task = tokio::spawn(server.run_server());
+    // Scheduled issues are published into the delivery queue, so the scheduler runs in the
+    // application even when deliveries are handled by the standalone worker
+    let scheduler_task = tokio::spawn(issue_scheduler::run_scheduler_until_stopped(
+        startup::get_db_connection_pool(&configuration.database),
+        configuration.application.get_subscription_token_ttl(),
+    ));
+
+    if !configuration.application.run_delivery_worker_in_process {
+        tokio::select! {
+            outcome = server_task => report_exit("API", outcome),
+            outcome = scheduler_task => report_exit("Issue scheduler", outcome),
+        };
+        return Ok(());
+    }
+
+    let worker_task = tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
+        configuration,
+    ));
+    tokio::select! {
+        outcome = server_task => report_exit("API", outcome),
+        outcome = scheduler_task => report_exit("Issue scheduler", outcome),
+        outcome = worker_task => report_exit("Background worker", outcome),
+    };
 
     Ok(())
 }
+
+fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} task failed to complete",
+                task_name
+            )
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use askama_actix::Template;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
+
+use crate::{
+    personalization::{markers_to_placeholders, placeholders_to_markers},
+    templates::NewsletterIssueTemplate,
+};
+
+fn parse(markdown: &str) -> Parser<'_> {
+    Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH)
+}
+
+// Markdown lets authors write raw HTML, so the output goes through the sanitizer before being
+// wrapped in the newsletter layout. Placeholders are kept for personalization, including inside
+// link destinations
+pub fn render_html(markdown: &str) -> String {
+    let mut body = String::new();
+    pulldown_cmark::html::push_html(&mut body, parse(&placeholders_to_markers(markdown)));
+    let body = markers_to_placeholders(&ammonia::clean(&body));
+
+    NewsletterIssueTemplate { body: &body }
+        .render()
+        .expect("Failed to render html for newsletter issue")
+}
+
+// Links are replaced by numbered references listed at the end, raw HTML is dropped
+pub fn render_plain_text(markdown: &str) -> String {
+    let mut writer = PlainTextWriter::default();
+    for event in parse(&placeholders_to_markers(markdown)) {
+        writer.handle(event);
+    }
+    markers_to_placeholders(&writer.finish())
+}
+
+#[derive(Default)]
+struct PlainTextWriter {
+    output: String,
+    links: Vec<String>,
+    // Destination of every link being written and where its text starts in the output
+    open_links: Vec<(String, usize)>,
+    // Next number of every open list, `None` for bullet lists
+    lists: Vec<Option<u64>>,
+    quote_depth: usize,
+    in_code_block: bool,
+    at_line_start: bool,
+    after_item_marker: bool,
+}
+
+impl PlainTextWriter {
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(text) => self.write_text(&text),
+            Event::Code(code) => self.write(&code),
+            Event::SoftBreak | Event::HardBreak => self.new_line(),
+            Event::Rule => {
+                self.blank_line();
+                self.write("----");
+                self.new_line();
+            }
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag) {
+        match tag {
+            Tag::Paragraph | Tag::Heading { .. } if !self.after_item_marker => self.blank_line(),
+            Tag::BlockQuote(_) => {
+                self.blank_line();
+                self.quote_depth += 1;
+            }
+            Tag::CodeBlock(_) => {
+                self.blank_line();
+                self.in_code_block = true;
+            }
+            Tag::List(first_number) => {
+                if self.lists.is_empty() {
+                    self.blank_line();
+                } else {
+                    self.new_line();
+                }
+                self.lists.push(first_number);
+            }
+            Tag::Item => {
+                self.new_line();
+                let indent = "  ".repeat(self.lists.len().saturating_sub(1));
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        format!("{}{}. ", indent, *number - 1)
+                    }
+                    _ => format!("{}- ", indent),
+                };
+                self.write(&marker);
+                self.after_item_marker = true;
+            }
+            Tag::Link { dest_url, .. } | Tag::Image { dest_url, .. } => {
+                self.open_links
+                    .push((dest_url.into_string(), self.output.len()));
+            }
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Paragraph | TagEnd::Heading(_) | TagEnd::Item => self.new_line(),
+            TagEnd::BlockQuote(_) => {
+                self.new_line();
+                self.quote_depth -= 1;
+            }
+            TagEnd::CodeBlock => {
+                self.new_line();
+                self.in_code_block = false;
+            }
+            TagEnd::List(_) => {
+                self.lists.pop();
+            }
+            TagEnd::Link | TagEnd::Image => {
+                let Some((dest_url, text_start)) = self.open_links.pop() else {
+                    return;
+                };
+                // Autolinks already show their destination
+                if self.output[text_start..] == dest_url {
+                    return;
+                }
+                let number = match self.links.iter().position(|link| *link == dest_url) {
+                    Some(index) => index + 1,
+                    None => {
+                        self.links.push(dest_url);
+                        self.links.len()
+                    }
+                };
+                self.write(&format!(" [{}]", number));
+            }
+            _ => {}
+        }
+    }
+
+    fn write_text(&mut self, text: &str) {
+        if !self.in_code_block {
+            self.write(text);
+            return;
+        }
+        for line in text.split_inclusive('\n') {
+            match line.strip_suffix('\n') {
+                Some(line) => {
+                    self.write(&format!("    {}", line));
+                    self.new_line();
+                }
+                None => self.write(&format!("    {}", line)),
+            }
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.at_line_start {
+            self.output.push_str(&"> ".repeat(self.quote_depth));
+            self.at_line_start = false;
+        }
+        self.output.push_str(text);
+        self.after_item_marker = false;
+    }
+
+    fn new_line(&mut self) {
+        if !self.output.is_empty() && !self.at_line_start {
+            self.output.push('\n');
+            self.at_line_start = true;
+        }
+    }
+
+    fn blank_line(&mut self) {
+        self.new_line();
+        if !self.output.is_empty() && !self.output.ends_with("\n\n") {
+            self.output.push('\n');
+        }
+    }
+
+    fn finish(self) -> String {
+        let mut output = self.output.trim_end().to_owned();
+        if !self.links.is_empty() {
+            output.push_str("\n\n");
+            for (index, link) in self.links.iter().enumerate() {
+                output.push_str(&format!("[{}] {}\n", index + 1, link));
+            }
+        }
+        output.trim_end().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_is_wrapped_in_the_newsletter_layout() {
+        let html = render_html("# Hello\n\nSome *text*");
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<h1>Hello</h1>"));
+        assert!(html.contains("<p>Some <em>text</em></p>"));
+    }
+
+    #[test]
+    fn test_raw_html_is_sanitized() {
+        let html = render_html(
+            "Hi <script>alert(1)</script><a href=\"javascript:alert(1)\" onclick=\"x()\">me</a>",
+        );
+
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("javascript:"));
+        assert!(!html.contains("onclick"));
+    }
+
+    #[test]
+    fn test_placeholders_survive_rendering() {
+        let markdown = "Hi {{ name }}, [leave]({{unsubscribe_url}})";
+
+        let html = render_html(markdown);
+        assert!(html.contains("Hi {{ name }}"));
+        assert!(html.contains("href=\"{{ unsubscribe_url }}\""));
+        assert_eq!(
+            render_plain_text(markdown),
+            "Hi {{ name }}, leave [1]\n\n[1] {{ unsubscribe_url }}"
+        );
+    }
+
+    #[test]
+    fn test_links_become_footnotes_in_plain_text() {
+        let text = render_plain_text(
+            "Read [the post](https://example.com/post) and \
+            [the docs](https://example.com/docs).\n\n\
+            Then [the post](https://example.com/post) again.",
+        );
+
+        assert_eq!(
+            text,
+            "Read the post [1] and the docs [2].\n\n\
+            Then the post [1] again.\n\n\
+            [1] https://example.com/post\n\
+            [2] https://example.com/docs"
+        );
+    }
+
+    #[test]
+    fn test_autolinks_are_not_footnoted() {
+        assert_eq!(
+            render_plain_text("Visit <https://example.com>"),
+            "Visit https://example.com"
+        );
+    }
+
+    #[test]
+    fn test_plain_text_keeps_the_document_structure() {
+        let text = render_plain_text(
+            "# Title\n\nIntro\n\n- one\n- two\n  1. nested\n\n\
+            > quoted\n\n```\ncode\n```\n\n<b>raw</b> text",
+        );
+
+        assert_eq!(
+            text,
+            "Title\n\nIntro\n\n- one\n- two\n  1. nested\n\n> quoted\n\n    code\n\nraw text"
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{
+    markdown::{render_html, render_plain_text},
+    personalization::check_placeholders,
+};
+
+// Both versions of an issue as they are sent to subscribers
+#[derive(Debug, PartialEq)]
+pub struct IssueContent {
+    pub html: String,
+    pub plain_text: String,
+}
+
+impl IssueContent {
+    // Without Markdown both versions have to be supplied. With it, either of them can still be
+    // given explicitly to override the rendered one. Every version is checked for unknown
+    // placeholders
+    pub fn parse(
+        html: Option<String>,
+        plain_text: Option<String>,
+        markdown: Option<String>,
+    ) -> Result<IssueContent, String> {
+        for version in [&html, &plain_text, &markdown].into_iter().flatten() {
+            check_placeholders(version)?;
+        }
+        match (html, plain_text, markdown) {
+            (html, plain_text, Some(markdown)) => Ok(IssueContent {
+                html: html.unwrap_or_else(|| render_html(&markdown)),
+                plain_text: plain_text.unwrap_or_else(|| render_plain_text(&markdown)),
+            }),
+            (Some(html), Some(plain_text), None) => Ok(IssueContent { html, plain_text }),
+            _ => Err("The content needs either markdown or both html and plain_text".into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok, assert_ok_eq};
+
+    use super::*;
+
+    #[test]
+    fn test_html_and_plain_text_are_kept_as_they_are() {
+        assert_ok_eq!(
+            IssueContent::parse(Some("<p>Hi</p>".into()), Some("Hi".into()), None),
+            IssueContent {
+                html: "<p>Hi</p>".into(),
+                plain_text: "Hi".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_markdown_renders_both_versions() {
+        let content = assert_ok!(IssueContent::parse(None, None, Some("*Hi*".into())));
+
+        assert!(content.html.contains("<em>Hi</em>"));
+        assert_eq!(content.plain_text, "Hi");
+    }
+
+    #[test]
+    fn test_explicit_versions_override_the_rendered_ones() {
+        let content = assert_ok!(IssueContent::parse(
+            None,
+            Some("Custom text".into()),
+            Some("*Hi*".into())
+        ));
+
+        assert!(content.html.contains("<em>Hi</em>"));
+        assert_eq!(content.plain_text, "Custom text");
+    }
+
+    #[test]
+    fn test_unknown_placeholders_are_rejected() {
+        assert_err!(IssueContent::parse(
+            None,
+            None,
+            Some("Hi {{ email }}".into())
+        ));
+        assert_err!(IssueContent::parse(
+            Some("<p>Hi</p>".into()),
+            Some("Hi {{ nmae }}".into()),
+            None
+        ));
+    }
+
+    #[test]
+    fn test_incomplete_content_is_rejected() {
+        assert_err!(IssueContent::parse(Some("<p>Hi</p>".into()), None, None));
+        assert_err!(IssueContent::parse(None, Some("Hi".into()), None));
+        assert_err!(IssueContent::parse(None, None, None));
+    }
+}
@@ -0,0 +1,84 @@
+const MAX_SLUG_LENGTH: usize = 64;
+
+// Identifier of a newsletter issue in the public archive URLs, derived from its title
+#[derive(Debug, Clone, PartialEq)]
+pub struct IssueSlug(String);
+
+impl IssueSlug {
+    // Letters and digits are kept, every other run of characters becomes a single '-'. Titles
+    // without any of them fall back to "issue"
+    pub fn from_title(title: &str) -> IssueSlug {
+        let mut slug = String::new();
+        for c in title.chars().flat_map(char::to_lowercase) {
+            if c.is_ascii_alphanumeric() {
+                slug.push(c);
+            } else if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        slug.truncate(MAX_SLUG_LENGTH);
+        let slug = slug.trim_end_matches('-');
+
+        if slug.is_empty() {
+            IssueSlug("issue".into())
+        } else {
+            IssueSlug(slug.into())
+        }
+    }
+
+    // Used to tell apart issues sharing the same title
+    pub fn with_suffix(&self, suffix: u32) -> IssueSlug {
+        IssueSlug(format!("{}-{}", self.0, suffix))
+    }
+}
+
+impl AsRef<str> for IssueSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for IssueSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_is_lowercased_and_separated_by_dashes() {
+        assert_eq!(
+            IssueSlug::from_title("Rust 2024: What's New?").as_ref(),
+            "rust-2024-what-s-new"
+        );
+    }
+
+    #[test]
+    fn test_non_ascii_characters_are_dropped() {
+        assert_eq!(IssueSlug::from_title("Été à Paris").as_ref(), "t-paris");
+    }
+
+    #[test]
+    fn test_title_without_letters_or_digits_falls_back_to_issue() {
+        assert_eq!(IssueSlug::from_title("!!!").as_ref(), "issue");
+        assert_eq!(IssueSlug::from_title("").as_ref(), "issue");
+    }
+
+    #[test]
+    fn test_long_titles_are_truncated() {
+        let slug = IssueSlug::from_title(&"word ".repeat(30));
+        assert!(slug.as_ref().len() <= MAX_SLUG_LENGTH);
+        assert!(!slug.as_ref().ends_with('-'));
+    }
+
+    #[test]
+    fn test_suffix_is_appended() {
+        assert_eq!(
+            IssueSlug::from_title("Weekly").with_suffix(2).as_ref(),
+            "weekly-2"
+        );
+    }
+}
@@ -0,0 +1,76 @@
+// List every subscriber was on before multiple lists were supported
+const DEFAULT_LIST_SLUG: &str = "default";
+
+// Identifier of a mailing list used in forms and API payloads
+#[derive(Debug, Clone)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    pub fn parse(slug: String) -> Result<ListSlug, String> {
+        let is_valid_character = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-';
+        if slug.is_empty()
+            || slug.len() > 64
+            || !slug.chars().all(is_valid_character)
+            || slug.starts_with('-')
+            || slug.ends_with('-')
+        {
+            return Err(format!("{} is not a valid list slug!", slug));
+        }
+
+        Ok(ListSlug(slug))
+    }
+
+    pub fn default_list() -> ListSlug {
+        ListSlug(DEFAULT_LIST_SLUG.into())
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ListSlug {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::*;
+
+    #[test]
+    fn test_valid_slugs_are_accepted() {
+        assert_ok!(ListSlug::parse("default".into()));
+        assert_ok!(ListSlug::parse("rust-weekly-2".into()));
+    }
+
+    #[test]
+    fn test_empty_slug_is_rejected() {
+        assert_err!(ListSlug::parse("".into()));
+    }
+
+    #[test]
+    fn test_slug_longer_than_64_characters_is_rejected() {
+        assert_ok!(ListSlug::parse("a".repeat(64)));
+        assert_err!(ListSlug::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn test_slug_with_invalid_characters_is_rejected() {
+        for slug in [
+            "Default",
+            "rust weekly",
+            "rust_weekly",
+            "-rust",
+            "rust-",
+            "ñ",
+        ] {
+            assert_err!(ListSlug::parse(slug.into()));
+        }
+    }
+}
@@ -1,9 +1,23 @@
+mod digest_frequency;
+mod issue_content;
+mod issue_slug;
+mod list_slug;
+mod new_password;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod subscribers_cursor;
 mod subscription_token;
+mod unsubscribe_token;
 
+pub use digest_frequency::DigestFrequency;
+pub use issue_content::IssueContent;
+pub use issue_slug::IssueSlug;
+pub use list_slug::ListSlug;
+pub use new_password::NewPassword;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use subscribers_cursor::SubscribersCursor;
 pub use subscription_token::SubscriptionToken;
+pub use unsubscribe_token::UnsubscribeToken;
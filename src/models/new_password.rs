@@ -0,0 +1,64 @@
+use secrecy::{ExposeSecret, SecretString};
+use unicode_segmentation::UnicodeSegmentation;
+
+const MIN_LENGTH: usize = 12;
+const MAX_LENGTH: usize = 128;
+
+#[derive(Debug)]
+pub struct NewPassword(SecretString);
+
+impl NewPassword {
+    pub fn parse(password: SecretString) -> Result<NewPassword, String> {
+        let length = password.expose_secret().graphemes(true).count();
+
+        if length < MIN_LENGTH {
+            Err(format!(
+                "The new password must be at least {} characters long.",
+                MIN_LENGTH
+            ))
+        } else if length > MAX_LENGTH {
+            Err(format!(
+                "The new password must be at most {} characters long.",
+                MAX_LENGTH
+            ))
+        } else {
+            Ok(Self(password))
+        }
+    }
+
+    pub fn into_inner(self) -> SecretString {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use secrecy::SecretString;
+
+    use super::NewPassword;
+
+    #[test]
+    fn test_12_long_password_is_valid() {
+        let password = SecretString::new("a".repeat(12));
+        assert_ok!(NewPassword::parse(password));
+    }
+
+    #[test]
+    fn test_11_long_password_is_invalid() {
+        let password = SecretString::new("a".repeat(11));
+        assert_err!(NewPassword::parse(password));
+    }
+
+    #[test]
+    fn test_128_long_password_is_valid() {
+        let password = SecretString::new("a".repeat(128));
+        assert_ok!(NewPassword::parse(password));
+    }
+
+    #[test]
+    fn test_129_long_password_is_invalid() {
+        let password = SecretString::new("a".repeat(129));
+        assert_err!(NewPassword::parse(password));
+    }
+}
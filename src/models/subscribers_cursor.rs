@@ -0,0 +1,68 @@
+use base64::Engine;
+use chrono::{DateTime, SecondsFormat, Utc};
+use uuid::Uuid;
+
+// Position of the last subscriber of a page when listing subscribers ordered by
+// `(subscribed_at, id)`. It is handed to clients as an opaque string
+#[derive(Debug, PartialEq)]
+pub struct SubscribersCursor {
+    pub subscribed_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl SubscribersCursor {
+    pub fn parse(cursor: String) -> Result<SubscribersCursor, String> {
+        let invalid_cursor = || format!("{} is not a valid cursor!", cursor);
+
+        let decoded_cursor = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(&cursor)
+            .map_err(|_| invalid_cursor())?;
+        let decoded_cursor = String::from_utf8(decoded_cursor).map_err(|_| invalid_cursor())?;
+        let (subscribed_at, id) = decoded_cursor.split_once('|').ok_or_else(invalid_cursor)?;
+
+        Ok(SubscribersCursor {
+            subscribed_at: DateTime::parse_from_rfc3339(subscribed_at)
+                .map_err(|_| invalid_cursor())?
+                .with_timezone(&Utc),
+            id: Uuid::parse_str(id).map_err(|_| invalid_cursor())?,
+        })
+    }
+
+    pub fn encode(&self) -> String {
+        let cursor = format!(
+            "{}|{}",
+            self.subscribed_at
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            self.id
+        );
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::*;
+
+    #[test]
+    fn test_encoded_cursor_is_parsed_back() {
+        let cursor = SubscribersCursor {
+            subscribed_at: Utc::now(),
+            id: Uuid::new_v4(),
+        };
+        let parsed_cursor = assert_ok!(SubscribersCursor::parse(cursor.encode()));
+        assert_eq!(cursor, parsed_cursor);
+    }
+
+    #[test]
+    fn test_cursor_that_is_not_base64_is_rejected() {
+        assert_err!(SubscribersCursor::parse("not a cursor!".into()));
+    }
+
+    #[test]
+    fn test_cursor_with_invalid_content_is_rejected() {
+        let cursor = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("yesterday|42");
+        assert_err!(SubscribersCursor::parse(cursor));
+    }
+}
@@ -0,0 +1,61 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+
+const UNSUBSCRIBE_TOKEN_LENGTH: usize = 32;
+
+#[derive(Debug)]
+pub struct UnsubscribeToken(String);
+
+impl UnsubscribeToken {
+    pub fn generate() -> UnsubscribeToken {
+        let mut rng = thread_rng();
+        let token = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+            .map(char::from)
+            .take(UNSUBSCRIBE_TOKEN_LENGTH)
+            .collect();
+
+        UnsubscribeToken(token)
+    }
+
+    pub fn parse(token: String) -> Result<UnsubscribeToken, String> {
+        if (token.len() != UNSUBSCRIBE_TOKEN_LENGTH)
+            || !token.chars().all(|c| c.is_ascii_alphanumeric())
+        {
+            return Err(format!("{} is not a valid unsubscribe token!", token));
+        }
+
+        Ok(UnsubscribeToken(token))
+    }
+}
+
+impl AsRef<str> for UnsubscribeToken {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::*;
+
+    #[test]
+    fn test_unsubscribe_token_length_32() {
+        let token = "a".repeat(32);
+        assert_ok!(UnsubscribeToken::parse(token));
+        let token = "b".repeat(25);
+        assert_err!(UnsubscribeToken::parse(token));
+    }
+
+    #[test]
+    fn test_unsubscribe_token_with_non_alphanumeric_characters_invalid() {
+        let token = "?".repeat(32);
+        assert_err!(UnsubscribeToken::parse(token));
+    }
+
+    #[test]
+    fn test_generated_token_is_valid() {
+        let token = UnsubscribeToken::generate();
+        assert_ok!(UnsubscribeToken::parse(token.as_ref().to_string()));
+    }
+}
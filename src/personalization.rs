@@ -0,0 +1,188 @@
+// Issues are stored with their placeholders, which are only filled in when an email is sent or
+// the issue is shown
+pub const PLACEHOLDERS: [&str; 3] = ["name", "unsubscribe_url", "archive_url"];
+
+// Stands for a placeholder while Markdown is being rendered, so that neither the renderer nor
+// the sanitizer touch it
+const MARKER_PREFIX: &str = "zz2prodplaceholder";
+const MARKER_SUFFIX: &str = "zz";
+
+pub struct Recipient<'a> {
+    pub name: &'a str,
+    pub unsubscribe_url: &'a str,
+    pub archive_url: &'a str,
+}
+
+impl Recipient<'_> {
+    fn value(&self, placeholder: &str) -> Option<&str> {
+        match placeholder {
+            "name" => Some(self.name),
+            "unsubscribe_url" => Some(self.unsubscribe_url),
+            "archive_url" => Some(self.archive_url),
+            _ => None,
+        }
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+// A `{{` without a matching `}}` is plain text
+fn segments(template: &str) -> Vec<Segment<'_>> {
+    let mut segments = vec![];
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start + 2..].find("}}") else {
+            break;
+        };
+        segments.push(Segment::Text(&rest[..start]));
+        segments.push(Segment::Placeholder(
+            rest[start + 2..start + 2 + length].trim(),
+        ));
+        rest = &rest[start + 2 + length + 2..];
+    }
+    segments.push(Segment::Text(rest));
+    segments
+}
+
+pub fn check_placeholders(template: &str) -> Result<(), String> {
+    for segment in segments(template) {
+        if let Segment::Placeholder(placeholder) = segment {
+            if !PLACEHOLDERS.contains(&placeholder) {
+                return Err(format!(
+                    "{{{{ {} }}}} is not a known placeholder, use one of: {}",
+                    placeholder,
+                    PLACEHOLDERS.join(", ")
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+// Unknown placeholders can only come from issues stored before they were validated, they are
+// left as they are
+pub fn personalize_text(template: &str, recipient: &Recipient) -> String {
+    personalize(template, |placeholder| match recipient.value(placeholder) {
+        Some(value) => value.into(),
+        None => format!("{{{{ {} }}}}", placeholder),
+    })
+}
+
+// Subscriber data ends up inside markup, so it is escaped
+pub fn personalize_html(template: &str, recipient: &Recipient) -> String {
+    personalize(template, |placeholder| match recipient.value(placeholder) {
+        Some(value) => escape_html(value),
+        None => format!("{{{{ {} }}}}", placeholder),
+    })
+}
+
+fn personalize(template: &str, value: impl Fn(&str) -> String) -> String {
+    segments(template)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.to_owned(),
+            Segment::Placeholder(placeholder) => value(placeholder),
+        })
+        .collect()
+}
+
+// Unknown placeholders are left as they are
+pub(crate) fn placeholders_to_markers(template: &str) -> String {
+    personalize(template, |placeholder| {
+        match PLACEHOLDERS.iter().position(|p| *p == placeholder) {
+            Some(index) => format!("{}{}{}", MARKER_PREFIX, index, MARKER_SUFFIX),
+            None => format!("{{{{ {} }}}}", placeholder),
+        }
+    })
+}
+
+pub(crate) fn markers_to_placeholders(rendered: &str) -> String {
+    PLACEHOLDERS
+        .iter()
+        .enumerate()
+        .fold(rendered.to_owned(), |rendered, (index, placeholder)| {
+            rendered.replace(
+                &format!("{}{}{}", MARKER_PREFIX, index, MARKER_SUFFIX),
+                &format!("{{{{ {} }}}}", placeholder),
+            )
+        })
+}
+
+fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#x27;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+
+    use super::*;
+
+    fn recipient() -> Recipient<'static> {
+        Recipient {
+            name: "Ursula <Le Guin>",
+            unsubscribe_url: "https://example.com/unsubscribe?token=a&b",
+            archive_url: "https://example.com/archive/issue",
+        }
+    }
+
+    #[test]
+    fn test_known_placeholders_are_accepted() {
+        assert_ok!(check_placeholders(
+            "Hi {{ name }}, {{unsubscribe_url}} {{  archive_url }}"
+        ));
+        assert_ok!(check_placeholders("No placeholders, just {{ braces"));
+    }
+
+    #[test]
+    fn test_unknown_placeholders_are_rejected() {
+        assert_err!(check_placeholders("Hi {{ email }}"));
+        assert_err!(check_placeholders("Hi {{ }}"));
+        assert_err!(check_placeholders("Hi {{ name | upper }}"));
+    }
+
+    #[test]
+    fn test_text_is_personalized_as_is() {
+        assert_eq!(
+            personalize_text("Hi {{ name }}, see {{archive_url}}", &recipient()),
+            "Hi Ursula <Le Guin>, see https://example.com/archive/issue"
+        );
+    }
+
+    #[test]
+    fn test_html_values_are_escaped() {
+        assert_eq!(
+            personalize_html(
+                "<p>Hi {{ name }}</p><a href=\"{{ unsubscribe_url }}\">x</a>",
+                &recipient()
+            ),
+            "<p>Hi Ursula &lt;Le Guin&gt;</p>\
+            <a href=\"https://example.com/unsubscribe?token=a&amp;b\">x</a>"
+        );
+    }
+
+    #[test]
+    fn test_markers_round_trip_to_placeholders() {
+        let markers = placeholders_to_markers("Hi {{name}}, {{ archive_url }}");
+
+        assert!(!markers.contains("{{"));
+        assert_eq!(
+            markers_to_placeholders(&markers),
+            "Hi {{ name }}, {{ archive_url }}"
+        );
+    }
+}
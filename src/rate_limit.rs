@@ -0,0 +1,261 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::MessageBody,
+    dev::{ServiceRequest, ServiceResponse},
+    error::InternalError,
+    http::header::{HeaderMap, HeaderName, RETRY_AFTER},
+    middleware::Next,
+    web, HttpResponse,
+};
+
+use crate::{
+    configuration::{SubscriptionRateLimitSettings, TokenBucketSettings},
+    utils::e500,
+};
+
+// Past this many tracked keys, buckets that refilled completely are dropped, they hold no more
+// information than a missing one
+const PRUNE_THRESHOLD: usize = 10_000;
+
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// Every key gets `capacity` requests in a burst, then one more every `refill_interval`. Buckets
+// live in memory, so each instance of the application enforces its own limits
+pub struct TokenBucketLimiter {
+    capacity: f64,
+    refill_interval: Duration,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl TokenBucketLimiter {
+    pub fn new(settings: &TokenBucketSettings) -> Self {
+        Self {
+            capacity: settings.capacity as f64,
+            refill_interval: Duration::from_secs(settings.refill_interval_seconds),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Takes a token for the key, or returns how long to wait until one is available
+    pub fn try_acquire(&self, key: &str) -> Result<(), Duration> {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&self, key: &str, now: Instant) -> Result<(), Duration> {
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, bucket| self.refill(bucket, now) < self.capacity);
+        }
+
+        let bucket = buckets.entry(key.to_owned()).or_insert(Bucket {
+            tokens: self.capacity,
+            refilled_at: now,
+        });
+        bucket.tokens = self.refill(bucket, now);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(self.refill_interval.mul_f64(1.0 - bucket.tokens))
+        }
+    }
+
+    fn refill(&self, bucket: &Bucket, now: Instant) -> f64 {
+        if self.refill_interval.is_zero() {
+            return self.capacity;
+        }
+        let elapsed = now.saturating_duration_since(bucket.refilled_at);
+        (bucket.tokens + elapsed.as_secs_f64() / self.refill_interval.as_secs_f64())
+            .min(self.capacity)
+    }
+}
+
+pub struct SubscriptionRateLimiter {
+    trusted_proxy_header: Option<HeaderName>,
+    per_ip: TokenBucketLimiter,
+    per_email: TokenBucketLimiter,
+}
+
+impl SubscriptionRateLimiter {
+    pub fn new(settings: &SubscriptionRateLimitSettings) -> Self {
+        Self {
+            trusted_proxy_header: settings.trusted_proxy_header.as_ref().map(|header| {
+                HeaderName::try_from(header.as_str())
+                    .expect("Invalid `subscription_rate_limit.trusted_proxy_header` setting")
+            }),
+            per_ip: TokenBucketLimiter::new(&settings.per_ip),
+            per_email: TokenBucketLimiter::new(&settings.per_email),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct SubscriptionTarget {
+    email: Option<String>,
+}
+
+// Requests that email the submitted address, subscriptions, preference links and data exports,
+// are limited both by client and by target address, so that neither a single client nor a botnet
+// can flood somebody's inbox. The endpoints share the same buckets
+pub async fn rate_limit_subscriptions(
+    mut request: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let limiter = request
+        .app_data::<web::Data<SubscriptionRateLimiter>>()
+        .cloned()
+        .ok_or_else(|| e500("The subscription rate limiter is not configured"))?;
+
+    let client_ip = client_ip(
+        request.headers(),
+        limiter.trusted_proxy_header.as_ref(),
+        request.peer_addr().map(|address| address.ip()),
+    );
+    if let Some(client_ip) = client_ip {
+        limiter
+            .per_ip
+            .try_acquire(&client_ip.to_string())
+            .map_err(too_many_requests)?;
+    }
+
+    // The body is read here to find the target address, then handed back to the handler
+    let body = request.extract::<web::Bytes>().await?;
+    let email = serde_urlencoded::from_bytes::<SubscriptionTarget>(&body)
+        .ok()
+        .and_then(|target| target.email)
+        .map(|email| email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+    request.set_payload(body.into());
+    if let Some(email) = email {
+        limiter
+            .per_email
+            .try_acquire(&email)
+            .map_err(too_many_requests)?;
+    }
+
+    next.call(request).await
+}
+
+// Only the last address of the header is used, it is the one added by the trusted proxy while
+// the ones before it come from the client. Without a usable header the peer address is used
+fn client_ip(
+    headers: &HeaderMap,
+    trusted_proxy_header: Option<&HeaderName>,
+    peer_ip: Option<IpAddr>,
+) -> Option<IpAddr> {
+    trusted_proxy_header
+        .and_then(|header| headers.get(header))
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|address| address.trim().parse().ok())
+        .or(peer_ip)
+}
+
+fn too_many_requests(retry_after: Duration) -> actix_web::Error {
+    // Rounded up, retrying any earlier would be rejected again
+    let retry_after_seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+    let response = HttpResponse::TooManyRequests()
+        .insert_header((RETRY_AFTER, retry_after_seconds.max(1)))
+        .finish();
+    let e = anyhow::anyhow!("Too many subscription requests");
+    InternalError::from_response(e, response).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::HeaderValue;
+    use claims::{assert_err, assert_ok, assert_some_eq};
+
+    use super::*;
+
+    fn limiter(capacity: u32, refill_interval_seconds: u64) -> TokenBucketLimiter {
+        TokenBucketLimiter::new(&TokenBucketSettings {
+            capacity,
+            refill_interval_seconds,
+        })
+    }
+
+    #[test]
+    fn test_bursts_are_allowed_up_to_the_capacity() {
+        let limiter = limiter(3, 60);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_ok!(limiter.try_acquire_at("key", now));
+        }
+        assert_eq!(
+            limiter.try_acquire_at("key", now),
+            Err(Duration::from_secs(60))
+        );
+    }
+
+    #[test]
+    fn test_tokens_are_given_back_over_time() {
+        let limiter = limiter(1, 60);
+        let now = Instant::now();
+        assert_ok!(limiter.try_acquire_at("key", now));
+
+        assert_eq!(
+            limiter.try_acquire_at("key", now + Duration::from_secs(45)),
+            Err(Duration::from_secs(15))
+        );
+        assert_ok!(limiter.try_acquire_at("key", now + Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_keys_have_their_own_bucket() {
+        let limiter = limiter(1, 60);
+        let now = Instant::now();
+
+        assert_ok!(limiter.try_acquire_at("first", now));
+        assert_ok!(limiter.try_acquire_at("second", now));
+        assert_err!(limiter.try_acquire_at("first", now));
+    }
+
+    #[test]
+    fn test_the_peer_address_is_used_without_a_trusted_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_static("x-forwarded-for"),
+            HeaderValue::from_static("10.0.0.1"),
+        );
+        let peer_ip = "192.168.1.1".parse().ok();
+
+        assert_eq!(client_ip(&headers, None, peer_ip), peer_ip);
+    }
+
+    #[test]
+    fn test_the_last_address_of_the_trusted_header_is_used() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            header.clone(),
+            HeaderValue::from_static("10.0.0.1, 203.0.113.7"),
+        );
+
+        assert_some_eq!(
+            client_ip(&headers, Some(&header), "192.168.1.1".parse().ok()),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_an_invalid_trusted_header_falls_back_to_the_peer_address() {
+        let header = HeaderName::from_static("x-forwarded-for");
+        let mut headers = HeaderMap::new();
+        headers.insert(header.clone(), HeaderValue::from_static("not an ip"));
+        let peer_ip = "192.168.1.1".parse().ok();
+
+        assert_eq!(client_ip(&headers, Some(&header), peer_ip), peer_ip);
+    }
+}
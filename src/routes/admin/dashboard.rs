@@ -0,0 +1,39 @@
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{authentication::UserId, templates::AdminDashboardTemplate, utils::e500};
+
+#[get("/dashboard")]
+pub async fn admin_dashboard(
+    db_connection_pool: web::Data<PgPool>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let username = get_username(*user_id.into_inner(), &db_connection_pool)
+        .await
+        .map_err(e500)?;
+    let html_body = AdminDashboardTemplate {
+        username: &username,
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(name = "Get username", skip(db_connection_pool))]
+pub async fn get_username(
+    user_id: Uuid,
+    db_connection_pool: &PgPool,
+) -> Result<String, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT username FROM users WHERE user_id = $1"#, user_id)
+        .fetch_one(db_connection_pool)
+        .await
+        .context("Failed to perform a query to retrieve a username")?;
+
+    Ok(row.username)
+}
@@ -0,0 +1,135 @@
+use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use secrecy::SecretString;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    authentication::{validate_credentials, Credentials, UserId},
+    errors::AuthError,
+    models::SubscriberEmail,
+    routes::get_username,
+    session::TypedSession,
+    templates::ChangeEmailTemplate,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ChangeEmailFormData {
+    current_password: SecretString,
+    email: String,
+}
+
+#[get("/email")]
+pub async fn change_email_form(
+    db_connection_pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let current_email = get_user_email(*user_id.into_inner(), &db_connection_pool)
+        .await
+        .map_err(e500)?;
+    let message = session.take_flash_message();
+    let html_body = ChangeEmailTemplate {
+        current_email: current_email.as_deref(),
+        message: message.as_deref(),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+// The address receives password reset links, so the current password is asked for, like when
+// changing the password itself
+#[tracing::instrument(
+    name = "Change admin email",
+    skip(form_data, db_connection_pool, session),
+    fields(user_id = %*user_id)
+)]
+#[post("/email")]
+pub async fn change_admin_email(
+    form_data: web::Form<ChangeEmailFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form_data = form_data.into_inner();
+
+    let email = match SubscriberEmail::parse(form_data.email.trim().to_string()) {
+        Ok(email) => email,
+        Err(e) => return redirect_with_message(&session, &e),
+    };
+
+    let username = get_username(*user_id, &db_connection_pool)
+        .await
+        .map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form_data.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &db_connection_pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                redirect_with_message(&session, "The current password is incorrect.")
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    let changed = set_user_email(*user_id, &email, &db_connection_pool)
+        .await
+        .map_err(e500)?;
+    if !changed {
+        return redirect_with_message(&session, "This email is already used by another account.");
+    }
+
+    redirect_with_message(&session, "Your email has been changed.")
+}
+
+fn redirect_with_message(
+    session: &TypedSession,
+    message: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    session.insert_flash_message(message).map_err(e500)?;
+    Ok(see_other("/admin/email"))
+}
+
+#[tracing::instrument(name = "Get user email", skip(db_connection_pool))]
+async fn get_user_email(
+    user_id: Uuid,
+    db_connection_pool: &PgPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(r#"SELECT email FROM users WHERE user_id = $1"#, user_id)
+        .fetch_one(db_connection_pool)
+        .await
+        .context("Failed to perform a query to retrieve the user's email")?;
+
+    Ok(row.email)
+}
+
+// Returns false when another account already uses the address
+#[tracing::instrument(name = "Set user email", skip(db_connection_pool))]
+async fn set_user_email(
+    user_id: Uuid,
+    email: &SubscriberEmail,
+    db_connection_pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"UPDATE users SET email = $2 WHERE user_id = $1"#,
+        user_id,
+        email.as_ref()
+    )
+    .execute(db_connection_pool)
+    .await;
+
+    match result {
+        Ok(_) => Ok(true),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(false),
+        Err(e) => Err(e).context("Failed to update the user's email"),
+    }
+}
@@ -0,0 +1,709 @@
+use actix_web::{get, http::header::ContentType, patch, post, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    authentication::UserId,
+    email_client::EmailSender,
+    issues::{insert_issue, publish_issue, set_issue_lists, NewIssue},
+    lists::{get_list_ids, parse_list_slugs},
+    models::{IssueContent, ListSlug, SubscriberEmail},
+    personalization::{check_placeholders, personalize_html, personalize_text, Recipient},
+    routes::{archive_link, unsubscribe_link},
+    startup::ApplicationBaseUrl,
+    suppressions::is_suppressed,
+    utils::{e400, e500},
+};
+
+const SAMPLE_NAME: &str = "Sample Subscriber";
+const SAMPLE_UNSUBSCRIBE_TOKEN: &str = "sample";
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ListIssuesParameters {
+    status: Option<String>,
+}
+
+// Either Markdown or both html and plain_text, see `IssueContent::parse`
+#[derive(serde::Deserialize)]
+pub struct IssueContentData {
+    html: Option<String>,
+    plain_text: Option<String>,
+    markdown: Option<String>,
+}
+
+impl TryFrom<IssueContentData> for IssueContent {
+    type Error = String;
+
+    fn try_from(content: IssueContentData) -> Result<Self, Self::Error> {
+        IssueContent::parse(content.html, content.plain_text, content.markdown)
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct NewDraftData {
+    title: String,
+    content: IssueContentData,
+    lists: Option<Vec<String>>,
+    #[serde(default)]
+    subscribers_only: bool,
+    #[serde(default)]
+    track_opens: bool,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct PreviewParameters {
+    // `html` or `text`, `html` when missing
+    format: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TestSendData {
+    recipients: Vec<String>,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PublishDraftData {
+    send_at: Option<DateTime<Utc>>,
+}
+
+// Fields left out are kept as they are
+#[derive(serde::Deserialize)]
+pub struct IssueUpdateData {
+    title: Option<String>,
+    content: Option<IssueContentData>,
+    lists: Option<Vec<String>>,
+    subscribers_only: Option<bool>,
+    track_opens: Option<bool>,
+    send_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct Issue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    status: String,
+    slug: Option<String>,
+    subscribers_only: bool,
+    track_opens: bool,
+    lists: Vec<String>,
+    created_at: DateTime<Utc>,
+    send_at: Option<DateTime<Utc>>,
+    published_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct IssueOpens {
+    newsletter_issue_id: Uuid,
+    track_opens: bool,
+    delivered: i64,
+    unique_opens: i64,
+    total_opens: i64,
+    // Unique opens over successful deliveries
+    open_rate: f64,
+}
+
+#[derive(serde::Serialize)]
+struct IssueClicks {
+    newsletter_issue_id: Uuid,
+    links: Vec<LinkClicks>,
+}
+
+#[derive(serde::Serialize)]
+struct LinkClicks {
+    url: String,
+    total_clicks: i64,
+    unique_clicks: i64,
+}
+
+struct IssueEmail {
+    title: String,
+    html_content: String,
+    text_content: String,
+    slug: Option<String>,
+    subscribers_only: bool,
+}
+
+impl IssueEmail {
+    // Previews and test sends are not addressed to a subscriber: placeholders get sample values
+    // and the unsubscribe link does not unsubscribe anyone
+    fn personalize_for_sample(self, application_base_url: &str) -> IssueEmail {
+        let unsubscribe_url = unsubscribe_link(application_base_url, SAMPLE_UNSUBSCRIBE_TOKEN);
+        let archive_url = archive_link(
+            application_base_url,
+            self.slug.as_deref().filter(|_| !self.subscribers_only),
+        );
+        let recipient = Recipient {
+            name: SAMPLE_NAME,
+            unsubscribe_url: &unsubscribe_url,
+            archive_url: &archive_url,
+        };
+
+        IssueEmail {
+            title: personalize_text(&self.title, &recipient),
+            html_content: personalize_html(&self.html_content, &recipient),
+            text_content: personalize_text(&self.text_content, &recipient),
+            ..self
+        }
+    }
+}
+
+#[tracing::instrument(name = "List newsletter issues", skip(db_connection_pool))]
+#[get("/issues")]
+pub async fn list_issues(
+    db_connection_pool: web::Data<PgPool>,
+    parameters: web::Query<ListIssuesParameters>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issues = sqlx::query_as!(
+        Issue,
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.status, i.slug, i.subscribers_only, i.track_opens,
+            ARRAY(
+                SELECT l.slug
+                FROM newsletter_issue_lists il
+                JOIN lists l ON l.id = il.list_id
+                WHERE il.newsletter_issue_id = i.newsletter_issue_id
+                ORDER BY l.slug
+            ) AS "lists!",
+            i.created_at, i.send_at, i.published_at
+        FROM newsletter_issues i
+        WHERE ($1::text IS NULL OR i.status = $1)
+        ORDER BY i.created_at DESC
+        "#,
+        parameters.status
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the newsletter issues")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(issues))
+}
+
+fn check_title(title: &str) -> Result<(), String> {
+    if title.trim().is_empty() {
+        return Err("The title of an issue cannot be empty".into());
+    }
+    check_placeholders(title)
+}
+
+// Drafts are never delivered on their own, they have to be published explicitly
+#[tracing::instrument(
+    name = "Create draft issue",
+    skip(db_connection_pool, draft_data, user_id)
+)]
+#[post("/issues")]
+pub async fn create_draft_issue(
+    db_connection_pool: web::Data<PgPool>,
+    draft_data: web::Json<NewDraftData>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let draft_data = draft_data.into_inner();
+    check_title(&draft_data.title).map_err(e400)?;
+    let list_slugs = parse_list_slugs(draft_data.lists).map_err(e400)?;
+    let content = IssueContent::try_from(draft_data.content).map_err(e400)?;
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let newsletter_issue_id = insert_issue(
+        &mut db_transaction,
+        &NewIssue {
+            title: &draft_data.title,
+            html_content: &content.html,
+            text_content: &content.plain_text,
+            author_id: *user_id.into_inner(),
+            subscribers_only: draft_data.subscribers_only,
+            track_opens: draft_data.track_opens,
+            status: "draft",
+            send_at: None,
+        },
+    )
+    .await
+    .map_err(e500)?;
+    attach_lists(&mut db_transaction, newsletter_issue_id, &list_slugs).await?;
+
+    let issue = get_issue(&mut *db_transaction, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to create the draft issue")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Created().json(issue))
+}
+
+// Only drafts and scheduled issues can be edited. The scheduler locks an issue while publishing
+// it, so an edit either lands before the issue is published or is rejected with a 409
+#[tracing::instrument(name = "Edit issue", skip(db_connection_pool, update_data))]
+#[patch("/issues/{newsletter_issue_id}")]
+pub async fn edit_issue(
+    db_connection_pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+    update_data: web::Json<IssueUpdateData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let update_data = update_data.into_inner();
+    if let Some(title) = &update_data.title {
+        check_title(title).map_err(e400)?;
+    }
+    let list_slugs = match update_data.lists {
+        Some(lists) => Some(parse_list_slugs(Some(lists)).map_err(e400)?),
+        None => None,
+    };
+    let (html_content, text_content) = match update_data.content {
+        Some(content) => {
+            let content = IssueContent::try_from(content).map_err(e400)?;
+            (Some(content.html), Some(content.plain_text))
+        }
+        None => (None, None),
+    };
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let updated_issue = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET title = COALESCE($2, title),
+            html_content = COALESCE($3, html_content),
+            text_content = COALESCE($4, text_content),
+            subscribers_only = COALESCE($5, subscribers_only),
+            track_opens = COALESCE($6, track_opens),
+            send_at = COALESCE($7, send_at)
+        WHERE newsletter_issue_id = $1 AND status IN ('draft', 'scheduled')
+        RETURNING newsletter_issue_id
+        "#,
+        newsletter_issue_id,
+        update_data.title,
+        html_content,
+        text_content,
+        update_data.subscribers_only,
+        update_data.track_opens,
+        update_data.send_at
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to update the issue")
+    .map_err(e500)?;
+    if updated_issue.is_none() {
+        return unchangeable_issue(&mut *db_transaction, newsletter_issue_id).await;
+    }
+
+    if let Some(list_slugs) = list_slugs {
+        attach_lists(&mut db_transaction, newsletter_issue_id, &list_slugs).await?;
+    }
+
+    let issue = get_issue(&mut *db_transaction, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to edit the issue")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+#[tracing::instrument(name = "Cancel scheduled issue", skip(db_connection_pool))]
+#[post("/issues/{newsletter_issue_id}/cancel")]
+pub async fn cancel_scheduled_issue(
+    db_connection_pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+
+    let cancelled_issue = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues SET status = 'cancelled'
+        WHERE newsletter_issue_id = $1 AND status = 'scheduled'
+        RETURNING newsletter_issue_id
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_connection_pool.as_ref())
+    .await
+    .context("Failed to cancel the scheduled issue")
+    .map_err(e500)?;
+
+    match cancelled_issue {
+        Some(_) => Ok(HttpResponse::NoContent().finish()),
+        None => unchangeable_issue(db_connection_pool.as_ref(), newsletter_issue_id).await,
+    }
+}
+
+// A send time that has already passed, or none at all, publishes the draft straight away
+#[tracing::instrument(name = "Publish draft issue", skip(db_connection_pool, publish_data))]
+#[post("/issues/{newsletter_issue_id}/publish")]
+pub async fn publish_draft_issue(
+    db_connection_pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+    publish_data: web::Json<PublishDraftData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let send_at = publish_data.send_at.filter(|send_at| *send_at > Utc::now());
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let draft = sqlx::query!(
+        r#"
+        SELECT newsletter_issue_id FROM newsletter_issues
+        WHERE newsletter_issue_id = $1 AND status = 'draft'
+        FOR UPDATE
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to retrieve the draft issue")
+    .map_err(e500)?;
+    if draft.is_none() {
+        return unpublishable_issue(&mut *db_transaction, newsletter_issue_id).await;
+    }
+
+    match send_at {
+        Some(send_at) => {
+            sqlx::query!(
+                r#"
+                UPDATE newsletter_issues SET status = 'scheduled', send_at = $2
+                WHERE newsletter_issue_id = $1
+                "#,
+                newsletter_issue_id,
+                send_at
+            )
+            .execute(&mut *db_transaction)
+            .await
+            .context("Failed to schedule the draft issue")
+            .map_err(e500)?;
+        }
+        None => publish_issue(&mut db_transaction, newsletter_issue_id)
+            .await
+            .map_err(e500)?,
+    }
+
+    let issue = get_issue(&mut *db_transaction, newsletter_issue_id)
+        .await
+        .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to publish the draft issue")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(issue))
+}
+
+// Shows the issue as its recipients will receive it
+#[tracing::instrument(name = "Preview issue", skip(db_connection_pool, application_base_url))]
+#[get("/issues/{newsletter_issue_id}/preview")]
+pub async fn preview_issue(
+    db_connection_pool: web::Data<PgPool>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    newsletter_issue_id: web::Path<Uuid>,
+    parameters: web::Query<PreviewParameters>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let Some(issue) = get_issue_content(db_connection_pool.as_ref(), *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let issue = issue.personalize_for_sample(&application_base_url.0);
+
+    match parameters.format.as_deref() {
+        None | Some("html") => Ok(HttpResponse::Ok()
+            .content_type(ContentType::html())
+            .body(issue.html_content)),
+        Some("text") => Ok(HttpResponse::Ok()
+            .content_type(ContentType::plaintext())
+            .body(issue.text_content)),
+        Some(format) => Err(e400(format!("{} is not a valid preview format", format))),
+    }
+}
+
+// Seed addresses get the issue exactly as subscribers would, without touching the delivery
+// queue or the delivery history
+#[tracing::instrument(
+    name = "Send test issue",
+    skip(db_connection_pool, email_client, application_base_url, test_data)
+)]
+#[post("/issues/{newsletter_issue_id}/test")]
+pub async fn send_test_issue(
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    newsletter_issue_id: web::Path<Uuid>,
+    test_data: web::Json<TestSendData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let test_data = test_data.into_inner();
+    if test_data.recipients.is_empty() {
+        return Err(e400("At least one recipient is required"));
+    }
+    let recipients = test_data
+        .recipients
+        .into_iter()
+        .map(SubscriberEmail::parse)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(e400)?;
+    for recipient in &recipients {
+        if is_suppressed(db_connection_pool.as_ref(), recipient.as_ref())
+            .await
+            .map_err(e500)?
+        {
+            return Err(e400(format!("{} is on the suppression list", recipient)));
+        }
+    }
+
+    let Some(issue) = get_issue_content(db_connection_pool.as_ref(), *newsletter_issue_id)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    let issue = issue.personalize_for_sample(&application_base_url.0);
+
+    for recipient in &recipients {
+        email_client
+            .send_email(
+                recipient,
+                &issue.title,
+                &issue.html_content,
+                &issue.text_content,
+            )
+            .await
+            .context("Failed to send a test issue")
+            .map_err(e500)?;
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Opens are only known for issues sent with `track_opens`, and only for readers whose email
+// client loads images
+#[tracing::instrument(name = "Get issue open statistics", skip(db_connection_pool))]
+#[get("/issues/{newsletter_issue_id}/opens")]
+pub async fn get_issue_opens(
+    db_connection_pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let opens = sqlx::query!(
+        r#"
+        SELECT i.track_opens,
+            (
+                SELECT COUNT(*) FROM newsletter_deliveries d
+                WHERE d.newsletter_issue_id = i.newsletter_issue_id AND d.outcome = 'delivered'
+            ) AS "delivered!",
+            (
+                SELECT COUNT(*) FROM newsletter_opens o
+                WHERE o.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "unique_opens!",
+            (
+                SELECT COALESCE(SUM(o.open_count), 0)::BIGINT FROM newsletter_opens o
+                WHERE o.newsletter_issue_id = i.newsletter_issue_id
+            ) AS "total_opens!"
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the open statistics of the issue")
+    .map_err(e500)?;
+    let Some(opens) = opens else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    Ok(HttpResponse::Ok().json(IssueOpens {
+        newsletter_issue_id,
+        track_opens: opens.track_opens,
+        delivered: opens.delivered,
+        unique_opens: opens.unique_opens,
+        total_opens: opens.total_opens,
+        open_rate: if opens.delivered > 0 {
+            opens.unique_opens as f64 / opens.delivered as f64
+        } else {
+            0.0
+        },
+    }))
+}
+
+// Links are listed from the most to the least clicked
+#[tracing::instrument(name = "Get issue click statistics", skip(db_connection_pool))]
+#[get("/issues/{newsletter_issue_id}/clicks")]
+pub async fn get_issue_clicks(
+    db_connection_pool: web::Data<PgPool>,
+    newsletter_issue_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let newsletter_issue_id = newsletter_issue_id.into_inner();
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let issue = sqlx::query!(
+        r#"SELECT newsletter_issue_id FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to retrieve the newsletter issue")
+    .map_err(e500)?;
+    if issue.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let links = sqlx::query_as!(
+        LinkClicks,
+        r#"
+        SELECT url,
+            COUNT(*) AS "total_clicks!",
+            COUNT(DISTINCT subscriber_id) AS "unique_clicks!"
+        FROM newsletter_clicks
+        WHERE newsletter_issue_id = $1
+        GROUP BY url
+        ORDER BY 2 DESC, url
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(&mut *db_transaction)
+    .await
+    .context("Failed to retrieve the click statistics of the issue")
+    .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to read the click statistics")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(IssueClicks {
+        newsletter_issue_id,
+        links,
+    }))
+}
+
+#[tracing::instrument(skip(db_transaction))]
+async fn attach_lists(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+    list_slugs: &[ListSlug],
+) -> Result<(), actix_web::Error> {
+    let list_ids = match get_list_ids(db_transaction, list_slugs)
+        .await
+        .map_err(e500)?
+    {
+        Ok(list_ids) => list_ids,
+        Err(unknown_list) => {
+            return Err(e400(format!("There is no list named {}", unknown_list)));
+        }
+    };
+
+    set_issue_lists(db_transaction, newsletter_issue_id, &list_ids)
+        .await
+        .map_err(e500)
+}
+
+// Tells apart issues that do not exist from issues that are already published or cancelled
+async fn unchangeable_issue(
+    db_executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_executor)
+    .await
+    .context("Failed to retrieve the newsletter issue")
+    .map_err(e500)?;
+
+    match issue {
+        Some(issue) => Err(actix_web::error::ErrorConflict(format!(
+            "The issue is {}, only drafts and scheduled issues can be changed",
+            issue.status
+        ))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+async fn unpublishable_issue(
+    db_executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"SELECT status FROM newsletter_issues WHERE newsletter_issue_id = $1"#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_executor)
+    .await
+    .context("Failed to retrieve the newsletter issue")
+    .map_err(e500)?;
+
+    match issue {
+        Some(issue) => Err(actix_web::error::ErrorConflict(format!(
+            "The issue is {}, only drafts can be published",
+            issue.status
+        ))),
+        None => Ok(HttpResponse::NotFound().finish()),
+    }
+}
+
+async fn get_issue(
+    db_executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<Issue, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        Issue,
+        r#"
+        SELECT i.newsletter_issue_id, i.title, i.status, i.slug, i.subscribers_only, i.track_opens,
+            ARRAY(
+                SELECT l.slug
+                FROM newsletter_issue_lists il
+                JOIN lists l ON l.id = il.list_id
+                WHERE il.newsletter_issue_id = i.newsletter_issue_id
+                ORDER BY l.slug
+            ) AS "lists!",
+            i.created_at, i.send_at, i.published_at
+        FROM newsletter_issues i
+        WHERE i.newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_one(db_executor)
+    .await
+    .context("Failed to retrieve the newsletter issue")?;
+
+    Ok(issue)
+}
+
+async fn get_issue_content(
+    db_executor: impl PgExecutor<'_>,
+    newsletter_issue_id: Uuid,
+) -> Result<Option<IssueEmail>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        IssueEmail,
+        r#"
+        SELECT title, html_content, text_content, slug, subscribers_only
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(db_executor)
+    .await
+    .context("Failed to retrieve the content of the newsletter issue")?;
+
+    Ok(issue)
+}
@@ -0,0 +1,88 @@
+use actix_web::{get, post, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    models::ListSlug,
+    utils::{e400, e500},
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct NewListData {
+    slug: String,
+    name: String,
+}
+
+#[derive(serde::Serialize)]
+struct List {
+    id: Uuid,
+    slug: String,
+    name: String,
+    created_at: DateTime<Utc>,
+    confirmed_members: i64,
+}
+
+#[tracing::instrument(name = "List mailing lists", skip(db_connection_pool))]
+#[get("/lists")]
+pub async fn list_lists(
+    db_connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let lists = sqlx::query_as!(
+        List,
+        r#"
+        SELECT l.id, l.slug, l.name, l.created_at,
+            COUNT(m.subscriber_id) FILTER (WHERE m.status = 'confirmed') AS "confirmed_members!"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.id
+        GROUP BY l.id
+        ORDER BY l.created_at, l.slug
+        "#
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the lists")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(lists))
+}
+
+#[tracing::instrument(name = "Create mailing list", skip(db_connection_pool))]
+#[post("/lists")]
+pub async fn create_list(
+    db_connection_pool: web::Data<PgPool>,
+    list_data: web::Json<NewListData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let list_data = list_data.into_inner();
+    let slug = ListSlug::parse(list_data.slug).map_err(e400)?;
+    let name = list_data.name.trim();
+    if name.is_empty() {
+        return Err(e400("The name of a list cannot be empty"));
+    }
+
+    let list = sqlx::query_as!(
+        List,
+        r#"
+        INSERT INTO lists (id, slug, name, created_at)
+        VALUES ($1, $2, $3, now())
+        ON CONFLICT (slug) DO NOTHING
+        RETURNING id, slug, name, created_at, 0::bigint AS "confirmed_members!"
+        "#,
+        Uuid::new_v4(),
+        slug.as_ref(),
+        name
+    )
+    .fetch_optional(db_connection_pool.as_ref())
+    .await
+    .context("Failed to create the list")
+    .map_err(e500)?;
+
+    match list {
+        Some(list) => Ok(HttpResponse::Created().json(list)),
+        None => Err(actix_web::error::ErrorConflict(format!(
+            "A list named {} already exists",
+            slug
+        ))),
+    }
+}
@@ -0,0 +1,17 @@
+mod dashboard;
+mod email;
+mod issues;
+mod lists;
+mod password;
+mod subscribers;
+mod subscribers_csv;
+mod suppressions;
+
+pub use dashboard::*;
+pub use email::*;
+pub use issues::*;
+pub use lists::*;
+pub use password::*;
+pub use subscribers::*;
+pub use subscribers_csv::*;
+pub use suppressions::*;
@@ -0,0 +1,92 @@
+use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use askama_actix::Template;
+use secrecy::{ExposeSecret, SecretString};
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{change_password, validate_credentials, Credentials, UserId},
+    errors::AuthError,
+    models::NewPassword,
+    routes::get_username,
+    session::TypedSession,
+    templates::ChangePasswordTemplate,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct ChangePasswordFormData {
+    current_password: SecretString,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+#[get("/password")]
+pub async fn change_password_form(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    let message = session.take_flash_message();
+    let html_body = ChangePasswordTemplate {
+        message: message.as_deref(),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Change admin password",
+    skip(form_data, db_connection_pool, session),
+    fields(user_id = %*user_id)
+)]
+#[post("/password")]
+pub async fn change_admin_password(
+    form_data: web::Form<ChangePasswordFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    session: TypedSession,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let user_id = user_id.into_inner();
+    let form_data = form_data.into_inner();
+
+    if form_data.new_password.expose_secret() != form_data.new_password_check.expose_secret() {
+        return redirect_with_message(
+            &session,
+            "You entered two different new passwords - the field values must match.",
+        );
+    }
+    let new_password = match NewPassword::parse(form_data.new_password) {
+        Ok(new_password) => new_password,
+        Err(e) => return redirect_with_message(&session, &e),
+    };
+
+    let username = get_username(*user_id, &db_connection_pool)
+        .await
+        .map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form_data.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &db_connection_pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                redirect_with_message(&session, "The current password is incorrect.")
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    change_password(*user_id, new_password, db_connection_pool.get_ref())
+        .await
+        .map_err(e500)?;
+
+    redirect_with_message(&session, "Your password has been changed.")
+}
+
+fn redirect_with_message(
+    session: &TypedSession,
+    message: &str,
+) -> Result<HttpResponse, actix_web::Error> {
+    session.insert_flash_message(message).map_err(e500)?;
+    Ok(see_other("/admin/password"))
+}
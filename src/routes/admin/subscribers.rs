@@ -0,0 +1,371 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditActor},
+    authentication::UserId,
+    models::{SubscribersCursor, UnsubscribeToken},
+    utils::{e400, e500},
+};
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ListSubscribersParameters {
+    status: Option<String>,
+    // Case insensitive substring matched against both the email and the name
+    search: Option<String>,
+    subscribed_after: Option<DateTime<Utc>>,
+    subscribed_before: Option<DateTime<Utc>>,
+    limit: Option<i64>,
+    cursor: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+pub struct Subscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct SubscribersPage {
+    subscribers: Vec<Subscriber>,
+    next_cursor: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ConfirmationToken {
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct ListMembership {
+    list: String,
+    status: String,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberDetails {
+    #[serde(flatten)]
+    subscriber: Subscriber,
+    lists: Vec<ListMembership>,
+    confirmation_tokens: Vec<ConfirmationToken>,
+}
+
+// Subscribers are listed newest first. Pages are delimited by a cursor on `(subscribed_at, id)`
+// instead of an offset, so that subscriptions coming in while paginating do not shift the pages
+#[tracing::instrument(name = "List subscribers", skip(db_connection_pool))]
+#[get("/subscribers")]
+pub async fn list_subscribers(
+    db_connection_pool: web::Data<PgPool>,
+    parameters: web::Query<ListSubscribersParameters>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let parameters = parameters.into_inner();
+    let limit = parameters.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+    if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+        return Err(e400(format!(
+            "limit must be between 1 and {}",
+            MAX_PAGE_SIZE
+        )));
+    }
+    let cursor = parameters
+        .cursor
+        .map(SubscribersCursor::parse)
+        .transpose()
+        .map_err(e400)?;
+    let search_pattern = parameters
+        .search
+        .map(|search| format!("%{}%", escape_like_pattern(&search)));
+
+    // One extra row is fetched to know whether there is a next page
+    let mut subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR email ILIKE $2 OR name ILIKE $2)
+            AND ($3::timestamptz IS NULL OR subscribed_at >= $3)
+            AND ($4::timestamptz IS NULL OR subscribed_at < $4)
+            AND ($5::timestamptz IS NULL OR (subscribed_at, id) < ($5, $6::uuid))
+        ORDER BY subscribed_at DESC, id DESC
+        LIMIT $7
+        "#,
+        parameters.status,
+        search_pattern,
+        parameters.subscribed_after,
+        parameters.subscribed_before,
+        cursor.as_ref().map(|c| c.subscribed_at),
+        cursor.as_ref().map(|c| c.id),
+        limit + 1
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to list subscribers")
+    .map_err(e500)?;
+
+    let next_cursor = if subscribers.len() as i64 > limit {
+        subscribers.truncate(limit as usize);
+        subscribers.last().map(|last| {
+            SubscribersCursor {
+                subscribed_at: last.subscribed_at,
+                id: last.id,
+            }
+            .encode()
+        })
+    } else {
+        None
+    };
+
+    Ok(HttpResponse::Ok().json(SubscribersPage {
+        subscribers,
+        next_cursor,
+    }))
+}
+
+#[tracing::instrument(name = "Get subscriber", skip(db_connection_pool))]
+#[get("/subscribers/{subscriber_id}")]
+pub async fn get_subscriber(
+    db_connection_pool: web::Data<PgPool>,
+    subscriber_id: web::Path<Uuid>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the subscriber")
+    .map_err(e500)?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let lists = sqlx::query_as!(
+        ListMembership,
+        r#"
+        SELECT l.slug AS list, m.status
+        FROM list_memberships m
+        JOIN lists l ON l.id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the subscriber's list memberships")
+    .map_err(e500)?;
+
+    let confirmation_tokens = sqlx::query_as!(
+        ConfirmationToken,
+        r#"
+        SELECT created_at, consumed_at
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        ORDER BY created_at DESC
+        "#,
+        subscriber_id
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the subscriber's confirmation tokens")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(SubscriberDetails {
+        subscriber,
+        lists,
+        confirmation_tokens,
+    }))
+}
+
+// Removes the subscriber altogether, the audit trail keeps a record of who did it
+#[tracing::instrument(name = "Delete subscriber", skip(db_connection_pool, user_id))]
+#[delete("/subscribers/{subscriber_id}")]
+pub async fn delete_subscriber(
+    db_connection_pool: web::Data<PgPool>,
+    subscriber_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's confirmation tokens")
+    .map_err(e500)?;
+    let deleted_subscriber = sqlx::query!(
+        r#"DELETE FROM subscriptions WHERE id = $1 RETURNING id"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber")
+    .map_err(e500)?;
+    if deleted_subscriber.is_none() {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    record_audit_event(
+        &mut db_transaction,
+        subscriber_id,
+        AuditAction::Deleted,
+        AuditActor::Admin(*user_id.into_inner()),
+    )
+    .await
+    .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to delete the subscriber")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Right to erasure: the personal data is overwritten instead of deleting the row, so that
+// delivery counts and the audit trail still add up. The operation cannot be undone
+#[tracing::instrument(name = "Erase subscriber", skip(db_connection_pool, user_id))]
+#[post("/subscribers/{subscriber_id}/erase")]
+pub async fn erase_subscriber(
+    db_connection_pool: web::Data<PgPool>,
+    subscriber_id: web::Path<Uuid>,
+    user_id: web::ReqData<UserId>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let subscriber_id = subscriber_id.into_inner();
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+    let subscriber = sqlx::query!(
+        r#"SELECT email, status FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to retrieve the subscriber")
+    .map_err(e500)?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+    if subscriber.status == "erased" {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's confirmation tokens")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM data_export_tokens WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's data export tokens")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM list_memberships WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's list memberships")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM issue_delivery_queue WHERE subscriber_email = $1"#,
+        subscriber.email
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's pending deliveries")
+    .map_err(e500)?;
+    sqlx::query!(
+        r#"DELETE FROM digest_queue WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete the subscriber's pending digest")
+    .map_err(e500)?;
+    // Open counts are kept for the statistics, but not the browser details
+    sqlx::query!(
+        r#"UPDATE newsletter_opens SET user_agent = NULL WHERE subscriber_id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to forget the subscriber's user agents")
+    .map_err(e500)?;
+    // The unsubscribe token is replaced too, as it was sent to the erased address
+    let unsubscribe_token = UnsubscribeToken::generate();
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET email = 'erased-' || id || '@erased.invalid',
+            name = 'erased',
+            status = 'erased',
+            unsubscribe_token = $2
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        unsubscribe_token.as_ref()
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to anonymise the subscriber")
+    .map_err(e500)?;
+
+    record_audit_event(
+        &mut db_transaction,
+        subscriber_id,
+        AuditAction::Erased,
+        AuditActor::Admin(*user_id.into_inner()),
+    )
+    .await
+    .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to erase the subscriber")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// `%` and `_` typed by the admin are matched literally
+fn escape_like_pattern(search: &str) -> String {
+    search
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
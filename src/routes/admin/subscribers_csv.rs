@@ -0,0 +1,428 @@
+use std::collections::HashSet;
+
+use actix_web::{
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    post, web, HttpResponse,
+};
+use anyhow::Context;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures_util::stream;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    email_client::EmailSender,
+    lists::{get_list_ids, set_membership_status},
+    models::{ListSlug, NewSubscriber, SubscriberEmail, SubscriberName, UnsubscribeToken},
+    routes::{create_and_store_subscription_token, send_confirmation_email},
+    startup::{ApplicationBaseUrl, ConfirmationEmailsPerDay},
+    suppressions::{is_suppressed, suppress, SuppressionReason, SuppressionSource},
+    utils::{e400, e500},
+};
+
+const EXPORT_BATCH_SIZE: i64 = 500;
+// Every status but `erased`, which exports leave out, so that a backup can always be restored
+const IMPORTABLE_STATUSES: [&str; 5] = [
+    "pending_confirmation",
+    "confirmed",
+    "unsubscribed",
+    "bounced",
+    "complained",
+];
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ImportParameters {
+    #[serde(default)]
+    dry_run: bool,
+    #[serde(default)]
+    send_confirmation_emails: bool,
+}
+
+// Columns of the CSV files, exports use the same layout. `status` defaults to
+// `pending_confirmation` and `subscribed_at` to the time of the import
+#[derive(serde::Deserialize)]
+struct ImportRow {
+    email: String,
+    name: String,
+    #[serde(default)]
+    status: Option<String>,
+    #[serde(default)]
+    subscribed_at: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ExportRow {
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: String,
+}
+
+struct ExportedSubscriber {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+struct ValidImportRow {
+    subscriber: NewSubscriber,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct RowError {
+    // Line in the file, the header being line 1
+    line: u64,
+    error: String,
+}
+
+#[derive(serde::Serialize)]
+struct ImportReport {
+    dry_run: bool,
+    imported: usize,
+    errors: Vec<RowError>,
+    confirmation_emails_sent: usize,
+}
+
+// Invalid rows are reported and skipped while the valid ones are imported. A dry run goes
+// through exactly the same steps but rolls the SQL transaction back
+#[tracing::instrument(
+    name = "Import subscribers",
+    skip(
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        confirmation_emails_per_day,
+        body
+    )
+)]
+#[post("/subscribers/import")]
+pub async fn import_subscribers(
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    confirmation_emails_per_day: web::Data<ConfirmationEmailsPerDay>,
+    parameters: web::Query<ImportParameters>,
+    body: web::Bytes,
+) -> Result<HttpResponse, actix_web::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let mut errors = vec![];
+    let mut seen_emails = HashSet::new();
+    let mut imported = 0;
+    let mut pending_confirmations = vec![];
+
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(body.as_ref());
+    let headers = reader
+        .headers()
+        .cloned()
+        .map_err(|e| e400(format!("Invalid CSV header: {}", e)))?;
+    for record in reader.records() {
+        let line = match &record {
+            Ok(record) => record.position().map(|p| p.line()),
+            Err(e) => e.position().map(|p| p.line()),
+        }
+        .unwrap_or_default();
+        let row = match record
+            .and_then(|record| record.deserialize::<ImportRow>(Some(&headers)))
+            .map_err(|e| format!("Invalid CSV record: {}", e))
+            .and_then(parse_import_row)
+        {
+            Ok(row) => row,
+            Err(error) => {
+                errors.push(RowError { line, error });
+                continue;
+            }
+        };
+
+        if !seen_emails.insert(row.subscriber.email.as_ref().to_lowercase()) {
+            errors.push(RowError {
+                line,
+                error: "The email appears more than once in the file".into(),
+            });
+            continue;
+        }
+        if subscriber_exists(&mut db_transaction, &row.subscriber.email)
+            .await
+            .map_err(e500)?
+        {
+            errors.push(RowError {
+                line,
+                error: "The email is already on the list".into(),
+            });
+            continue;
+        }
+        // Bounced and complained addresses are expected on the suppression list, they are added
+        // to it when missing, like the Postmark webhook does
+        let suppression_reason = SuppressionReason::try_from(row.status.as_str()).ok();
+        if let Some(reason) = suppression_reason {
+            suppress(
+                &mut *db_transaction,
+                row.subscriber.email.as_ref(),
+                reason,
+                SuppressionSource::Admin,
+            )
+            .await
+            .map_err(e500)?;
+        } else if is_suppressed(&mut *db_transaction, row.subscriber.email.as_ref())
+            .await
+            .map_err(e500)?
+        {
+            errors.push(RowError {
+                line,
+                error: "The email is on the suppression list".into(),
+            });
+            continue;
+        }
+
+        let subscriber_id = insert_imported_subscriber(&mut db_transaction, &row)
+            .await
+            .map_err(e500)?;
+        imported += 1;
+        if row.status == "pending_confirmation" && parameters.send_confirmation_emails {
+            let subscription_token =
+                create_and_store_subscription_token(&mut db_transaction, subscriber_id)
+                    .await
+                    .map_err(e500)?;
+            pending_confirmations.push((subscriber_id, row.subscriber, subscription_token));
+        }
+    }
+
+    if parameters.dry_run {
+        db_transaction
+            .rollback()
+            .await
+            .context("Failed to roll back the dry run of the import")
+            .map_err(e500)?;
+        return Ok(HttpResponse::Ok().json(ImportReport {
+            dry_run: true,
+            imported,
+            errors,
+            confirmation_emails_sent: 0,
+        }));
+    }
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to import subscribers")
+        .map_err(e500)?;
+
+    // A failed email does not undo the import, the subscriber can ask for a new one by
+    // subscribing again
+    let mut confirmation_emails_sent = 0;
+    for (subscriber_id, subscriber, subscription_token) in pending_confirmations {
+        match send_confirmation_email(
+            &db_connection_pool,
+            email_client.as_ref(),
+            subscriber_id,
+            subscriber,
+            &application_base_url.0,
+            &subscription_token,
+            confirmation_emails_per_day.0,
+        )
+        .await
+        {
+            Ok(true) => confirmation_emails_sent += 1,
+            Ok(false) => {}
+            Err(e) => tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send a confirmation email to an imported subscriber"
+            ),
+        }
+    }
+
+    Ok(HttpResponse::Ok().json(ImportReport {
+        dry_run: false,
+        imported,
+        errors,
+        confirmation_emails_sent,
+    }))
+}
+
+// Rows are fetched in batches while the response is being sent, so the whole list is never
+// held in memory. Erased subscribers are left out, nothing is left of them worth restoring
+#[tracing::instrument(name = "Export subscribers", skip(db_connection_pool))]
+#[get("/subscribers/export.csv")]
+pub async fn export_subscribers(
+    db_connection_pool: web::Data<PgPool>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let db_connection_pool = db_connection_pool.into_inner();
+
+    // The state is the position of the last exported subscriber, `None` once everything is sent
+    let batches = stream::try_unfold(Some(None), move |cursor| {
+        let db_connection_pool = db_connection_pool.clone();
+        async move {
+            let Some(cursor) = cursor else {
+                return Ok(None);
+            };
+            let batch = fetch_export_batch(&db_connection_pool, cursor)
+                .await
+                .map_err(e500)?;
+            let next_cursor = match batch.last() {
+                Some(last) if batch.len() as i64 == EXPORT_BATCH_SIZE => {
+                    Some(Some((last.subscribed_at, last.id)))
+                }
+                _ => None,
+            };
+            let chunk = write_csv_rows(batch, cursor.is_none()).map_err(e500)?;
+
+            Ok::<_, actix_web::Error>(Some((chunk, next_cursor)))
+        }
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/csv; charset=utf-8")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("subscribers.csv".into())],
+        })
+        .streaming(batches))
+}
+
+fn parse_import_row(row: ImportRow) -> Result<ValidImportRow, String> {
+    let email = SubscriberEmail::parse(row.email)?;
+    let name = SubscriberName::parse(row.name)?;
+    let status = match row.status.filter(|s| !s.is_empty()) {
+        Some(status) if IMPORTABLE_STATUSES.contains(&status.as_str()) => status,
+        Some(status) => return Err(format!("{} is not a valid status", status)),
+        None => "pending_confirmation".into(),
+    };
+    let subscribed_at = match row.subscribed_at.filter(|s| !s.is_empty()) {
+        Some(subscribed_at) => DateTime::parse_from_rfc3339(&subscribed_at)
+            .map_err(|_| format!("{} is not a valid RFC 3339 date", subscribed_at))?
+            .with_timezone(&Utc),
+        None => Utc::now(),
+    };
+
+    Ok(ValidImportRow {
+        subscriber: NewSubscriber { email, name },
+        status,
+        subscribed_at,
+    })
+}
+
+fn write_csv_rows(
+    subscribers: Vec<ExportedSubscriber>,
+    with_header: bool,
+) -> Result<web::Bytes, anyhow::Error> {
+    let mut writer = csv::WriterBuilder::new()
+        .has_headers(with_header)
+        .from_writer(vec![]);
+    if with_header && subscribers.is_empty() {
+        writer.write_record(["email", "name", "status", "subscribed_at"])?;
+    }
+    for subscriber in subscribers {
+        writer.serialize(ExportRow {
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status,
+            subscribed_at: subscriber
+                .subscribed_at
+                .to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        })?;
+    }
+    let content = writer
+        .into_inner()
+        .context("Failed to write the CSV content")?;
+
+    Ok(web::Bytes::from(content))
+}
+
+// Case is ignored, as it is for duplicates within the file
+#[tracing::instrument(skip_all)]
+async fn subscriber_exists(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<bool, anyhow::Error> {
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE lower(email) = lower($1)"#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to look up an existing subscriber")?;
+
+    Ok(subscriber.is_some())
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_imported_subscriber(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    row: &ValidImportRow,
+) -> Result<Uuid, anyhow::Error> {
+    let subscriber_id = Uuid::new_v4();
+    let unsubscribe_token = UnsubscribeToken::generate();
+    let default_list = ListSlug::default_list();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        subscriber_id,
+        row.subscriber.email.as_ref(),
+        row.subscriber.name.as_ref(),
+        row.subscribed_at,
+        row.status,
+        unsubscribe_token.as_ref()
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to insert an imported subscriber")?;
+
+    // Files carry no list column, imported subscribers join the default list. Bounced and
+    // complained subscribers are off every list, as they would be after the webhook
+    let membership_status = match row.status.as_str() {
+        "bounced" | "complained" => "unsubscribed",
+        status => status,
+    };
+    let list_ids = get_list_ids(db_transaction, std::slice::from_ref(&default_list))
+        .await?
+        .map_err(|_| anyhow::anyhow!("The default list does not exist"))?;
+    set_membership_status(
+        db_transaction,
+        subscriber_id,
+        list_ids[0],
+        membership_status,
+    )
+    .await?;
+
+    Ok(subscriber_id)
+}
+
+#[tracing::instrument(skip(db_connection_pool))]
+async fn fetch_export_batch(
+    db_connection_pool: &PgPool,
+    cursor: Option<(DateTime<Utc>, Uuid)>,
+) -> Result<Vec<ExportedSubscriber>, anyhow::Error> {
+    let subscribers = sqlx::query_as!(
+        ExportedSubscriber,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE status <> 'erased'
+            AND ($1::timestamptz IS NULL OR (subscribed_at, id) > ($1, $2::uuid))
+        ORDER BY subscribed_at, id
+        LIMIT $3
+        "#,
+        cursor.map(|(subscribed_at, _)| subscribed_at),
+        cursor.map(|(_, id)| id),
+        EXPORT_BATCH_SIZE
+    )
+    .fetch_all(db_connection_pool)
+    .await
+    .context("Failed to fetch a batch of subscribers to export")?;
+
+    Ok(subscribers)
+}
@@ -0,0 +1,142 @@
+use actix_web::{delete, get, post, web, HttpResponse};
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+use crate::{
+    models::SubscriberEmail,
+    suppressions::{suppress, SuppressionReason, SuppressionSource},
+    utils::{e400, e500},
+};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ListSuppressionsParameters {
+    reason: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct NewSuppressionData {
+    email: String,
+    // `manual` when missing
+    reason: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct Suppression {
+    email: String,
+    reason: String,
+    source: String,
+    created_at: DateTime<Utc>,
+}
+
+#[tracing::instrument(name = "List suppressions", skip(db_connection_pool))]
+#[get("/suppressions")]
+pub async fn list_suppressions(
+    db_connection_pool: web::Data<PgPool>,
+    parameters: web::Query<ListSuppressionsParameters>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let reason = parameters
+        .into_inner()
+        .reason
+        .map(|reason| SuppressionReason::try_from(reason.as_str()))
+        .transpose()
+        .map_err(e400)?;
+
+    let suppressions = sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT email, reason, source, created_at
+        FROM suppressions
+        WHERE ($1::text IS NULL OR reason = $1)
+        ORDER BY created_at DESC, email
+        "#,
+        reason.map(|reason| reason.as_str())
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the suppressions")
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok().json(suppressions))
+}
+
+#[tracing::instrument(name = "Add suppression", skip(db_connection_pool))]
+#[post("/suppressions")]
+pub async fn add_suppression(
+    db_connection_pool: web::Data<PgPool>,
+    suppression_data: web::Json<NewSuppressionData>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let suppression_data = suppression_data.into_inner();
+    let email = SubscriberEmail::parse(suppression_data.email).map_err(e400)?;
+    let reason = match suppression_data.reason {
+        Some(reason) => SuppressionReason::try_from(reason.as_str()).map_err(e400)?,
+        None => SuppressionReason::Manual,
+    };
+
+    let added = suppress(
+        db_connection_pool.as_ref(),
+        email.as_ref(),
+        reason,
+        SuppressionSource::Admin,
+    )
+    .await
+    .map_err(e500)?;
+    if !added {
+        return Err(actix_web::error::ErrorConflict(format!(
+            "{} is already suppressed",
+            email
+        )));
+    }
+
+    let suppression = get_suppression(db_connection_pool.as_ref(), email.as_ref())
+        .await
+        .map_err(e500)?
+        .context("The new suppression was not found")
+        .map_err(e500)?;
+
+    Ok(HttpResponse::Created().json(suppression))
+}
+
+// The address can subscribe and be emailed again straight away
+#[tracing::instrument(name = "Remove suppression", skip(db_connection_pool))]
+#[delete("/suppressions/{email}")]
+pub async fn remove_suppression(
+    db_connection_pool: web::Data<PgPool>,
+    email: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM suppressions WHERE email = lower($1)"#,
+        email.as_str()
+    )
+    .execute(db_connection_pool.as_ref())
+    .await
+    .context("Failed to remove the suppression")
+    .map_err(e500)?;
+
+    if result.rows_affected() == 0 {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[tracing::instrument(skip(db_connection_pool))]
+async fn get_suppression(
+    db_connection_pool: &PgPool,
+    email: &str,
+) -> Result<Option<Suppression>, anyhow::Error> {
+    let suppression = sqlx::query_as!(
+        Suppression,
+        r#"
+        SELECT email, reason, source, created_at
+        FROM suppressions
+        WHERE email = lower($1)
+        "#,
+        email
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to retrieve the suppression")?;
+
+    Ok(suppression)
+}
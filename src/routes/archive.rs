@@ -0,0 +1,122 @@
+use actix_web::{get, http::header::ContentType, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use sqlx::PgPool;
+
+use crate::{
+    issues::body_content,
+    personalization::{personalize_html, personalize_text, Recipient},
+    startup::ApplicationBaseUrl,
+    templates::{ArchiveIssueTemplate, ArchiveTemplate, ArchivedIssue},
+    utils::e500,
+};
+
+const PUBLISHED_ON_FORMAT: &str = "%Y-%m-%d";
+const READER_NAME: &str = "reader";
+
+// Points to the issue when it is in the public archive, to the archive itself otherwise
+pub fn archive_link(application_base_url: &str, public_slug: Option<&str>) -> String {
+    match public_slug {
+        Some(slug) => format!("{}/archive/{}", application_base_url, slug),
+        None => format!("{}/archive", application_base_url),
+    }
+}
+
+// Archive readers are not subscribers: they get a generic greeting, and the archive stands in
+// for the unsubscribe link
+fn reader<'a>(archive_index_url: &'a str, archive_url: &'a str) -> Recipient<'a> {
+    Recipient {
+        name: READER_NAME,
+        unsubscribe_url: archive_index_url,
+        archive_url,
+    }
+}
+
+#[tracing::instrument(
+    name = "Show newsletter archive",
+    skip(db_connection_pool, application_base_url)
+)]
+#[get("/archive")]
+pub async fn archive_index(
+    db_connection_pool: web::Data<PgPool>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let archive_index_url = archive_link(&application_base_url.0, None);
+    let issues = sqlx::query!(
+        r#"
+        SELECT slug AS "slug!", title, published_at AS "published_at!"
+        FROM newsletter_issues
+        WHERE status = 'published' AND NOT subscribers_only
+        ORDER BY published_at DESC
+        "#
+    )
+    .fetch_all(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the archived newsletter issues")
+    .map_err(e500)?
+    .into_iter()
+    .map(|issue| ArchivedIssue {
+        title: personalize_text(
+            &issue.title,
+            &reader(
+                &archive_index_url,
+                &archive_link(&application_base_url.0, Some(&issue.slug)),
+            ),
+        ),
+        slug: issue.slug,
+        published_on: issue.published_at.format(PUBLISHED_ON_FORMAT).to_string(),
+    })
+    .collect::<Vec<_>>();
+
+    let html_body = ArchiveTemplate { issues: &issues }.render().map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+// Subscriber-only issues are answered with a 404, exactly like issues that do not exist
+#[tracing::instrument(
+    name = "Show archived newsletter issue",
+    skip(db_connection_pool, application_base_url)
+)]
+#[get("/archive/{slug}")]
+pub async fn archived_issue(
+    db_connection_pool: web::Data<PgPool>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    slug: web::Path<String>,
+) -> Result<HttpResponse, actix_web::Error> {
+    let issue = sqlx::query!(
+        r#"
+        SELECT i.title, i.html_content, i.published_at AS "published_at!",
+            u.username AS "author?"
+        FROM newsletter_issues i
+        LEFT JOIN users u ON u.user_id = i.author_id
+        WHERE i.slug = $1 AND i.status = 'published' AND NOT i.subscribers_only
+        "#,
+        slug.as_str()
+    )
+    .fetch_optional(db_connection_pool.as_ref())
+    .await
+    .context("Failed to retrieve the archived newsletter issue")
+    .map_err(e500)?;
+    let Some(issue) = issue else {
+        return Ok(HttpResponse::NotFound().finish());
+    };
+
+    let archive_index_url = archive_link(&application_base_url.0, None);
+    let archive_url = archive_link(&application_base_url.0, Some(&slug));
+    let reader = reader(&archive_index_url, &archive_url);
+    let html_body = ArchiveIssueTemplate {
+        title: &personalize_text(&issue.title, &reader),
+        author: issue.author.as_deref(),
+        published_on: &issue.published_at.format(PUBLISHED_ON_FORMAT).to_string(),
+        html_content: &personalize_html(body_content(&issue.html_content), &reader),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
@@ -0,0 +1,77 @@
+use actix_web::{error::InternalError, get, http::header::ContentType, post, web, HttpResponse};
+use askama_actix::Template;
+use secrecy::SecretString;
+use sqlx::PgPool;
+
+use crate::{
+    authentication::{validate_credentials, Credentials},
+    errors::{AuthError, LoginError},
+    session::TypedSession,
+    templates::LoginTemplate,
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct LoginFormData {
+    username: String,
+    password: SecretString,
+}
+
+#[get("/login")]
+pub async fn log_in_form(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    let error_message = session.take_flash_message();
+    let html_body = LoginTemplate {
+        error_message: error_message.as_deref(),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Log in",
+    skip(form_data, db_connection_pool, session),
+    fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
+#[post("/login")]
+pub async fn log_in(
+    form_data: web::Form<LoginFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, InternalError<LoginError>> {
+    let form_data = form_data.into_inner();
+    let credentials = Credentials {
+        username: form_data.username,
+        password: form_data.password,
+    };
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+
+    match validate_credentials(credentials, &db_connection_pool).await {
+        Ok(user_id) => {
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            session.renew();
+            session
+                .insert_user_id(user_id)
+                .map_err(|e| login_redirect(&session, LoginError::UnexpectedError(e.into())))?;
+            Ok(see_other("/admin/dashboard"))
+        }
+        Err(e) => {
+            let e = match e {
+                AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
+                AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
+            };
+            Err(login_redirect(&session, e))
+        }
+    }
+}
+
+// Sends the user back to the login form, which will display the error
+fn login_redirect(session: &TypedSession, e: LoginError) -> InternalError<LoginError> {
+    if let Err(insert_error) = session.insert_flash_message(&e.to_string()) {
+        tracing::warn!(error.cause_chain = ?insert_error, "Failed to store login error message");
+    }
+    InternalError::from_response(e, see_other("/login"))
+}
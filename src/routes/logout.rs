@@ -0,0 +1,17 @@
+use actix_web::{post, HttpResponse};
+
+use crate::{
+    session::TypedSession,
+    utils::{e500, see_other},
+};
+
+#[post("/logout")]
+pub async fn log_out(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    if session.get_user_id().map_err(e500)?.is_some() {
+        session.log_out();
+        session
+            .insert_flash_message("You have successfully logged out.")
+            .map_err(e500)?;
+    }
+    Ok(see_other("/login"))
+}
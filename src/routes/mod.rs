@@ -1,9 +1,29 @@
+mod admin;
+mod archive;
 mod health;
+mod login;
+mod logout;
 mod newsletters;
+mod password_reset;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data_export;
+mod subscriptions_preferences;
+mod subscriptions_unsubscribe;
+mod tracking;
+mod webhooks;
 
+pub use admin::*;
+pub use archive::*;
 pub use health::*;
+pub use login::*;
+pub use logout::*;
 pub use newsletters::*;
+pub use password_reset::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data_export::*;
+pub use subscriptions_preferences::*;
+pub use subscriptions_unsubscribe::*;
+pub use tracking::*;
+pub use webhooks::*;
@@ -1,67 +1,154 @@
-use actix_web::{post, web, HttpResponse};
+use actix_web::{http::header::HeaderMap, post, web, HttpRequest, HttpResponse};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
 use sqlx::PgPool;
 
-use crate::{email_client::EmailClient, errors::PublishError, models::SubscriberEmail};
+use crate::{
+    authentication::{basic_authentication, validate_credentials},
+    errors::{AuthError, PublishError},
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    issues::{insert_issue, publish_issue, set_issue_lists, NewIssue},
+    lists::{get_list_ids, parse_list_slugs},
+    models::IssueContent,
+    personalization::check_placeholders,
+};
 
 #[derive(serde::Deserialize)]
 struct EmailBodyData {
     title: String,
     content: EmailContentData,
+    // Slugs of the lists receiving the issue, the default list when missing
+    lists: Option<Vec<String>>,
+    // Keeps the issue out of the public archive
+    #[serde(default)]
+    subscribers_only: bool,
+    // Adds a tracking pixel to the HTML version
+    #[serde(default)]
+    track_opens: bool,
+    // Publishes the issue later instead of straight away
+    send_at: Option<DateTime<Utc>>,
 }
 
+// Either Markdown or both html and plain_text, see `IssueContent::parse`
 #[derive(serde::Deserialize)]
 struct EmailContentData {
-    html: String,
-    plain_text: String,
+    html: Option<String>,
+    plain_text: Option<String>,
+    markdown: Option<String>,
 }
 
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
-}
-
-#[tracing::instrument(name = "Get confirmed subscribers", skip(db_connection_pool))]
-async fn get_confirmed_subscribers(
-    db_connection_pool: &PgPool,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    let confirmed_subscribers =
-        sqlx::query!(r#"SELECT email FROM subscriptions WHERE status = 'confirmed'"#,)
-            .fetch_all(db_connection_pool)
-            .await?
-            .into_iter()
-            .map(|s| match SubscriberEmail::parse(s.email) {
-                Ok(email) => Ok(ConfirmedSubscriber { email }),
-                Err(e) => Err(anyhow::anyhow!(e)),
-            })
-            .collect();
+// The key is optional: requests without it are processed every time they are received
+fn get_idempotency_key(headers: &HeaderMap) -> Result<Option<IdempotencyKey>, PublishError> {
+    let Some(header_value) = headers.get("Idempotency-Key") else {
+        return Ok(None);
+    };
+    let idempotency_key = header_value
+        .to_str()
+        .map_err(|_| {
+            PublishError::ValidationError(
+                "The 'Idempotency-Key' header was not a valid UTF8 string".to_string(),
+            )
+        })?
+        .to_string();
 
-    Ok(confirmed_subscribers)
+    IdempotencyKey::parse(idempotency_key)
+        .map(Some)
+        .map_err(PublishError::ValidationError)
 }
 
+#[tracing::instrument(
+    name = "Publish a newsletter issue",
+    skip(request, db_connection_pool, email_body),
+    fields(username = tracing::field::Empty, user_id = tracing::field::Empty)
+)]
 #[post("/newsletters")]
 pub async fn publish_newsletter(
+    request: HttpRequest,
     db_connection_pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
     email_body: web::Json<EmailBodyData>,
 ) -> Result<HttpResponse, PublishError> {
-    let confirmed_subscribers = get_confirmed_subscribers(&db_connection_pool).await?;
-    for confirmed_subscriber in confirmed_subscribers {
-        match confirmed_subscriber {
-            Ok(s) => {
-                email_client
-                    .send_email(
-                        &s.email,
-                        &email_body.title,
-                        &email_body.content.html,
-                        &email_body.content.plain_text,
-                    )
-                    .await
-                    .with_context(|| format!("Failed to send newsletter issue to {}", s.email))?;
-            }
-            Err(e) => {
-                tracing::warn!(e.cause_chain = ?e, "Skipping a confirmed subscriber. Their stored email is invalid");
+    let credentials = basic_authentication(request.headers()).map_err(PublishError::AuthError)?;
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+
+    let user_id = validate_credentials(credentials, &db_connection_pool)
+        .await
+        .map_err(|e| match e {
+            AuthError::InvalidCredentials(_) => PublishError::AuthError(e.into()),
+            AuthError::UnexpectedError(_) => PublishError::UnexpectedError(e.into()),
+        })?;
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    let idempotency_key = get_idempotency_key(request.headers())?;
+    let mut email_body = email_body.into_inner();
+    let list_slugs =
+        parse_list_slugs(email_body.lists.take()).map_err(PublishError::ValidationError)?;
+    let content = IssueContent::parse(
+        email_body.content.html.take(),
+        email_body.content.plain_text.take(),
+        email_body.content.markdown.take(),
+    )
+    .map_err(PublishError::ValidationError)?;
+    check_placeholders(&email_body.title).map_err(PublishError::ValidationError)?;
+    // A send time that has already passed means publishing straight away
+    let send_at = email_body.send_at.filter(|send_at| *send_at > Utc::now());
+    let mut db_transaction = match &idempotency_key {
+        Some(idempotency_key) => {
+            match try_processing(&db_connection_pool, idempotency_key, user_id).await? {
+                NextAction::StartProcessing(db_transaction) => db_transaction,
+                NextAction::ReturnSavedResponse(saved_response) => return Ok(saved_response),
             }
         }
+        None => db_connection_pool
+            .begin()
+            .await
+            .context("Failed to acquire a Postgres connection from the pool")?,
+    };
+
+    // Scheduled issues are only stored, the scheduler publishes them once `send_at` is reached
+    let newsletter_issue_id = insert_issue(
+        &mut db_transaction,
+        &NewIssue {
+            title: &email_body.title,
+            html_content: &content.html,
+            text_content: &content.plain_text,
+            author_id: user_id,
+            subscribers_only: email_body.subscribers_only,
+            track_opens: email_body.track_opens,
+            status: if send_at.is_some() {
+                "scheduled"
+            } else {
+                "published"
+            },
+            send_at,
+        },
+    )
+    .await?;
+    let list_ids = match get_list_ids(&mut db_transaction, &list_slugs).await? {
+        Ok(list_ids) => list_ids,
+        Err(unknown_list) => {
+            return Err(PublishError::ValidationError(format!(
+                "There is no list named {}",
+                unknown_list
+            )))
+        }
+    };
+    set_issue_lists(&mut db_transaction, newsletter_issue_id, &list_ids).await?;
+    if send_at.is_none() {
+        publish_issue(&mut db_transaction, newsletter_issue_id).await?;
+    }
+
+    // Delivery happens in the background, the issue has only been accepted at this point
+    let response = HttpResponse::Accepted().finish();
+    match idempotency_key {
+        Some(idempotency_key) => {
+            Ok(save_response(db_transaction, &idempotency_key, user_id, response).await?)
+        }
+        None => {
+            db_transaction
+                .commit()
+                .await
+                .context("Failed to commit the SQL transaction to publish the newsletter")?;
+            Ok(response)
+        }
     }
-    Ok(HttpResponse::Ok().finish())
 }
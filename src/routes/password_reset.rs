@@ -0,0 +1,364 @@
+use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use chrono::Utc;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    authentication::change_password,
+    email_client::EmailSender,
+    models::{NewPassword, SubscriberEmail},
+    session::TypedSession,
+    startup::{ApplicationBaseUrl, PasswordResetTokenTtl},
+    suppressions::is_suppressed,
+    templates::{PasswordResetConfirmTemplate, PasswordResetEmailTemplate, PasswordResetTemplate},
+    utils::{e500, see_other},
+};
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetQueryParameters {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetConfirmFormData {
+    token: String,
+    new_password: SecretString,
+    new_password_check: SecretString,
+}
+
+fn create_password_reset_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+// Tokens are stored hashed. They are random and long enough that a fast hash is sufficient
+fn hash_password_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[get("/password-reset")]
+pub async fn password_reset_form(session: TypedSession) -> Result<HttpResponse, actix_web::Error> {
+    let message = session.take_flash_message();
+    let html_body = PasswordResetTemplate {
+        message: message.as_deref(),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(
+        form_data,
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        token_ttl,
+        session
+    )
+)]
+#[post("/password-reset")]
+pub async fn request_password_reset(
+    form_data: web::Form<PasswordResetFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    token_ttl: web::Data<PasswordResetTokenTtl>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    // The same answer is given whether the address belongs to an account or not, so that the
+    // form cannot be used to find out who the operators are
+    let response_message =
+        "If an account with that email exists, a password reset link has been sent to it.";
+
+    let Ok(email) = SubscriberEmail::parse(form_data.0.email) else {
+        session
+            .insert_flash_message(response_message)
+            .map_err(e500)?;
+        return Ok(see_other("/password-reset"));
+    };
+
+    let user_id = if is_suppressed(db_connection_pool.get_ref(), email.as_ref())
+        .await
+        .map_err(e500)?
+    {
+        None
+    } else {
+        get_user_id_by_email(&db_connection_pool, &email)
+            .await
+            .map_err(e500)?
+    };
+    if let Some(user_id) = user_id {
+        let password_reset_token = create_password_reset_token();
+        store_password_reset_token(
+            &db_connection_pool,
+            user_id,
+            &password_reset_token,
+            token_ttl.0,
+        )
+        .await
+        .map_err(e500)?;
+        // A failure is only logged, an error page would reveal that the account exists
+        if let Err(e) = send_password_reset_email(
+            email_client.as_ref(),
+            &email,
+            &application_base_url.0,
+            &password_reset_token,
+            token_ttl.0,
+        )
+        .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to send password reset email"
+            );
+        }
+    }
+
+    session
+        .insert_flash_message(response_message)
+        .map_err(e500)?;
+    Ok(see_other("/password-reset"))
+}
+
+#[tracing::instrument(
+    name = "Show password reset form",
+    skip(db_connection_pool, queryparams, session)
+)]
+#[get("/password-reset/confirm")]
+pub async fn password_reset_confirm_form(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<PasswordResetQueryParameters>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let token_hash = hash_password_reset_token(&queryparams.token);
+    let is_valid = sqlx::query!(
+        r#"
+        SELECT user_id FROM password_reset_tokens
+        WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > now()
+        "#,
+        token_hash
+    )
+    .fetch_optional(db_connection_pool.get_ref())
+    .await
+    .context("Failed to look up password reset token")
+    .map_err(e500)?
+    .is_some();
+
+    let message = session.take_flash_message();
+    let html_body = PasswordResetConfirmTemplate {
+        token: is_valid.then_some(queryparams.token.as_str()),
+        message: message.as_deref(),
+    }
+    .render()
+    .map_err(e500)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Reset password",
+    skip(form_data, db_connection_pool, session),
+    fields(user_id = tracing::field::Empty)
+)]
+#[post("/password-reset/confirm")]
+pub async fn confirm_password_reset(
+    form_data: web::Form<PasswordResetConfirmFormData>,
+    db_connection_pool: web::Data<PgPool>,
+    session: TypedSession,
+) -> Result<HttpResponse, actix_web::Error> {
+    let form_data = form_data.into_inner();
+    let retry_location = format!(
+        "/password-reset/confirm?{}",
+        serde_urlencoded::to_string([("token", &form_data.token)]).map_err(e500)?
+    );
+
+    if form_data.new_password.expose_secret() != form_data.new_password_check.expose_secret() {
+        session
+            .insert_flash_message(
+                "You entered two different new passwords - the field values must match.",
+            )
+            .map_err(e500)?;
+        return Ok(see_other(&retry_location));
+    }
+    let new_password = match NewPassword::parse(form_data.new_password) {
+        Ok(new_password) => new_password,
+        Err(e) => {
+            session.insert_flash_message(&e).map_err(e500)?;
+            return Ok(see_other(&retry_location));
+        }
+    };
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")
+        .map_err(e500)?;
+
+    let Some(user_id) = consume_password_reset_token(&mut db_transaction, &form_data.token)
+        .await
+        .map_err(e500)?
+    else {
+        session
+            .insert_flash_message("This password reset link is invalid or has expired.")
+            .map_err(e500)?;
+        return Ok(see_other("/password-reset"));
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    change_password(user_id, new_password, &mut *db_transaction)
+        .await
+        .map_err(e500)?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to reset the password")
+        .map_err(e500)?;
+
+    session
+        .insert_flash_message("Your password has been reset, you can now log in.")
+        .map_err(e500)?;
+    Ok(see_other("/login"))
+}
+
+#[tracing::instrument(name = "Get user id by email", skip(db_connection_pool, email))]
+async fn get_user_id_by_email(
+    db_connection_pool: &PgPool,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT user_id FROM users WHERE email = $1"#,
+        email.as_ref()
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .context("Failed to perform a query to retrieve a user by email")?;
+
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(
+    name = "Store password reset token in the database",
+    skip(db_connection_pool, password_reset_token)
+)]
+async fn store_password_reset_token(
+    db_connection_pool: &PgPool,
+    user_id: Uuid,
+    password_reset_token: &str,
+    token_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    // Only the most recently requested link is usable
+    sqlx::query!(
+        r#"DELETE FROM password_reset_tokens WHERE user_id = $1 AND consumed_at IS NULL"#,
+        user_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to invalidate previous password reset tokens")?;
+
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_password_reset_token(password_reset_token),
+        user_id,
+        now,
+        now + token_ttl
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to store password reset token")?;
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to store the password reset token")?;
+
+    Ok(())
+}
+
+// Marks the token as used and returns its owner, or None if the token is unknown, expired or was
+// already used
+#[tracing::instrument(
+    name = "Consume password reset token",
+    skip(db_transaction, password_reset_token)
+)]
+async fn consume_password_reset_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    password_reset_token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE password_reset_tokens SET consumed_at = now()
+        WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > now()
+        RETURNING user_id
+        "#,
+        hash_password_reset_token(password_reset_token)
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to consume password reset token")?;
+
+    Ok(row.map(|r| r.user_id))
+}
+
+#[tracing::instrument(
+    name = "Send a password reset email",
+    skip(email_client, application_base_url, password_reset_token, token_ttl)
+)]
+async fn send_password_reset_email(
+    email_client: &dyn EmailSender,
+    recipient: &SubscriberEmail,
+    application_base_url: &str,
+    password_reset_token: &str,
+    token_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let password_reset_link = format!(
+        "{}/password-reset/confirm?token={}",
+        application_base_url, password_reset_token
+    );
+
+    let plain_text_body = &format!(
+        "Visit {} to choose a new password.\nThe link expires in {} minutes. \
+        If you did not ask for a password reset, you can ignore this email.",
+        password_reset_link,
+        token_ttl.num_minutes()
+    );
+    let html_body = PasswordResetEmailTemplate {
+        password_reset_link: &password_reset_link,
+        ttl_minutes: token_ttl.num_minutes(),
+    }
+    .render()
+    .expect("Failed to render html for password reset email");
+
+    email_client
+        .send_email(recipient, "Password reset", &html_body, plain_text_body)
+        .await
+}
@@ -8,17 +8,22 @@ use sqlx::{Executor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::{
-    email_client::EmailClient,
+    email_client::EmailSender,
     errors::{StoreTokenError, SubscribeError},
-    models::NewSubscriber,
-    startup::ApplicationBaseUrl,
-    templates::ConfirmationEmailTemplate,
+    lists::{get_list_ids, get_membership_status, set_membership_status},
+    models::{ListSlug, NewSubscriber, UnsubscribeToken},
+    routes::unsubscribe_link,
+    startup::{ApplicationBaseUrl, ConfirmationEmailsPerDay, SubscriptionTokenTtl},
+    suppressions::is_suppressed,
+    templates::{AlreadySubscribedEmailTemplate, ConfirmationEmailTemplate},
 };
 
 #[derive(Deserialize)]
 pub struct SubscriberData {
     pub email: String,
     pub name: String,
+    // Slug of the list to join, the default list when missing
+    pub list: Option<String>,
 }
 
 fn create_subscription_token() -> String {
@@ -29,22 +34,60 @@ fn create_subscription_token() -> String {
         .collect()
 }
 
+// Outcome of a subscription request for an address, depending on the state of its subscription
+enum SubscriptionOutcome {
+    ConfirmationRequired {
+        subscriber_id: Uuid,
+        subscription_token: String,
+    },
+    AlreadySubscribed {
+        subscriber_id: Uuid,
+        unsubscribe_token: String,
+    },
+}
+
+struct ExistingSubscription {
+    id: Uuid,
+    status: String,
+    unsubscribe_token: String,
+}
+
+// The response is the same whatever the state of the subscription, so the endpoint cannot be
+// used to find out which addresses are on the list. Only the owner of the address can tell the
+// difference through the email they receive
 #[tracing::instrument(
     name = "Add a new subscriber",
-    skip(subscriber_data, db_connection_pool, email_client, application_base_url),
+    skip(
+        subscriber_data,
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        token_ttl,
+        confirmation_emails_per_day
+    ),
     fields(
         subscriber_name = %subscriber_data.name,
         subscriber_email = %subscriber_data.email,
         )
 )]
-#[post("/subscriptions")]
+#[post(
+    "/subscriptions",
+    wrap = "actix_web::middleware::from_fn(crate::rate_limit::rate_limit_subscriptions)"
+)]
 pub async fn subscribe(
     subscriber_data: web::Form<SubscriberData>,
     db_connection_pool: web::Data<PgPool>,
-    email_client: web::Data<EmailClient>,
+    email_client: web::Data<dyn EmailSender>,
     application_base_url: web::Data<ApplicationBaseUrl>,
+    token_ttl: web::Data<SubscriptionTokenTtl>,
+    confirmation_emails_per_day: web::Data<ConfirmationEmailsPerDay>,
 ) -> Result<HttpResponse, SubscribeError> {
-    let new_subscriber = subscriber_data
+    let list_slug = match &subscriber_data.list {
+        Some(list_slug) => ListSlug::parse(list_slug.clone()),
+        None => Ok(ListSlug::default_list()),
+    }
+    .map_err(SubscribeError::ValidationError)?;
+    let new_subscriber: NewSubscriber = subscriber_data
         .0
         .try_into()
         .map_err(SubscribeError::ValidationError)?;
@@ -53,31 +96,288 @@ pub async fn subscribe(
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let subscriber_id =
-        insert_subscriber_into_database(&db_connection_pool, &mut db_transaction, &new_subscriber)
+
+    let list_id = match get_list_ids(&mut db_transaction, std::slice::from_ref(&list_slug)).await? {
+        Ok(list_ids) => list_ids[0],
+        Err(unknown_list) => {
+            return Err(SubscribeError::ValidationError(format!(
+                "There is no list named {}",
+                unknown_list
+            )))
+        }
+    };
+
+    // Suppressed addresses cannot be added back, and get the same answer as everybody else
+    if is_suppressed(&mut *db_transaction, new_subscriber.email.as_ref()).await? {
+        tracing::info!("Ignoring a subscription request for a suppressed address");
+        return Ok(HttpResponse::Created().finish());
+    }
+
+    let inserted_subscriber_id =
+        insert_subscriber_into_database(&mut db_transaction, &new_subscriber)
             .await
             .context("Failed to insert new subscriber in the database")?;
-
-    let subscription_token = create_subscription_token();
-    store_subscription_token_into_database(&mut db_transaction, subscriber_id, &subscription_token)
-        .await
-        .context("Failed to store the confirmation token for the new subscriber")?;
+    let outcome = match inserted_subscriber_id {
+        Some(subscriber_id) => {
+            set_membership_status(
+                &mut db_transaction,
+                subscriber_id,
+                list_id,
+                "pending_confirmation",
+            )
+            .await?;
+            let subscription_token =
+                create_and_store_subscription_token(&mut db_transaction, subscriber_id).await?;
+            SubscriptionOutcome::ConfirmationRequired {
+                subscriber_id,
+                subscription_token,
+            }
+        }
+        None => {
+            let subscription = get_existing_subscription(&mut db_transaction, &new_subscriber)
+                .await
+                .context("Failed to look up an existing subscription for the email")?
+                .context("The existing subscription for the email was deleted concurrently")?;
+            handle_existing_subscription(
+                &mut db_transaction,
+                subscription,
+                &new_subscriber,
+                list_id,
+                token_ttl.0,
+            )
+            .await?
+        }
+    };
 
     db_transaction
         .commit()
         .await
         .context("Failed to commit the SQL transaction to store the new subscriber")?;
 
-    send_confirmation_email(
-        &email_client,
-        new_subscriber,
-        &application_base_url.0,
-        &subscription_token,
+    match outcome {
+        SubscriptionOutcome::ConfirmationRequired {
+            subscriber_id,
+            subscription_token,
+        } => {
+            send_confirmation_email(
+                &db_connection_pool,
+                email_client.as_ref(),
+                subscriber_id,
+                new_subscriber,
+                &application_base_url.0,
+                &subscription_token,
+                confirmation_emails_per_day.0,
+            )
+            .await
+            .context("Failed to send confirmation email")?;
+        }
+        SubscriptionOutcome::AlreadySubscribed {
+            subscriber_id,
+            unsubscribe_token,
+        } => {
+            send_already_subscribed_email(
+                &db_connection_pool,
+                email_client.as_ref(),
+                subscriber_id,
+                new_subscriber,
+                &application_base_url.0,
+                &unsubscribe_token,
+                confirmation_emails_per_day.0,
+            )
+            .await
+            .context("Failed to send already subscribed notice")?;
+        }
+    }
+
+    Ok(HttpResponse::Created().finish())
+}
+
+// Decides what a new request means for an address that already has a subscription, the row is
+// locked by `get_existing_subscription`
+async fn handle_existing_subscription(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscription: ExistingSubscription,
+    new_subscriber: &NewSubscriber,
+    list_id: Uuid,
+    token_ttl: chrono::Duration,
+) -> Result<SubscriptionOutcome, SubscribeError> {
+    let outcome = match subscription.status.as_str() {
+        "pending_confirmation" => {
+            set_membership_status(
+                db_transaction,
+                subscription.id,
+                list_id,
+                "pending_confirmation",
+            )
+            .await?;
+            let valid_token =
+                get_valid_subscription_token(db_transaction, subscription.id, token_ttl)
+                    .await
+                    .context("Failed to look up the subscriber's confirmation tokens")?;
+            let subscription_token = match valid_token {
+                Some(subscription_token) => subscription_token,
+                None => {
+                    create_and_store_subscription_token(db_transaction, subscription.id).await?
+                }
+            };
+            SubscriptionOutcome::ConfirmationRequired {
+                subscriber_id: subscription.id,
+                subscription_token,
+            }
+        }
+        // Addresses that bounced or complained only get here once their suppression was lifted
+        "unsubscribed" | "bounced" | "complained" => {
+            restart_subscription(db_transaction, subscription.id, new_subscriber)
+                .await
+                .context("Failed to restart the subscription of a former subscriber")?;
+            set_membership_status(
+                db_transaction,
+                subscription.id,
+                list_id,
+                "pending_confirmation",
+            )
+            .await?;
+            let subscription_token =
+                create_and_store_subscription_token(db_transaction, subscription.id).await?;
+            SubscriptionOutcome::ConfirmationRequired {
+                subscriber_id: subscription.id,
+                subscription_token,
+            }
+        }
+        "confirmed" => {
+            let membership_status =
+                get_membership_status(db_transaction, subscription.id, list_id).await?;
+            if membership_status.as_deref() == Some("confirmed") {
+                SubscriptionOutcome::AlreadySubscribed {
+                    subscriber_id: subscription.id,
+                    unsubscribe_token: subscription.unsubscribe_token,
+                }
+            } else {
+                // Joining another list is confirmed by email as well, otherwise anybody
+                // could sign a known subscriber up to every list
+                set_membership_status(
+                    db_transaction,
+                    subscription.id,
+                    list_id,
+                    "pending_confirmation",
+                )
+                .await?;
+                let subscription_token =
+                    create_and_store_subscription_token(db_transaction, subscription.id).await?;
+                SubscriptionOutcome::ConfirmationRequired {
+                    subscriber_id: subscription.id,
+                    subscription_token,
+                }
+            }
+        }
+        other => {
+            return Err(anyhow::anyhow!("Unexpected subscription status {}", other).into());
+        }
+    };
+
+    Ok(outcome)
+}
+
+pub async fn create_and_store_subscription_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<String, anyhow::Error> {
+    let subscription_token = create_subscription_token();
+    store_subscription_token_into_database(db_transaction, subscriber_id, &subscription_token)
+        .await
+        .context("Failed to store the confirmation token for the subscriber")?;
+
+    Ok(subscription_token)
+}
+
+// The row is locked so that concurrent requests for the same address are handled one at a time
+#[tracing::instrument(
+    name = "Get existing subscription",
+    skip(db_transaction, subscriber_data)
+)]
+async fn get_existing_subscription(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_data: &NewSubscriber,
+) -> Result<Option<ExistingSubscription>, sqlx::Error> {
+    let subscription = sqlx::query_as!(
+        ExistingSubscription,
+        r#"
+        SELECT id, status, unsubscribe_token
+        FROM subscriptions
+        WHERE email = $1
+        FOR UPDATE
+        "#,
+        subscriber_data.email.as_ref()
     )
+    .fetch_optional(&mut **db_transaction)
     .await
-    .context("Failed to send confirmation email")?;
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
 
-    Ok(HttpResponse::Created().finish())
+    Ok(subscription)
+}
+
+// Resending a confirmation email reuses the latest token as long as it can still be used
+#[tracing::instrument(
+    name = "Get valid subscription token",
+    skip(db_transaction, subscriber_id, token_ttl)
+)]
+async fn get_valid_subscription_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    token_ttl: chrono::Duration,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"
+        SELECT subscription_token
+        FROM subscription_tokens
+        WHERE subscriber_id = $1 AND consumed_at IS NULL AND created_at > $2
+        ORDER BY created_at DESC
+        LIMIT 1
+        "#,
+        subscriber_id,
+        Utc::now() - token_ttl
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result.map(|r| r.subscription_token))
+}
+
+// A former subscriber has to go through the double opt-in again before receiving any issue
+#[tracing::instrument(
+    name = "Restart subscription",
+    skip(db_transaction, subscriber_id, subscriber_data)
+)]
+async fn restart_subscription(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    subscriber_data: &NewSubscriber,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscriptions
+        SET status = 'pending_confirmation', name = $2, subscribed_at = $3
+        WHERE id = $1
+        "#,
+        subscriber_id,
+        subscriber_data.name.as_ref(),
+        Utc::now()
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
 }
 
 #[tracing::instrument(
@@ -104,21 +404,36 @@ async fn store_subscription_token_into_database(
     Ok(())
 }
 
+// Returns whether the email was sent, nothing is sent to suppressed addresses or to addresses
+// that already received their daily share of confirmation emails
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
     skip(
+        db_connection_pool,
         email_client,
         subscriber_data,
         application_base_url,
         subscription_token
     )
 )]
-async fn send_confirmation_email(
-    email_client: &EmailClient,
+pub async fn send_confirmation_email(
+    db_connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    subscriber_id: Uuid,
     subscriber_data: NewSubscriber,
     application_base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+    emails_per_day: i64,
+) -> Result<bool, anyhow::Error> {
+    if is_suppressed(db_connection_pool, subscriber_data.email.as_ref()).await? {
+        tracing::info!("Not sending a confirmation email to a suppressed address");
+        return Ok(false);
+    }
+    if !reserve_requested_email(db_connection_pool, subscriber_id, emails_per_day).await? {
+        tracing::warn!("Not sending a confirmation email, the daily limit was reached");
+        return Ok(false);
+    }
+
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         application_base_url, subscription_token
@@ -142,45 +457,149 @@ async fn send_confirmation_email(
             &html_body,
             plain_text_body,
         )
+        .await?;
+
+    Ok(true)
+}
+
+// Emails anybody can trigger by submitting an address, confirmations, already subscribed notices,
+// preference links and data export links, share a daily limit per subscriber. The email is
+// counted before it is sent, so that concurrent requests cannot go over it. The subscription row
+// is locked while counting for the same reason
+#[tracing::instrument(skip(db_connection_pool))]
+pub async fn reserve_requested_email(
+    db_connection_pool: &PgPool,
+    subscriber_id: Uuid,
+    emails_per_day: i64,
+) -> Result<bool, anyhow::Error> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE id = $1 FOR UPDATE"#,
+        subscriber_id
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to lock the subscription")?;
+    sqlx::query!(
+        r#"DELETE FROM confirmation_emails_sent WHERE sent_at < now() - interval '1 day'"#
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to delete expired confirmation email records")?;
+
+    let sent_today = sqlx::query!(
+        r#"
+        SELECT COUNT(*) AS "count!"
+        FROM confirmation_emails_sent
+        WHERE subscriber_id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_one(&mut *db_transaction)
+    .await
+    .context("Failed to count the confirmation emails sent today")?
+    .count;
+    if sent_today >= emails_per_day {
+        return Ok(false);
+    }
+
+    sqlx::query!(
+        r#"INSERT INTO confirmation_emails_sent (subscriber_id, sent_at) VALUES ($1, now())"#,
+        subscriber_id
+    )
+    .execute(&mut *db_transaction)
+    .await
+    .context("Failed to record a confirmation email")?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to record a confirmation email")?;
+
+    Ok(true)
+}
+
+#[tracing::instrument(
+    name = "Send an already subscribed notice",
+    skip(
+        db_connection_pool,
+        email_client,
+        subscriber_data,
+        application_base_url,
+        unsubscribe_token
+    )
+)]
+async fn send_already_subscribed_email(
+    db_connection_pool: &PgPool,
+    email_client: &dyn EmailSender,
+    subscriber_id: Uuid,
+    subscriber_data: NewSubscriber,
+    application_base_url: &str,
+    unsubscribe_token: &str,
+    emails_per_day: i64,
+) -> Result<(), anyhow::Error> {
+    if !reserve_requested_email(db_connection_pool, subscriber_id, emails_per_day).await? {
+        tracing::warn!("Not sending an already subscribed notice, the daily limit was reached");
+        return Ok(());
+    }
+
+    let unsubscribe_link = unsubscribe_link(application_base_url, unsubscribe_token);
+
+    let plain_text_body = &format!(
+        "You are already subscribed to our newsletter, there is nothing else to do.\n\
+        Visit {} if you no longer want to receive it.",
+        unsubscribe_link
+    );
+    let html_body = AlreadySubscribedEmailTemplate {
+        unsubscribe_link: &unsubscribe_link,
+    }
+    .render()
+    .expect("Failed to render html for already subscribed email");
+
+    email_client
+        .send_email(
+            &subscriber_data.email,
+            "You are already subscribed",
+            &html_body,
+            plain_text_body,
+        )
         .await
 }
 
+// Returns None when the email already has a subscription. Unlike a lookup followed by an insert,
+// this cannot fail on the UNIQUE constraint when two requests for a new email race: the second
+// one waits for the first to commit, then finds its row
 #[tracing::instrument(
     name = "Save new subscriber details in the database",
     skip(db_transaction, subscriber_data)
 )]
 async fn insert_subscriber_into_database(
-    db_connection_pool: &PgPool,
     db_transaction: &mut Transaction<'_, Postgres>,
     subscriber_data: &NewSubscriber,
-) -> Result<Uuid, sqlx::Error> {
-    let subscriber_id = Uuid::new_v4();
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let unsubscribe_token = UnsubscribeToken::generate();
 
-    let subscriber = sqlx::query!(
-        r#"SELECT id FROM subscriptions WHERE email = $1 AND status = 'pending_confirmation'"#,
-        subscriber_data.email.as_ref()
-    )
-    .fetch_optional(db_connection_pool)
-    .await?;
-    if let Some(subscriber) = subscriber {
-        return Ok(subscriber.id);
-    }
-
-    let db_query = sqlx::query!(
+    let result = sqlx::query!(
         r#"
-        INSERT INTO subscriptions (id, email, name, subscribed_at, status)
-        VALUES ($1, $2, $3, $4, 'pending_confirmation')
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, 'pending_confirmation', $5)
+        ON CONFLICT (email) DO NOTHING
+        RETURNING id
         "#,
-        subscriber_id,
+        Uuid::new_v4(),
         subscriber_data.email.as_ref(),
         subscriber_data.name.as_ref(),
-        Utc::now()
-    );
-
-    db_transaction.execute(db_query).await.map_err(|e| {
+        Utc::now(),
+        unsubscribe_token.as_ref()
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
 
-    Ok(subscriber_id)
+    Ok(result.map(|r| r.id))
 }
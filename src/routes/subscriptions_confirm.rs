@@ -1,51 +1,85 @@
 use actix_web::{get, web, HttpResponse};
 use anyhow::Context;
-use sqlx::PgPool;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use crate::errors::ConfirmationError;
+use crate::lists::confirm_pending_memberships;
 use crate::models::SubscriptionToken;
+use crate::startup::SubscriptionTokenTtl;
 
 #[derive(serde::Deserialize)]
 pub struct QueryParameters {
     pub subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm subscriber", skip(db_connection_pool, queryparams))]
+struct StoredSubscriptionToken {
+    subscriber_id: Uuid,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+#[tracing::instrument(
+    name = "Confirm subscriber",
+    skip(db_connection_pool, queryparams, token_ttl)
+)]
 #[get("/subscriptions/confirm")]
 pub async fn confirm_subscriber(
     db_connection_pool: web::Data<PgPool>,
     queryparams: web::Query<QueryParameters>,
+    token_ttl: web::Data<SubscriptionTokenTtl>,
 ) -> Result<HttpResponse, ConfirmationError> {
     let subscription_token = SubscriptionToken::parse(queryparams.subscription_token.clone())
         .map_err(ConfirmationError::TokenError)
         .context("The token was invalid")?;
 
-    let subscriber_id = get_subscriber_id_from_token(&db_connection_pool, &subscription_token)
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+
+    let stored_token = get_stored_token(&mut db_transaction, &subscription_token)
         .await
         .context("No email was associated to this token")?
         .ok_or(ConfirmationError::UnknownToken)?;
+    if stored_token.consumed_at.is_some() || stored_token.created_at + token_ttl.0 < Utc::now() {
+        return Err(ConfirmationError::ExpiredToken);
+    }
 
-    mark_subscriber_status_as_confirmed(&db_connection_pool, subscriber_id)
+    mark_subscriber_status_as_confirmed(&mut db_transaction, stored_token.subscriber_id)
         .await
         .context("Failed to mark subscriber as confirmed")?;
+    confirm_pending_memberships(&mut db_transaction, stored_token.subscriber_id).await?;
+    consume_subscriber_tokens(
+        &mut db_transaction,
+        stored_token.subscriber_id,
+        &subscription_token,
+    )
+    .await
+    .context("Failed to consume the subscriber's confirmation tokens")?;
+
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to confirm the subscriber")?;
 
     Ok(HttpResponse::Ok().finish())
 }
 
 #[tracing::instrument(
     name = "Mark subscriber as confirmed",
-    skip(db_connection_pool, subscriber_id)
+    skip(db_transaction, subscriber_id)
 )]
 async fn mark_subscriber_status_as_confirmed(
-    db_connection_pool: &PgPool,
+    db_transaction: &mut Transaction<'_, Postgres>,
     subscriber_id: Uuid,
 ) -> Result<(), sqlx::Error> {
     sqlx::query!(
         r#"UPDATE subscriptions SET status = 'confirmed' WHERE id = $1"#,
         subscriber_id
     )
-    .execute(db_connection_pool)
+    .execute(&mut **db_transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
@@ -55,24 +89,94 @@ async fn mark_subscriber_status_as_confirmed(
     Ok(())
 }
 
+// The used token is kept, marked as consumed, so that hitting the link again is reported as
+// expired until `delete_expired_subscription_tokens` gets rid of it. Any other outstanding token
+// of the subscriber is useless from now on and is removed
 #[tracing::instrument(
-    name = "Get subscriber id from associated token",
-    skip(db_connection_pool, subscription_token)
+    name = "Consume subscriber confirmation tokens",
+    skip(db_transaction, subscriber_id, subscription_token)
 )]
-async fn get_subscriber_id_from_token(
-    db_connection_pool: &PgPool,
+async fn consume_subscriber_tokens(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
     subscription_token: &SubscriptionToken,
-) -> Result<Option<Uuid>, sqlx::Error> {
-    let result = sqlx::query!(
-        r#"SELECT subscriber_id FROM subscription_tokens WHERE subscription_token = $1"#,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscription_tokens
+        SET consumed_at = now()
+        WHERE subscription_token = $1
+        "#,
         subscription_token.as_ref()
     )
-    .fetch_optional(db_connection_pool)
+    .execute(&mut **db_transaction)
     .await
     .map_err(|e| {
         tracing::error!("Failed to execute query: {:?}", e);
         e
     })?;
 
-    Ok(result.map(|r| r.subscriber_id))
+    sqlx::query!(
+        r#"
+        DELETE FROM subscription_tokens
+        WHERE subscriber_id = $1 AND subscription_token <> $2
+        "#,
+        subscriber_id,
+        subscription_token.as_ref()
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
+}
+
+// The row is locked so that concurrent requests with the same link cannot both confirm
+#[tracing::instrument(
+    name = "Get stored subscription token",
+    skip(db_transaction, subscription_token)
+)]
+async fn get_stored_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscription_token: &SubscriptionToken,
+) -> Result<Option<StoredSubscriptionToken>, sqlx::Error> {
+    let result = sqlx::query_as!(
+        StoredSubscriptionToken,
+        r#"
+        SELECT subscriber_id, created_at, consumed_at
+        FROM subscription_tokens
+        WHERE subscription_token = $1
+        FOR UPDATE
+        "#,
+        subscription_token.as_ref()
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result)
+}
+
+// Tokens past their TTL, whether they were used or not, cannot confirm anything anymore. Their
+// links are answered as unknown instead of expired once they are gone
+#[tracing::instrument(skip(db_connection_pool), err)]
+pub async fn delete_expired_subscription_tokens(
+    db_connection_pool: &PgPool,
+    token_ttl: chrono::Duration,
+) -> Result<u64, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE created_at < $1"#,
+        Utc::now() - token_ttl
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to delete the expired subscription tokens")?;
+
+    Ok(result.rows_affected())
 }
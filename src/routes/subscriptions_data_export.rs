@@ -0,0 +1,451 @@
+use actix_web::{
+    get,
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    post, web, HttpResponse,
+};
+use anyhow::Context;
+use askama_actix::Template;
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditActor},
+    email_client::EmailSender,
+    errors::DataExportError,
+    models::SubscriberEmail,
+    routes::reserve_requested_email,
+    startup::{ApplicationBaseUrl, ConfirmationEmailsPerDay, DataExportTokenTtl},
+    suppressions::is_suppressed,
+    templates::DataExportEmailTemplate,
+};
+
+#[derive(serde::Deserialize)]
+pub struct DataExportRequestData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataExportQueryParameters {
+    token: String,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionData {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+    digest_frequency: String,
+    unsubscribe_token: String,
+}
+
+#[derive(serde::Serialize)]
+struct SubscriptionTokenData {
+    subscription_token: String,
+    created_at: DateTime<Utc>,
+    consumed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(serde::Serialize)]
+struct ListMembershipData {
+    list: String,
+    status: String,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct DeliveryData {
+    newsletter_issue_id: Uuid,
+    title: String,
+    outcome: String,
+    attempted_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct OpenData {
+    newsletter_issue_id: Uuid,
+    title: String,
+    first_opened_at: DateTime<Utc>,
+    last_opened_at: DateTime<Utc>,
+    open_count: i32,
+    user_agent: Option<String>,
+}
+
+#[derive(serde::Serialize)]
+struct ClickData {
+    newsletter_issue_id: Uuid,
+    title: String,
+    url: String,
+    clicked_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize)]
+struct DataExportBundle {
+    exported_at: DateTime<Utc>,
+    subscription: SubscriptionData,
+    lists: Vec<ListMembershipData>,
+    subscription_tokens: Vec<SubscriptionTokenData>,
+    deliveries: Vec<DeliveryData>,
+    opens: Vec<OpenData>,
+    clicks: Vec<ClickData>,
+}
+
+fn create_data_export_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+// Tokens are stored hashed. They are random and long enough that a fast hash is sufficient
+fn hash_data_export_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// The same answer is given whether the address is on the list or not. The data is only ever sent
+// to the address itself, through a single-use link
+#[tracing::instrument(
+    name = "Request a data export",
+    skip(
+        form_data,
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        token_ttl,
+        emails_per_day
+    )
+)]
+#[post(
+    "/subscriptions/data-export",
+    wrap = "actix_web::middleware::from_fn(crate::rate_limit::rate_limit_subscriptions)"
+)]
+pub async fn request_data_export(
+    form_data: web::Form<DataExportRequestData>,
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    token_ttl: web::Data<DataExportTokenTtl>,
+    emails_per_day: web::Data<ConfirmationEmailsPerDay>,
+) -> Result<HttpResponse, DataExportError> {
+    let Ok(email) = SubscriberEmail::parse(form_data.0.email) else {
+        return Ok(HttpResponse::Accepted().finish());
+    };
+    if is_suppressed(db_connection_pool.get_ref(), email.as_ref()).await? {
+        return Ok(HttpResponse::Accepted().finish());
+    }
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let Some(subscriber_id) = get_subscriber_id_by_email(&mut db_transaction, &email).await? else {
+        return Ok(HttpResponse::Accepted().finish());
+    };
+    // Checked before storing the token, so that the last link sent keeps working
+    if !reserve_requested_email(
+        db_connection_pool.get_ref(),
+        subscriber_id,
+        emails_per_day.0,
+    )
+    .await?
+    {
+        tracing::warn!("Not sending a data export email, the daily limit was reached");
+        return Ok(HttpResponse::Accepted().finish());
+    }
+
+    let data_export_token = create_data_export_token();
+    store_data_export_token(
+        &mut db_transaction,
+        subscriber_id,
+        &data_export_token,
+        token_ttl.0,
+    )
+    .await?;
+    record_audit_event(
+        &mut db_transaction,
+        subscriber_id,
+        AuditAction::DataExportRequested,
+        AuditActor::Subscriber,
+    )
+    .await?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to store the data export token")?;
+
+    // A failure is only logged, an error page would reveal that the address is on the list
+    if let Err(e) = send_data_export_email(
+        email_client.as_ref(),
+        &email,
+        &application_base_url.0,
+        &data_export_token,
+        token_ttl.0,
+    )
+    .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send data export email"
+        );
+    }
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[tracing::instrument(name = "Export subscriber data", skip(db_connection_pool, queryparams))]
+#[get("/subscriptions/data-export")]
+pub async fn export_data(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<DataExportQueryParameters>,
+) -> Result<HttpResponse, DataExportError> {
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber_id = consume_data_export_token(&mut db_transaction, &queryparams.token)
+        .await?
+        .ok_or(DataExportError::InvalidToken)?;
+
+    let bundle = get_data_export_bundle(&mut db_transaction, subscriber_id).await?;
+    record_audit_event(
+        &mut db_transaction,
+        subscriber_id,
+        AuditAction::DataExported,
+        AuditActor::Subscriber,
+    )
+    .await?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to export the subscriber's data")?;
+
+    Ok(HttpResponse::Ok()
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename("my-data.json".into())],
+        })
+        .json(bundle))
+}
+
+// Erased subscribers are not reachable by email anymore, their address was overwritten
+#[tracing::instrument(name = "Get subscriber id by email", skip(db_transaction, email))]
+async fn get_subscriber_id_by_email(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    email: &SubscriberEmail,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1"#,
+        email.as_ref()
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to perform a query to retrieve a subscriber by email")?;
+
+    Ok(row.map(|r| r.id))
+}
+
+#[tracing::instrument(
+    name = "Store data export token in the database",
+    skip(db_transaction, data_export_token)
+)]
+async fn store_data_export_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+    data_export_token: &str,
+    token_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    // Only the most recently requested link is usable
+    sqlx::query!(
+        r#"DELETE FROM data_export_tokens WHERE subscriber_id = $1 AND consumed_at IS NULL"#,
+        subscriber_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to invalidate previous data export tokens")?;
+
+    let now = Utc::now();
+    sqlx::query!(
+        r#"
+        INSERT INTO data_export_tokens (token_hash, subscriber_id, created_at, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        hash_data_export_token(data_export_token),
+        subscriber_id,
+        now,
+        now + token_ttl
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .context("Failed to store data export token")?;
+
+    Ok(())
+}
+
+// Marks the token as used and returns its owner, or None if the token is unknown, expired or was
+// already used
+#[tracing::instrument(
+    name = "Consume data export token",
+    skip(db_transaction, data_export_token)
+)]
+async fn consume_data_export_token(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    data_export_token: &str,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        UPDATE data_export_tokens SET consumed_at = now()
+        WHERE token_hash = $1 AND consumed_at IS NULL AND expires_at > now()
+        RETURNING subscriber_id
+        "#,
+        hash_data_export_token(data_export_token)
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to consume data export token")?;
+
+    Ok(row.map(|r| r.subscriber_id))
+}
+
+#[tracing::instrument(name = "Build data export bundle", skip(db_transaction))]
+async fn get_data_export_bundle(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<DataExportBundle, anyhow::Error> {
+    let subscription = sqlx::query_as!(
+        SubscriptionData,
+        r#"
+        SELECT id, email, name, status, subscribed_at, digest_frequency, unsubscribe_token
+        FROM subscriptions
+        WHERE id = $1
+        "#,
+        subscriber_id
+    )
+    .fetch_one(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the subscription")?;
+
+    let lists = sqlx::query_as!(
+        ListMembershipData,
+        r#"
+        SELECT l.slug AS list, m.status, m.created_at
+        FROM list_memberships m
+        JOIN lists l ON l.id = m.list_id
+        WHERE m.subscriber_id = $1
+        ORDER BY m.created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the list memberships")?;
+
+    let subscription_tokens = sqlx::query_as!(
+        SubscriptionTokenData,
+        r#"
+        SELECT subscription_token, created_at, consumed_at
+        FROM subscription_tokens
+        WHERE subscriber_id = $1
+        ORDER BY created_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the subscription tokens")?;
+
+    let deliveries = sqlx::query_as!(
+        DeliveryData,
+        r#"
+        SELECT d.newsletter_issue_id, i.title, d.outcome, d.attempted_at
+        FROM newsletter_deliveries d
+        JOIN newsletter_issues i ON i.newsletter_issue_id = d.newsletter_issue_id
+        WHERE d.subscriber_id = $1
+        ORDER BY d.attempted_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the delivery history")?;
+
+    let opens = sqlx::query_as!(
+        OpenData,
+        r#"
+        SELECT o.newsletter_issue_id, i.title, o.first_opened_at, o.last_opened_at,
+            o.open_count, o.user_agent
+        FROM newsletter_opens o
+        JOIN newsletter_issues i ON i.newsletter_issue_id = o.newsletter_issue_id
+        WHERE o.subscriber_id = $1
+        ORDER BY o.first_opened_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the issue opens")?;
+
+    let clicks = sqlx::query_as!(
+        ClickData,
+        r#"
+        SELECT c.newsletter_issue_id, i.title, c.url, c.clicked_at
+        FROM newsletter_clicks c
+        JOIN newsletter_issues i ON i.newsletter_issue_id = c.newsletter_issue_id
+        WHERE c.subscriber_id = $1
+        ORDER BY c.clicked_at
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the link clicks")?;
+
+    Ok(DataExportBundle {
+        exported_at: Utc::now(),
+        subscription,
+        lists,
+        subscription_tokens,
+        deliveries,
+        opens,
+        clicks,
+    })
+}
+
+#[tracing::instrument(
+    name = "Send a data export email",
+    skip(email_client, application_base_url, data_export_token, token_ttl)
+)]
+async fn send_data_export_email(
+    email_client: &dyn EmailSender,
+    recipient: &SubscriberEmail,
+    application_base_url: &str,
+    data_export_token: &str,
+    token_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let data_export_link = format!(
+        "{}/subscriptions/data-export?token={}",
+        application_base_url, data_export_token
+    );
+
+    let plain_text_body = &format!(
+        "Visit {} to download the data we hold about you.\nThe link can be used once and \
+        expires in {} minutes. If you did not ask for your data, you can ignore this email.",
+        data_export_link,
+        token_ttl.num_minutes()
+    );
+    let html_body = DataExportEmailTemplate {
+        data_export_link: &data_export_link,
+        ttl_minutes: token_ttl.num_minutes(),
+    }
+    .render()
+    .expect("Failed to render html for data export email");
+
+    email_client
+        .send_email(recipient, "Your data", &html_body, plain_text_body)
+        .await
+}
@@ -0,0 +1,380 @@
+use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use chrono::{DateTime, Utc};
+use secrecy::SecretString;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    email_client::EmailSender,
+    errors::PreferencesError,
+    lists::set_membership_status,
+    models::{DigestFrequency, SubscriberEmail, SubscriberName},
+    routes::reserve_requested_email,
+    session::TypedSession,
+    signing,
+    startup::{ApplicationBaseUrl, ConfirmationEmailsPerDay, HmacSecret, PreferencesLinkTtl},
+    suppressions::is_suppressed,
+    templates::{
+        DigestFrequencyOption, ListPreference, PreferencesEmailTemplate, PreferencesTemplate,
+    },
+    utils::see_other,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesLinkRequestData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesQueryParameters {
+    token: String,
+}
+
+struct SubscriberPreferences {
+    name: String,
+    digest_frequency: String,
+    unsubscribe_token: String,
+}
+
+struct ListMembership {
+    id: Uuid,
+    slug: String,
+    name: String,
+    status: Option<String>,
+}
+
+// The link is not stored anywhere: it carries the subscriber id and its expiry, signed with the
+// HMAC secret. It can be used any number of times until it expires
+fn create_preferences_token(
+    hmac_secret: &SecretString,
+    subscriber_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> String {
+    let payload = format!("{}.{}", subscriber_id, expires_at.timestamp());
+    let signature = signing::sign(hmac_secret, &format!("preferences.{}", payload));
+    format!("{}.{}", payload, signature)
+}
+
+// Returns the subscriber the token was issued for, or None if it was tampered with or expired
+fn verify_preferences_token(hmac_secret: &SecretString, token: &str) -> Option<Uuid> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    if !signing::verify(hmac_secret, &format!("preferences.{}", payload), signature) {
+        return None;
+    }
+    let (subscriber_id, expires_at) = payload.split_once('.')?;
+    let expires_at: i64 = expires_at.parse().ok()?;
+    if expires_at < Utc::now().timestamp() {
+        return None;
+    }
+
+    Uuid::parse_str(subscriber_id).ok()
+}
+
+fn preferences_link(application_base_url: &str, preferences_token: &str) -> String {
+    format!(
+        "{}/subscriptions/preferences?token={}",
+        application_base_url, preferences_token
+    )
+}
+
+// The same answer is given whether the address is on the list or not, the link is only ever sent
+// to the address itself
+#[tracing::instrument(
+    name = "Request a preferences link",
+    skip(
+        form_data,
+        db_connection_pool,
+        email_client,
+        application_base_url,
+        hmac_secret,
+        link_ttl,
+        emails_per_day
+    )
+)]
+#[post(
+    "/subscriptions/preferences/link",
+    wrap = "actix_web::middleware::from_fn(crate::rate_limit::rate_limit_subscriptions)"
+)]
+pub async fn request_preferences_link(
+    form_data: web::Form<PreferencesLinkRequestData>,
+    db_connection_pool: web::Data<PgPool>,
+    email_client: web::Data<dyn EmailSender>,
+    application_base_url: web::Data<ApplicationBaseUrl>,
+    hmac_secret: web::Data<HmacSecret>,
+    link_ttl: web::Data<PreferencesLinkTtl>,
+    emails_per_day: web::Data<ConfirmationEmailsPerDay>,
+) -> Result<HttpResponse, PreferencesError> {
+    let Ok(email) = SubscriberEmail::parse(form_data.0.email) else {
+        return Ok(HttpResponse::Accepted().finish());
+    };
+    if is_suppressed(db_connection_pool.get_ref(), email.as_ref()).await? {
+        return Ok(HttpResponse::Accepted().finish());
+    }
+
+    let subscriber = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE email = $1 AND status = 'confirmed'"#,
+        email.as_ref()
+    )
+    .fetch_optional(db_connection_pool.get_ref())
+    .await
+    .context("Failed to perform a query to retrieve a confirmed subscriber by email")?;
+    let Some(subscriber) = subscriber else {
+        return Ok(HttpResponse::Accepted().finish());
+    };
+    if !reserve_requested_email(
+        db_connection_pool.get_ref(),
+        subscriber.id,
+        emails_per_day.0,
+    )
+    .await?
+    {
+        tracing::warn!("Not sending a preferences email, the daily limit was reached");
+        return Ok(HttpResponse::Accepted().finish());
+    }
+
+    let preferences_token =
+        create_preferences_token(&hmac_secret.0, subscriber.id, Utc::now() + link_ttl.0);
+    // A failure is only logged, an error page would reveal that the address is on the list
+    if let Err(e) = send_preferences_email(
+        email_client.as_ref(),
+        &email,
+        &preferences_link(&application_base_url.0, &preferences_token),
+        link_ttl.0,
+    )
+    .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send preferences email"
+        );
+    }
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+#[tracing::instrument(
+    name = "Show preferences",
+    skip(db_connection_pool, queryparams, hmac_secret, session)
+)]
+#[get("/subscriptions/preferences")]
+pub async fn preferences_form(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<PreferencesQueryParameters>,
+    hmac_secret: web::Data<HmacSecret>,
+    session: TypedSession,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = verify_preferences_token(&hmac_secret.0, &queryparams.token)
+        .ok_or(PreferencesError::InvalidToken)?;
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let subscriber = get_subscriber_preferences(&mut db_transaction, subscriber_id)
+        .await?
+        .ok_or(PreferencesError::InvalidToken)?;
+    let lists = get_list_memberships(&mut db_transaction, subscriber_id)
+        .await?
+        .into_iter()
+        .map(|list| ListPreference {
+            subscribed: list.status.as_deref() == Some("confirmed"),
+            slug: list.slug,
+            name: list.name,
+        })
+        .collect::<Vec<_>>();
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to read the preferences")?;
+
+    let digest_frequencies = DigestFrequency::ALL.map(|f| DigestFrequencyOption {
+        value: f.as_str(),
+        selected: f.as_str() == subscriber.digest_frequency,
+    });
+    let message = session.take_flash_message();
+    let html_body = PreferencesTemplate {
+        token: &queryparams.token,
+        name: &subscriber.name,
+        digest_frequencies: &digest_frequencies,
+        lists: &lists,
+        unsubscribe_token: &subscriber.unsubscribe_token,
+        message: message.as_deref(),
+    }
+    .render()
+    .context("Failed to render the preferences page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+// The form is read as a list of pairs because every ticked list checkbox sends its own `list`
+// field. Subscribers reach this page through their own inbox, so lists they tick are joined
+// without another confirmation email
+#[tracing::instrument(
+    name = "Update preferences",
+    skip(db_connection_pool, queryparams, form_data, hmac_secret, session)
+)]
+#[post("/subscriptions/preferences")]
+pub async fn update_preferences(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<PreferencesQueryParameters>,
+    form_data: web::Form<Vec<(String, String)>>,
+    hmac_secret: web::Data<HmacSecret>,
+    session: TypedSession,
+) -> Result<HttpResponse, PreferencesError> {
+    let subscriber_id = verify_preferences_token(&hmac_secret.0, &queryparams.token)
+        .ok_or(PreferencesError::InvalidToken)?;
+    let retry_location = format!("/subscriptions/preferences?token={}", queryparams.token);
+
+    let mut name = None;
+    let mut digest_frequency = None;
+    let mut selected_lists = vec![];
+    for (field, value) in form_data.into_inner() {
+        match field.as_str() {
+            "name" => name = Some(value),
+            "digest_frequency" => digest_frequency = Some(value),
+            "list" => selected_lists.push(value),
+            _ => {}
+        }
+    }
+    let name = match SubscriberName::parse(name.unwrap_or_default()) {
+        Ok(name) => name,
+        Err(e) => {
+            session
+                .insert_flash_message(&e)
+                .context("Failed to store the flash message")?;
+            return Ok(see_other(&retry_location));
+        }
+    };
+    let digest_frequency = match DigestFrequency::parse(digest_frequency.unwrap_or_default()) {
+        Ok(digest_frequency) => digest_frequency,
+        Err(e) => {
+            session
+                .insert_flash_message(&e)
+                .context("Failed to store the flash message")?;
+            return Ok(see_other(&retry_location));
+        }
+    };
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    let updated_subscriber = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET name = $2, digest_frequency = $3
+        WHERE id = $1 AND status = 'confirmed'
+        RETURNING id
+        "#,
+        subscriber_id,
+        name.as_ref(),
+        digest_frequency.as_str()
+    )
+    .fetch_optional(&mut *db_transaction)
+    .await
+    .context("Failed to update the subscriber's preferences")?;
+    if updated_subscriber.is_none() {
+        return Err(PreferencesError::InvalidToken);
+    }
+
+    for list in get_list_memberships(&mut db_transaction, subscriber_id).await? {
+        let is_member = list.status.as_deref() == Some("confirmed");
+        let is_selected = selected_lists.contains(&list.slug);
+        if is_selected && !is_member {
+            set_membership_status(&mut db_transaction, subscriber_id, list.id, "confirmed").await?;
+        } else if !is_selected
+            && list.status.is_some()
+            && list.status.as_deref() != Some("unsubscribed")
+        {
+            set_membership_status(&mut db_transaction, subscriber_id, list.id, "unsubscribed")
+                .await?;
+        }
+    }
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to update the preferences")?;
+
+    session
+        .insert_flash_message("Your preferences have been saved.")
+        .context("Failed to store the flash message")?;
+    Ok(see_other(&retry_location))
+}
+
+// Only confirmed subscribers can manage their preferences, a link sent before unsubscribing
+// stops working
+#[tracing::instrument(skip(db_transaction))]
+async fn get_subscriber_preferences(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberPreferences>, anyhow::Error> {
+    let subscriber = sqlx::query_as!(
+        SubscriberPreferences,
+        r#"
+        SELECT name, digest_frequency, unsubscribe_token
+        FROM subscriptions
+        WHERE id = $1 AND status = 'confirmed'
+        "#,
+        subscriber_id
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the subscriber's preferences")?;
+
+    Ok(subscriber)
+}
+
+// Every list is returned, with the status of the subscriber's membership when there is one
+#[tracing::instrument(skip(db_transaction))]
+async fn get_list_memberships(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<Vec<ListMembership>, anyhow::Error> {
+    let lists = sqlx::query_as!(
+        ListMembership,
+        r#"
+        SELECT l.id, l.slug, l.name, m.status AS "status?"
+        FROM lists l
+        LEFT JOIN list_memberships m ON m.list_id = l.id AND m.subscriber_id = $1
+        ORDER BY l.created_at, l.slug
+        "#,
+        subscriber_id
+    )
+    .fetch_all(&mut **db_transaction)
+    .await
+    .context("Failed to retrieve the subscriber's list memberships")?;
+
+    Ok(lists)
+}
+
+#[tracing::instrument(
+    name = "Send a preferences email",
+    skip(email_client, preferences_link, link_ttl)
+)]
+async fn send_preferences_email(
+    email_client: &dyn EmailSender,
+    recipient: &SubscriberEmail,
+    preferences_link: &str,
+    link_ttl: chrono::Duration,
+) -> Result<(), anyhow::Error> {
+    let plain_text_body = &format!(
+        "Visit {} to manage your subscription.\nThe link expires in {} minutes. \
+        If you did not ask for it, you can ignore this email.",
+        preferences_link,
+        link_ttl.num_minutes()
+    );
+    let html_body = PreferencesEmailTemplate {
+        preferences_link,
+        ttl_minutes: link_ttl.num_minutes(),
+    }
+    .render()
+    .expect("Failed to render html for preferences email");
+
+    email_client
+        .send_email(recipient, "Your preferences", &html_body, plain_text_body)
+        .await
+}
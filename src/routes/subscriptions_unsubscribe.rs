@@ -0,0 +1,134 @@
+use actix_web::{get, http::header::ContentType, post, web, HttpResponse};
+use anyhow::Context;
+use askama_actix::Template;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    errors::UnsubscribeError,
+    lists::leave_all_lists,
+    models::UnsubscribeToken,
+    templates::{UnsubscribeTemplate, UnsubscribedTemplate},
+};
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeQueryParameters {
+    pub token: String,
+}
+
+// Builds the link used in emails and in the `List-Unsubscribe` header. The same URL serves the
+// confirmation page on GET and the RFC 8058 one-click unsubscription on POST
+pub fn unsubscribe_link(application_base_url: &str, unsubscribe_token: &str) -> String {
+    format!(
+        "{}/subscriptions/unsubscribe?token={}",
+        application_base_url, unsubscribe_token
+    )
+}
+
+#[tracing::instrument(name = "Unsubscribe confirmation form", skip_all)]
+#[get("/subscriptions/unsubscribe")]
+pub async fn unsubscribe_form(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<UnsubscribeQueryParameters>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let unsubscribe_token =
+        UnsubscribeToken::parse(queryparams.0.token).map_err(UnsubscribeError::TokenError)?;
+
+    get_subscriber_id_from_unsubscribe_token(&db_connection_pool, &unsubscribe_token)
+        .await
+        .context("Failed to look up the subscriber associated to the unsubscribe token")?
+        .ok_or(UnsubscribeError::UnknownToken)?;
+
+    let html_body = UnsubscribeTemplate {
+        token: unsubscribe_token.as_ref(),
+    }
+    .render()
+    .context("Failed to render the unsubscribe page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+// Mail clients implementing RFC 8058 send `List-Unsubscribe=One-Click` in the body, the token in
+// the query string is all that is needed to identify the subscriber though
+#[tracing::instrument(name = "Unsubscribe subscriber", skip_all)]
+#[post("/subscriptions/unsubscribe")]
+pub async fn unsubscribe(
+    db_connection_pool: web::Data<PgPool>,
+    queryparams: web::Query<UnsubscribeQueryParameters>,
+) -> Result<HttpResponse, UnsubscribeError> {
+    let unsubscribe_token =
+        UnsubscribeToken::parse(queryparams.0.token).map_err(UnsubscribeError::TokenError)?;
+
+    let subscriber_id =
+        get_subscriber_id_from_unsubscribe_token(&db_connection_pool, &unsubscribe_token)
+            .await
+            .context("Failed to look up the subscriber associated to the unsubscribe token")?
+            .ok_or(UnsubscribeError::UnknownToken)?;
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    mark_subscriber_status_as_unsubscribed(&mut db_transaction, subscriber_id)
+        .await
+        .context("Failed to mark subscriber as unsubscribed")?;
+    leave_all_lists(&mut db_transaction, subscriber_id).await?;
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to unsubscribe the subscriber")?;
+
+    let html_body = UnsubscribedTemplate
+        .render()
+        .context("Failed to render the unsubscribed page")?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(ContentType::html())
+        .body(html_body))
+}
+
+#[tracing::instrument(
+    name = "Get subscriber id from unsubscribe token",
+    skip(db_connection_pool, unsubscribe_token)
+)]
+async fn get_subscriber_id_from_unsubscribe_token(
+    db_connection_pool: &PgPool,
+    unsubscribe_token: &UnsubscribeToken,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let result = sqlx::query!(
+        r#"SELECT id FROM subscriptions WHERE unsubscribe_token = $1"#,
+        unsubscribe_token.as_ref()
+    )
+    .fetch_optional(db_connection_pool)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result.map(|r| r.id))
+}
+
+#[tracing::instrument(
+    name = "Mark subscriber as unsubscribed",
+    skip(db_transaction, subscriber_id)
+)]
+async fn mark_subscriber_status_as_unsubscribed(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"UPDATE subscriptions SET status = 'unsubscribed' WHERE id = $1"#,
+        subscriber_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
+}
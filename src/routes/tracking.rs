@@ -0,0 +1,170 @@
+use actix_web::{
+    get,
+    http::header::{CacheControl, CacheDirective, LOCATION, USER_AGENT},
+    web, HttpRequest, HttpResponse,
+};
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    startup::HmacSecret,
+    tracking::{verify_click_token, verify_open_token},
+};
+
+// A transparent 1x1 GIF
+const PIXEL: &[u8] = &[
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+pub fn open_pixel_link(application_base_url: &str, open_token: &str) -> String {
+    format!("{}/t/o/{}.gif", application_base_url, open_token)
+}
+
+pub fn click_link(application_base_url: &str, click_token: &str) -> String {
+    format!("{}/t/c/{}", application_base_url, click_token)
+}
+
+// The pixel is served whatever happens, a broken image in the middle of an issue would only
+// bother the reader. It must not be cached, or later opens would never reach us
+#[tracing::instrument(name = "Track issue open", skip_all)]
+#[get("/t/o/{token}.gif")]
+pub async fn track_open(
+    request: HttpRequest,
+    db_connection_pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    token: web::Path<String>,
+) -> HttpResponse {
+    if let Some((newsletter_issue_id, subscriber_id)) = verify_open_token(&hmac_secret.0, &token) {
+        let user_agent = request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|user_agent| user_agent.to_str().ok());
+        if let Err(e) = record_open(
+            &db_connection_pool,
+            newsletter_issue_id,
+            subscriber_id,
+            user_agent,
+        )
+        .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to record an issue open"
+            );
+        }
+    }
+
+    HttpResponse::Ok()
+        .content_type("image/gif")
+        .insert_header(CacheControl(vec![
+            CacheDirective::NoStore,
+            CacheDirective::MaxAge(0),
+        ]))
+        .body(PIXEL)
+}
+
+// Erased subscribers are not tracked anymore, even through emails received before the erasure
+#[tracing::instrument(skip(db_connection_pool, user_agent))]
+async fn record_open(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    user_agent: Option<&str>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_opens (
+            newsletter_issue_id, subscriber_id, first_opened_at, last_opened_at, open_count,
+            user_agent
+        )
+        SELECT $1, $2, now(), now(), 1, $3
+        WHERE EXISTS (
+            SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $1
+        ) AND EXISTS (
+            SELECT 1 FROM subscriptions WHERE id = $2 AND status <> 'erased'
+        )
+        ON CONFLICT (newsletter_issue_id, subscriber_id) DO UPDATE
+        SET last_opened_at = now(),
+            open_count = newsletter_opens.open_count + 1,
+            user_agent = EXCLUDED.user_agent
+        "#,
+        newsletter_issue_id,
+        subscriber_id,
+        user_agent
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to record an issue open")?;
+
+    Ok(())
+}
+
+// Only signed tokens are redirected, anything else gets a 404 so that the endpoint cannot send
+// people to an address of somebody else's choosing
+#[tracing::instrument(name = "Track link click", skip_all)]
+#[get("/t/c/{token}")]
+pub async fn track_click(
+    db_connection_pool: web::Data<PgPool>,
+    hmac_secret: web::Data<HmacSecret>,
+    token: web::Path<String>,
+) -> HttpResponse {
+    let Some((newsletter_issue_id, subscriber_id, url)) =
+        verify_click_token(&hmac_secret.0, &token)
+    else {
+        return HttpResponse::NotFound().finish();
+    };
+
+    // Readers are sent on their way even when the click could not be recorded
+    if let Err(e) = record_click(
+        &db_connection_pool,
+        newsletter_issue_id,
+        subscriber_id,
+        &url,
+    )
+    .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to record a link click"
+        );
+    }
+
+    HttpResponse::Found()
+        .insert_header((LOCATION, url))
+        .insert_header(CacheControl(vec![CacheDirective::NoStore]))
+        .finish()
+}
+
+#[tracing::instrument(skip(db_connection_pool))]
+async fn record_click(
+    db_connection_pool: &PgPool,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    url: &str,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_clicks (id, newsletter_issue_id, subscriber_id, url, clicked_at)
+        SELECT $1, $2, $3, $4, now()
+        WHERE EXISTS (
+            SELECT 1 FROM newsletter_issues WHERE newsletter_issue_id = $2
+        ) AND EXISTS (
+            SELECT 1 FROM subscriptions WHERE id = $3 AND status <> 'erased'
+        )
+        "#,
+        Uuid::new_v4(),
+        newsletter_issue_id,
+        subscriber_id,
+        url
+    )
+    .execute(db_connection_pool)
+    .await
+    .context("Failed to record a link click")?;
+
+    Ok(())
+}
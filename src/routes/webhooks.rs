@@ -0,0 +1,230 @@
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use anyhow::Context;
+use secrecy::ExposeSecret;
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    authentication::basic_authentication,
+    errors::WebhookError,
+    lists::leave_all_lists,
+    startup::PostmarkWebhookCredentials,
+    suppressions::{suppress, SuppressionReason, SuppressionSource},
+};
+
+// Only the fields we act upon, Postmark sends many more
+#[derive(serde::Deserialize)]
+#[serde(tag = "RecordType")]
+enum PostmarkEvent {
+    Bounce {
+        #[serde(rename = "Type")]
+        bounce_type: String,
+        #[serde(rename = "Email")]
+        email: String,
+        // Set when Postmark stopped sending to the address because of the bounce
+        #[serde(rename = "Inactive", default)]
+        inactive: bool,
+    },
+    SpamComplaint {
+        #[serde(rename = "Email")]
+        email: String,
+    },
+    SubscriptionChange {
+        #[serde(rename = "Recipient")]
+        recipient: String,
+        #[serde(rename = "SuppressSending")]
+        suppress_sending: bool,
+        #[serde(rename = "SuppressionReason")]
+        suppression_reason: Option<String>,
+    },
+    // Deliveries, opens, clicks... are acknowledged and ignored
+    #[serde(other)]
+    Other,
+}
+
+// Subscription status an event moves the address to, along with the statuses it replaces. A
+// complaint is never turned back into a bounce, and nothing brings back an erased subscriber.
+// Bounces and complaints also put the address on the suppression list
+struct StatusChange {
+    status: &'static str,
+    replaces: &'static [&'static str],
+    suppression: Option<SuppressionReason>,
+}
+
+const BOUNCED: StatusChange = StatusChange {
+    status: "bounced",
+    replaces: &["pending_confirmation", "confirmed", "unsubscribed"],
+    suppression: Some(SuppressionReason::Bounced),
+};
+
+const COMPLAINED: StatusChange = StatusChange {
+    status: "complained",
+    replaces: &[
+        "pending_confirmation",
+        "confirmed",
+        "unsubscribed",
+        "bounced",
+    ],
+    suppression: Some(SuppressionReason::Complained),
+};
+
+const UNSUBSCRIBED: StatusChange = StatusChange {
+    status: "unsubscribed",
+    replaces: &["pending_confirmation", "confirmed"],
+    suppression: None,
+};
+
+impl PostmarkEvent {
+    // Soft bounces are transient, Postmark keeps trying and tells us if it gives up
+    fn status_change(&self) -> Option<(&str, &StatusChange)> {
+        match self {
+            PostmarkEvent::Bounce {
+                bounce_type,
+                email,
+                inactive,
+            } if bounce_type == "HardBounce" || *inactive => Some((email, &BOUNCED)),
+            PostmarkEvent::SpamComplaint { email } => Some((email, &COMPLAINED)),
+            PostmarkEvent::SubscriptionChange {
+                recipient,
+                suppress_sending: true,
+                suppression_reason: Some(reason),
+            } => match reason.as_str() {
+                "HardBounce" => Some((recipient, &BOUNCED)),
+                "SpamComplaint" => Some((recipient, &COMPLAINED)),
+                "ManualSuppression" => Some((recipient, &UNSUBSCRIBED)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+// Every well-formed event is acknowledged, even for unknown addresses, otherwise Postmark keeps
+// retrying it
+#[tracing::instrument(
+    name = "Handle Postmark webhook",
+    skip_all,
+    fields(subscriber_email = tracing::field::Empty)
+)]
+#[post("/webhooks/postmark")]
+pub async fn postmark_webhook(
+    request: HttpRequest,
+    body: web::Bytes,
+    db_connection_pool: web::Data<PgPool>,
+    credentials: web::Data<PostmarkWebhookCredentials>,
+) -> Result<HttpResponse, WebhookError> {
+    check_credentials(&request, &credentials)?;
+    let event: PostmarkEvent = serde_json::from_slice(&body)
+        .map_err(|e| WebhookError::ValidationError(format!("Invalid webhook payload: {}", e)))?;
+
+    let Some((email, change)) = event.status_change() else {
+        return Ok(HttpResponse::Ok().finish());
+    };
+    tracing::Span::current().record("subscriber_email", tracing::field::display(email));
+
+    let mut db_transaction = db_connection_pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool")?;
+    if let Some(reason) = change.suppression {
+        suppress(
+            &mut *db_transaction,
+            email,
+            reason,
+            SuppressionSource::Postmark,
+        )
+        .await?;
+    }
+    if let Some(subscriber_id) = change_subscription_status(&mut db_transaction, email, change)
+        .await
+        .context("Failed to update the subscription status")?
+    {
+        leave_all_lists(&mut db_transaction, subscriber_id).await?;
+        delete_pending_subscription_tokens(&mut db_transaction, subscriber_id)
+            .await
+            .context("Failed to delete the subscriber's pending confirmation tokens")?;
+        tracing::info!(%subscriber_id, status = change.status, "Subscription status changed");
+    }
+    db_transaction
+        .commit()
+        .await
+        .context("Failed to commit the SQL transaction to update the subscription")?;
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+fn check_credentials(
+    request: &HttpRequest,
+    expected: &PostmarkWebhookCredentials,
+) -> Result<(), WebhookError> {
+    let credentials = basic_authentication(request.headers()).map_err(WebhookError::AuthError)?;
+    let username_matches = constant_time_eq(&credentials.username, &expected.username);
+    let password_matches = constant_time_eq(
+        credentials.password.expose_secret(),
+        expected.password.expose_secret(),
+    );
+    if !(username_matches && password_matches) {
+        return Err(WebhookError::AuthError(anyhow::anyhow!(
+            "Invalid webhook credentials"
+        )));
+    }
+
+    Ok(())
+}
+
+// Comparison time does not depend on where the values differ, so the password cannot be
+// guessed one character at a time
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |difference, (a, b)| difference | (a ^ b))
+            == 0
+}
+
+#[tracing::instrument(skip(db_transaction, change), fields(status = change.status))]
+async fn change_subscription_status(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    email: &str,
+    change: &StatusChange,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let replaces: Vec<String> = change.replaces.iter().map(|s| s.to_string()).collect();
+    let result = sqlx::query!(
+        r#"
+        UPDATE subscriptions SET status = $2
+        WHERE email = $1 AND status = ANY($3)
+        RETURNING id
+        "#,
+        email,
+        change.status,
+        &replaces
+    )
+    .fetch_optional(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(result.map(|r| r.id))
+}
+
+// Confirmation links already sent to the address must not bring it back
+#[tracing::instrument(skip(db_transaction))]
+async fn delete_pending_subscription_tokens(
+    db_transaction: &mut Transaction<'_, Postgres>,
+    subscriber_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"DELETE FROM subscription_tokens WHERE subscriber_id = $1 AND consumed_at IS NULL"#,
+        subscriber_id
+    )
+    .execute(&mut **db_transaction)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to execute query: {:?}", e);
+        e
+    })?;
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+mod postgres_store;
+mod typed_session;
+
+pub use postgres_store::*;
+pub use typed_session::*;
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use actix_session::storage::{
+    generate_session_key, LoadError, SaveError, SessionKey, SessionStore, UpdateError,
+};
+use actix_web::cookie::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+type SessionState = HashMap<String, String>;
+
+// Session state lives in the application database so that running the app does not require any
+// additional service
+#[derive(Clone)]
+pub struct PostgresSessionStore {
+    db_connection_pool: PgPool,
+}
+
+impl PostgresSessionStore {
+    pub fn new(db_connection_pool: PgPool) -> Self {
+        Self { db_connection_pool }
+    }
+}
+
+fn get_expiration(ttl: &Duration) -> DateTime<Utc> {
+    Utc::now() + chrono::Duration::seconds(ttl.whole_seconds())
+}
+
+impl SessionStore for PostgresSessionStore {
+    async fn load(&self, session_key: &SessionKey) -> Result<Option<SessionState>, LoadError> {
+        let row = sqlx::query!(
+            r#"SELECT session_state FROM sessions WHERE session_key = $1 AND expires_at > now()"#,
+            session_key.as_ref()
+        )
+        .fetch_optional(&self.db_connection_pool)
+        .await
+        .context("Failed to load session state")
+        .map_err(LoadError::Other)?;
+
+        row.map(|r| serde_json::from_value(r.session_state))
+            .transpose()
+            .context("Failed to deserialize session state")
+            .map_err(LoadError::Deserialization)
+    }
+
+    async fn save(
+        &self,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, SaveError> {
+        let session_state = serde_json::to_value(session_state)
+            .context("Failed to serialize session state")
+            .map_err(SaveError::Serialization)?;
+        let session_key = generate_session_key();
+
+        // Sessions are only created on login, which is rare enough to piggyback the cleanup of
+        // expired ones on it
+        sqlx::query!(r#"DELETE FROM sessions WHERE expires_at <= now()"#)
+            .execute(&self.db_connection_pool)
+            .await
+            .context("Failed to delete expired sessions")
+            .map_err(SaveError::Other)?;
+
+        sqlx::query!(
+            r#"INSERT INTO sessions (session_key, session_state, expires_at) VALUES ($1, $2, $3)"#,
+            session_key.as_ref(),
+            session_state,
+            get_expiration(ttl)
+        )
+        .execute(&self.db_connection_pool)
+        .await
+        .context("Failed to save session state")
+        .map_err(SaveError::Other)?;
+
+        Ok(session_key)
+    }
+
+    async fn update(
+        &self,
+        session_key: SessionKey,
+        session_state: SessionState,
+        ttl: &Duration,
+    ) -> Result<SessionKey, UpdateError> {
+        let serialized_session_state = serde_json::to_value(&session_state)
+            .context("Failed to serialize session state")
+            .map_err(UpdateError::Serialization)?;
+
+        let result = sqlx::query!(
+            r#"
+            UPDATE sessions SET session_state = $2, expires_at = $3
+            WHERE session_key = $1 AND expires_at > now()
+            "#,
+            session_key.as_ref(),
+            serialized_session_state,
+            get_expiration(ttl)
+        )
+        .execute(&self.db_connection_pool)
+        .await
+        .context("Failed to update session state")
+        .map_err(UpdateError::Other)?;
+
+        // The session expired in the meantime, so it is stored again under a fresh key
+        if result.rows_affected() == 0 {
+            return self.save(session_state, ttl).await.map_err(|e| match e {
+                SaveError::Serialization(e) => UpdateError::Serialization(e),
+                SaveError::Other(e) => UpdateError::Other(e),
+            });
+        }
+
+        Ok(session_key)
+    }
+
+    async fn update_ttl(&self, session_key: &SessionKey, ttl: &Duration) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"UPDATE sessions SET expires_at = $2 WHERE session_key = $1"#,
+            session_key.as_ref(),
+            get_expiration(ttl)
+        )
+        .execute(&self.db_connection_pool)
+        .await
+        .context("Failed to update session TTL")?;
+
+        Ok(())
+    }
+
+    async fn delete(&self, session_key: &SessionKey) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"DELETE FROM sessions WHERE session_key = $1"#,
+            session_key.as_ref()
+        )
+        .execute(&self.db_connection_pool)
+        .await
+        .context("Failed to delete session")?;
+
+        Ok(())
+    }
+}
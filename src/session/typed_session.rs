@@ -0,0 +1,53 @@
+use std::future::{ready, Ready};
+
+use actix_session::{Session, SessionExt, SessionGetError, SessionInsertError};
+use actix_web::{dev::Payload, FromRequest, HttpRequest};
+use uuid::Uuid;
+
+// Wraps the untyped session so handlers cannot get the keys or value types wrong
+pub struct TypedSession(Session);
+
+impl TypedSession {
+    const USER_ID_KEY: &'static str = "user_id";
+    const FLASH_MESSAGE_KEY: &'static str = "flash_message";
+
+    // Rotates the session key, preventing session fixation attacks
+    pub fn renew(&self) {
+        self.0.renew();
+    }
+
+    pub fn insert_user_id(&self, user_id: Uuid) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::USER_ID_KEY, user_id)
+    }
+
+    pub fn get_user_id(&self) -> Result<Option<Uuid>, SessionGetError> {
+        self.0.get(Self::USER_ID_KEY)
+    }
+
+    // Drops all the session state and rotates the key, while still allowing a flash message to
+    // be stored for the next page
+    pub fn log_out(&self) {
+        self.0.clear();
+        self.0.renew();
+    }
+
+    pub fn insert_flash_message(&self, message: &str) -> Result<(), SessionInsertError> {
+        self.0.insert(Self::FLASH_MESSAGE_KEY, message)
+    }
+
+    // Flash messages are shown only once, so reading one removes it from the session
+    pub fn take_flash_message(&self) -> Option<String> {
+        self.0
+            .remove_as(Self::FLASH_MESSAGE_KEY)
+            .and_then(Result::ok)
+    }
+}
+
+impl FromRequest for TypedSession {
+    type Error = <Session as FromRequest>::Error;
+    type Future = Ready<Result<TypedSession, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Ok(TypedSession(req.get_session())))
+    }
+}
@@ -0,0 +1,58 @@
+use hmac::{Hmac, Mac};
+use secrecy::{ExposeSecret, SecretString};
+use sha2::Sha256;
+
+// Links carrying their own authorisation, such as the preference center ones, are signed with
+// the application HMAC secret instead of being stored
+pub fn sign(hmac_secret: &SecretString, message: &str) -> String {
+    let mut mac = new_mac(hmac_secret);
+    mac.update(message.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// The comparison runs in constant time, so the signature cannot be guessed byte by byte
+pub fn verify(hmac_secret: &SecretString, message: &str, signature: &str) -> bool {
+    let Ok(signature) = hex::decode(signature) else {
+        return false;
+    };
+    let mut mac = new_mac(hmac_secret);
+    mac.update(message.as_bytes());
+    mac.verify_slice(&signature).is_ok()
+}
+
+fn new_mac(hmac_secret: &SecretString) -> Hmac<Sha256> {
+    Hmac::<Sha256>::new_from_slice(hmac_secret.expose_secret().as_bytes())
+        .expect("HMAC accepts keys of any length")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secret() -> SecretString {
+        SecretString::new("super-long-and-secret-random-key".into())
+    }
+
+    #[test]
+    fn test_signature_is_verified() {
+        let signature = sign(&secret(), "message");
+        assert!(verify(&secret(), "message", &signature));
+    }
+
+    #[test]
+    fn test_signature_of_another_message_is_rejected() {
+        let signature = sign(&secret(), "message");
+        assert!(!verify(&secret(), "another message", &signature));
+    }
+
+    #[test]
+    fn test_signature_made_with_another_secret_is_rejected() {
+        let signature = sign(&SecretString::new("another secret".into()), "message");
+        assert!(!verify(&secret(), "message", &signature));
+    }
+
+    #[test]
+    fn test_malformed_signature_is_rejected() {
+        assert!(!verify(&secret(), "message", "not hex"));
+    }
+}
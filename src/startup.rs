@@ -1,12 +1,28 @@
-use actix_web::{dev::Server, web, App, HttpServer};
+use actix_session::SessionMiddleware;
+use actix_web::{cookie::Key, dev::Server, middleware::from_fn, web, App, HttpServer};
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::{postgres::PgPoolOptions, PgPool};
-use std::net::TcpListener;
+use std::{net::TcpListener, sync::Arc};
 use tracing_actix_web::TracingLogger;
 
 use crate::{
-    configuration::{DatabaseSettings, Settings},
-    email_client::EmailClient,
-    routes::{confirm_subscriber, health_check, publish_newsletter, subscribe},
+    authentication::reject_anonymous_users,
+    configuration::{ApplicationSettings, DatabaseSettings, EmailClientSettings, Settings},
+    email_client::EmailSender,
+    rate_limit::SubscriptionRateLimiter,
+    routes::{
+        add_suppression, admin_dashboard, archive_index, archived_issue, cancel_scheduled_issue,
+        change_admin_email, change_admin_password, change_email_form, change_password_form,
+        confirm_password_reset, confirm_subscriber, create_draft_issue, create_list,
+        delete_subscriber, edit_issue, erase_subscriber, export_data, export_subscribers,
+        get_issue_clicks, get_issue_opens, get_subscriber, health_check, import_subscribers,
+        list_issues, list_lists, list_subscribers, list_suppressions, log_in, log_in_form, log_out,
+        password_reset_confirm_form, password_reset_form, postmark_webhook, preferences_form,
+        preview_issue, publish_draft_issue, publish_newsletter, remove_suppression,
+        request_data_export, request_password_reset, request_preferences_link, send_test_issue,
+        subscribe, track_click, track_open, unsubscribe, unsubscribe_form, update_preferences,
+    },
+    session::PostgresSessionStore,
 };
 
 pub struct Application {
@@ -15,19 +31,31 @@ pub struct Application {
 }
 
 pub struct ApplicationBaseUrl(pub String);
+
+pub struct PasswordResetTokenTtl(pub chrono::Duration);
+
+pub struct SubscriptionTokenTtl(pub chrono::Duration);
+
+pub struct DataExportTokenTtl(pub chrono::Duration);
+
+pub struct PreferencesLinkTtl(pub chrono::Duration);
+
+pub struct HmacSecret(pub SecretString);
+
+pub struct ConfirmationEmailsPerDay(pub i64);
+
+pub struct PostmarkWebhookCredentials {
+    pub username: String,
+    pub password: SecretString,
+}
+
 impl Application {
     pub async fn build_application(
         configuration: &Settings,
     ) -> Result<Application, std::io::Error> {
         let db_connection_pool = get_db_connection_pool(&configuration.database);
 
-        let email_client_timeout = configuration.email_client.get_timeout();
-        let email_client = EmailClient::new(
-            &configuration.email_client.base_url,
-            configuration.email_client.sender_email.clone(),
-            configuration.email_client.api_token.clone(),
-            email_client_timeout,
-        );
+        let email_client = configuration.email_client.client();
 
         let server_address = format!(
             "{}:{}",
@@ -40,7 +68,8 @@ impl Application {
             server_tcp_socket,
             db_connection_pool,
             email_client,
-            configuration.application.base_url.clone(),
+            &configuration.application,
+            &configuration.email_client,
         )?;
 
         Ok(Self {
@@ -60,22 +89,113 @@ impl Application {
     fn build_http_server(
         tcp_socket: TcpListener,
         db_connection_pool: PgPool,
-        email_client: EmailClient,
-        server_base_url: String,
+        email_client: Arc<dyn EmailSender>,
+        application_settings: &ApplicationSettings,
+        email_client_settings: &EmailClientSettings,
     ) -> Result<Server, std::io::Error> {
+        let session_store = PostgresSessionStore::new(db_connection_pool.clone());
+        let session_key = Key::from(application_settings.hmac_secret.expose_secret().as_bytes());
         let db_connection_pool = web::Data::new(db_connection_pool);
-        let http_email_client = web::Data::new(email_client);
-        let server_base_url = web::Data::new(ApplicationBaseUrl(server_base_url));
+        let http_email_client = web::Data::from(email_client);
+        let server_base_url =
+            web::Data::new(ApplicationBaseUrl(application_settings.base_url.clone()));
+        let password_reset_token_ttl = web::Data::new(PasswordResetTokenTtl(
+            application_settings.get_password_reset_token_ttl(),
+        ));
+        let subscription_token_ttl = web::Data::new(SubscriptionTokenTtl(
+            application_settings.get_subscription_token_ttl(),
+        ));
+        let data_export_token_ttl = web::Data::new(DataExportTokenTtl(
+            application_settings.get_data_export_token_ttl(),
+        ));
+        let preferences_link_ttl = web::Data::new(PreferencesLinkTtl(
+            application_settings.get_preferences_link_ttl(),
+        ));
+        let hmac_secret = web::Data::new(HmacSecret(application_settings.hmac_secret.clone()));
+        let subscription_rate_limiter = web::Data::new(SubscriptionRateLimiter::new(
+            &application_settings.subscription_rate_limit,
+        ));
+        let confirmation_emails_per_day = web::Data::new(ConfirmationEmailsPerDay(
+            application_settings
+                .subscription_rate_limit
+                .max_confirmation_emails_per_day,
+        ));
+        let webhook_credentials = web::Data::new(PostmarkWebhookCredentials {
+            username: email_client_settings.webhook_username.clone(),
+            password: email_client_settings.webhook_password.clone(),
+        });
         let server = HttpServer::new(move || {
             App::new()
+                .wrap(SessionMiddleware::new(
+                    session_store.clone(),
+                    session_key.clone(),
+                ))
                 .wrap(TracingLogger::default())
                 .app_data(db_connection_pool.clone())
                 .app_data(http_email_client.clone())
                 .app_data(server_base_url.clone())
+                .app_data(password_reset_token_ttl.clone())
+                .app_data(subscription_token_ttl.clone())
+                .app_data(data_export_token_ttl.clone())
+                .app_data(preferences_link_ttl.clone())
+                .app_data(hmac_secret.clone())
+                .app_data(webhook_credentials.clone())
+                .app_data(subscription_rate_limiter.clone())
+                .app_data(confirmation_emails_per_day.clone())
                 .service(health_check)
                 .service(subscribe)
                 .service(confirm_subscriber)
+                .service(unsubscribe_form)
+                .service(unsubscribe)
+                .service(request_data_export)
+                .service(export_data)
+                .service(request_preferences_link)
+                .service(preferences_form)
+                .service(update_preferences)
                 .service(publish_newsletter)
+                .service(archive_index)
+                .service(archived_issue)
+                .service(track_open)
+                .service(track_click)
+                .service(postmark_webhook)
+                .service(log_in_form)
+                .service(log_in)
+                .service(log_out)
+                .service(password_reset_form)
+                .service(request_password_reset)
+                .service(password_reset_confirm_form)
+                .service(confirm_password_reset)
+                .service(
+                    web::scope("/admin")
+                        .wrap(from_fn(reject_anonymous_users))
+                        // Subscriber imports can be much larger than the default payload limit
+                        .app_data(web::PayloadConfig::new(10 * 1024 * 1024))
+                        .service(admin_dashboard)
+                        .service(change_email_form)
+                        .service(change_admin_email)
+                        .service(change_password_form)
+                        .service(change_admin_password)
+                        .service(list_subscribers)
+                        .service(import_subscribers)
+                        .service(export_subscribers)
+                        .service(get_subscriber)
+                        .service(delete_subscriber)
+                        .service(erase_subscriber)
+                        .service(list_suppressions)
+                        .service(add_suppression)
+                        .service(remove_suppression)
+                        .service(list_lists)
+                        .service(create_list)
+                        .service(list_issues)
+                        .service(create_draft_issue)
+                        .service(edit_issue)
+                        .service(cancel_scheduled_issue)
+                        .service(publish_draft_issue)
+                        .service(preview_issue)
+                        .service(send_test_issue)
+                        .service(get_issue_opens)
+                        .service(get_issue_clicks),
+                )
         })
         .listen(tcp_socket)?
         .run();
@@ -0,0 +1,94 @@
+use anyhow::Context;
+use sqlx::PgExecutor;
+
+// Why an address was suppressed. `Manual` covers people asking never to be contacted again
+#[derive(Debug, Clone, Copy)]
+pub enum SuppressionReason {
+    Bounced,
+    Complained,
+    Manual,
+}
+
+impl SuppressionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionReason::Bounced => "bounced",
+            SuppressionReason::Complained => "complained",
+            SuppressionReason::Manual => "manual",
+        }
+    }
+}
+
+impl TryFrom<&str> for SuppressionReason {
+    type Error = String;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "bounced" => Ok(SuppressionReason::Bounced),
+            "complained" => Ok(SuppressionReason::Complained),
+            "manual" => Ok(SuppressionReason::Manual),
+            other => Err(format!(
+                "{} is not a valid suppression reason, use one of: bounced, complained, manual",
+                other
+            )),
+        }
+    }
+}
+
+// Where the suppression comes from, either the email provider or an admin
+#[derive(Debug, Clone, Copy)]
+pub enum SuppressionSource {
+    Postmark,
+    Admin,
+}
+
+impl SuppressionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuppressionSource::Postmark => "postmark",
+            SuppressionSource::Admin => "admin",
+        }
+    }
+}
+
+// Every email sent by the application goes through this check first
+#[tracing::instrument(skip(executor))]
+pub async fn is_suppressed(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+) -> Result<bool, anyhow::Error> {
+    let suppression = sqlx::query!(
+        r#"SELECT 1 AS "found!" FROM suppressions WHERE email = lower($1)"#,
+        email
+    )
+    .fetch_optional(executor)
+    .await
+    .context("Failed to look up the suppression list")?;
+
+    Ok(suppression.is_some())
+}
+
+// Returns false when the address was already suppressed, the existing entry is kept as is
+#[tracing::instrument(skip(executor))]
+pub async fn suppress(
+    executor: impl PgExecutor<'_>,
+    email: &str,
+    reason: SuppressionReason,
+    source: SuppressionSource,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        INSERT INTO suppressions (email, reason, source, created_at)
+        VALUES (lower($1), $2, $3, now())
+        ON CONFLICT (email) DO NOTHING
+        "#,
+        email,
+        reason.as_str(),
+        source.as_str()
+    )
+    .execute(executor)
+    .await
+    .context("Failed to add an address to the suppression list")?;
+
+    Ok(result.rows_affected() == 1)
+}
@@ -1,3 +1,4 @@
+use tokio::task::JoinHandle;
 use tracing::{subscriber, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
 use tracing_log::LogTracer;
@@ -27,3 +28,14 @@ pub fn init_tracing_subscriber(subscriber: impl Subscriber + Send + Sync) {
 
     subscriber::set_global_default(subscriber).expect("Failed to set tracing subscriber");
 }
+
+// Runs CPU-heavy work (e.g. password hashing) off the async executor while keeping it attached
+// to the caller's tracing span
+pub fn spawn_blocking_with_tracing<F, R>(f: F) -> JoinHandle<R>
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let current_span = tracing::Span::current();
+    tokio::task::spawn_blocking(move || current_span.in_scope(f))
+}
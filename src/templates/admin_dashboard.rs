@@ -0,0 +1,7 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "admin_dashboard.html")]
+pub struct AdminDashboardTemplate<'a> {
+    pub username: &'a str,
+}
@@ -0,0 +1,7 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "already_subscribed_email.html")]
+pub struct AlreadySubscribedEmailTemplate<'a> {
+    pub unsubscribe_link: &'a str,
+}
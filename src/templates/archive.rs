@@ -0,0 +1,23 @@
+use askama_actix::Template;
+
+pub struct ArchivedIssue {
+    pub slug: String,
+    pub title: String,
+    pub published_on: String,
+}
+
+#[derive(Template)]
+#[template(path = "archive.html")]
+pub struct ArchiveTemplate<'a> {
+    pub issues: &'a [ArchivedIssue],
+}
+
+// The HTML content was written by an authenticated publisher and is rendered as is
+#[derive(Template)]
+#[template(path = "archive_issue.html")]
+pub struct ArchiveIssueTemplate<'a> {
+    pub title: &'a str,
+    pub author: Option<&'a str>,
+    pub published_on: &'a str,
+    pub html_content: &'a str,
+}
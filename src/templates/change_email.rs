@@ -0,0 +1,8 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "change_email.html")]
+pub struct ChangeEmailTemplate<'a> {
+    pub current_email: Option<&'a str>,
+    pub message: Option<&'a str>,
+}
@@ -0,0 +1,7 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "change_password.html")]
+pub struct ChangePasswordTemplate<'a> {
+    pub message: Option<&'a str>,
+}
@@ -0,0 +1,8 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "data_export_email.html")]
+pub struct DataExportEmailTemplate<'a> {
+    pub data_export_link: &'a str,
+    pub ttl_minutes: i64,
+}
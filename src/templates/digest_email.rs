@@ -0,0 +1,13 @@
+use askama_actix::Template;
+
+// An issue of the digest, already personalized for the subscriber
+pub struct DigestIssue {
+    pub title: String,
+    pub body: String,
+}
+
+#[derive(Template)]
+#[template(path = "digest_email.html")]
+pub struct DigestEmailTemplate<'a> {
+    pub issues: &'a [DigestIssue],
+}
@@ -0,0 +1,7 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "login.html")]
+pub struct LoginTemplate<'a> {
+    pub error_message: Option<&'a str>,
+}
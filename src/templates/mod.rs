@@ -1,3 +1,31 @@
+mod admin_dashboard;
+mod already_subscribed_email;
+mod archive;
+mod change_email;
+mod change_password;
 mod confirmation_email;
+mod data_export_email;
+mod digest_email;
+mod login;
+mod newsletter_issue;
+mod password_reset;
+mod preferences;
+mod unsubscribe;
 
+pub use admin_dashboard::AdminDashboardTemplate;
+pub use already_subscribed_email::AlreadySubscribedEmailTemplate;
+pub use archive::{ArchiveIssueTemplate, ArchiveTemplate, ArchivedIssue};
+pub use change_email::ChangeEmailTemplate;
+pub use change_password::ChangePasswordTemplate;
 pub use confirmation_email::ConfirmationEmailTemplate;
+pub use data_export_email::DataExportEmailTemplate;
+pub use digest_email::{DigestEmailTemplate, DigestIssue};
+pub use login::LoginTemplate;
+pub use newsletter_issue::NewsletterIssueTemplate;
+pub use password_reset::{
+    PasswordResetConfirmTemplate, PasswordResetEmailTemplate, PasswordResetTemplate,
+};
+pub use preferences::{
+    DigestFrequencyOption, ListPreference, PreferencesEmailTemplate, PreferencesTemplate,
+};
+pub use unsubscribe::{UnsubscribeTemplate, UnsubscribedTemplate};
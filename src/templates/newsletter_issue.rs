@@ -0,0 +1,8 @@
+use askama_actix::Template;
+
+// The body is sanitized HTML rendered from the issue's Markdown
+#[derive(Template)]
+#[template(path = "newsletter_issue.html")]
+pub struct NewsletterIssueTemplate<'a> {
+    pub body: &'a str,
+}
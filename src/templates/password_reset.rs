@@ -0,0 +1,22 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "password_reset.html")]
+pub struct PasswordResetTemplate<'a> {
+    pub message: Option<&'a str>,
+}
+
+// The form is only rendered when the token is still valid
+#[derive(Template)]
+#[template(path = "password_reset_confirm.html")]
+pub struct PasswordResetConfirmTemplate<'a> {
+    pub token: Option<&'a str>,
+    pub message: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "password_reset_email.html")]
+pub struct PasswordResetEmailTemplate<'a> {
+    pub password_reset_link: &'a str,
+    pub ttl_minutes: i64,
+}
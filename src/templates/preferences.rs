@@ -0,0 +1,30 @@
+use askama_actix::Template;
+
+pub struct ListPreference {
+    pub slug: String,
+    pub name: String,
+    pub subscribed: bool,
+}
+
+pub struct DigestFrequencyOption {
+    pub value: &'static str,
+    pub selected: bool,
+}
+
+#[derive(Template)]
+#[template(path = "preferences.html")]
+pub struct PreferencesTemplate<'a> {
+    pub token: &'a str,
+    pub name: &'a str,
+    pub digest_frequencies: &'a [DigestFrequencyOption],
+    pub lists: &'a [ListPreference],
+    pub unsubscribe_token: &'a str,
+    pub message: Option<&'a str>,
+}
+
+#[derive(Template)]
+#[template(path = "preferences_email.html")]
+pub struct PreferencesEmailTemplate<'a> {
+    pub preferences_link: &'a str,
+    pub ttl_minutes: i64,
+}
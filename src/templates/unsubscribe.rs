@@ -0,0 +1,11 @@
+use askama_actix::Template;
+
+#[derive(Template)]
+#[template(path = "unsubscribe.html")]
+pub struct UnsubscribeTemplate<'a> {
+    pub token: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "unsubscribed.html")]
+pub struct UnsubscribedTemplate;
@@ -0,0 +1,288 @@
+use base64::Engine;
+use secrecy::SecretString;
+use uuid::Uuid;
+
+use crate::signing;
+
+// Tracking links carry the issue and the subscriber they were sent to, signed with the HMAC
+// secret so that they cannot be forged for somebody else
+pub fn create_open_token(
+    hmac_secret: &SecretString,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+) -> String {
+    let payload = format!("{}.{}", newsletter_issue_id, subscriber_id);
+    let signature = signing::sign(hmac_secret, &format!("open.{}", payload));
+    format!("{}.{}", payload, signature)
+}
+
+// Returns the issue and the subscriber, or None if the token was tampered with
+pub fn verify_open_token(hmac_secret: &SecretString, token: &str) -> Option<(Uuid, Uuid)> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    if !signing::verify(hmac_secret, &format!("open.{}", payload), signature) {
+        return None;
+    }
+    let (newsletter_issue_id, subscriber_id) = payload.split_once('.')?;
+
+    Some((
+        Uuid::parse_str(newsletter_issue_id).ok()?,
+        Uuid::parse_str(subscriber_id).ok()?,
+    ))
+}
+
+// The destination travels inside the token, so the redirect endpoint only ever sends readers
+// to URLs that we signed ourselves
+pub fn create_click_token(
+    hmac_secret: &SecretString,
+    newsletter_issue_id: Uuid,
+    subscriber_id: Uuid,
+    url: &str,
+) -> String {
+    let payload = format!(
+        "{}.{}.{}",
+        newsletter_issue_id,
+        subscriber_id,
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(url)
+    );
+    let signature = signing::sign(hmac_secret, &format!("click.{}", payload));
+    format!("{}.{}", payload, signature)
+}
+
+// Returns the issue, the subscriber and the destination, or None if the token was tampered with
+pub fn verify_click_token(hmac_secret: &SecretString, token: &str) -> Option<(Uuid, Uuid, String)> {
+    let (payload, signature) = token.rsplit_once('.')?;
+    if !signing::verify(hmac_secret, &format!("click.{}", payload), signature) {
+        return None;
+    }
+    let (newsletter_issue_id, rest) = payload.split_once('.')?;
+    let (subscriber_id, url) = rest.split_once('.')?;
+    let url = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(url)
+        .ok()?;
+
+    Some((
+        Uuid::parse_str(newsletter_issue_id).ok()?,
+        Uuid::parse_str(subscriber_id).ok()?,
+        String::from_utf8(url).ok()?,
+    ))
+}
+
+fn is_trackable(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+// Rewrites the `href` of every anchor pointing to a web page. `rewrite` gets the URL with its
+// `&amp;` entities decoded and returns None to leave a link as it is
+pub fn rewrite_html_links(html: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let lowercase_html = html.to_ascii_lowercase();
+    let mut output = String::with_capacity(html.len());
+    let mut position = 0;
+    while let Some(offset) = lowercase_html[position..].find("<a") {
+        let tag_start = position + offset;
+        let Some(tag_length) = lowercase_html[tag_start..].find('>') else {
+            break;
+        };
+        let tag_end = tag_start + tag_length;
+        let is_anchor =
+            lowercase_html[tag_start + 2..].starts_with(|c: char| c.is_ascii_whitespace());
+        let href = is_anchor
+            .then(|| find_href(&lowercase_html[tag_start..tag_end]))
+            .flatten();
+        let Some((value_start, value_end)) = href else {
+            output.push_str(&html[position..tag_end]);
+            position = tag_end;
+            continue;
+        };
+        let (value_start, value_end) = (tag_start + value_start, tag_start + value_end);
+        let url = html[value_start..value_end].replace("&amp;", "&");
+        output.push_str(&html[position..value_start]);
+        match is_trackable(&url).then(|| rewrite(&url)).flatten() {
+            Some(tracked_url) => output.push_str(&tracked_url),
+            None => output.push_str(&html[value_start..value_end]),
+        }
+        position = value_end;
+    }
+    output.push_str(&html[position..]);
+    output
+}
+
+// Position of the quoted value of the `href` attribute within a tag
+fn find_href(tag: &str) -> Option<(usize, usize)> {
+    let mut search_from = 0;
+    while let Some(offset) = tag[search_from..].find("href") {
+        let name_start = search_from + offset;
+        search_from = name_start + "href".len();
+        if !tag[..name_start].ends_with(|c: char| c.is_ascii_whitespace()) {
+            continue;
+        }
+        let rest = tag[search_from..].trim_start();
+        let Some(rest) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let rest = rest.trim_start();
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value_start = tag.len() - rest.len() + 1;
+        let value_length = tag[value_start..].find(quote)?;
+        return Some((value_start, value_start + value_length));
+    }
+    None
+}
+
+// Rewrites every web address in plain text. Trailing punctuation is taken as part of the
+// sentence rather than of the address
+pub fn rewrite_text_links(text: &str, rewrite: impl Fn(&str) -> Option<String>) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut position = 0;
+    while let Some(url_start) = find_url_start(&text[position..]).map(|offset| position + offset) {
+        let url_length = text[url_start..]
+            .find(|c: char| c.is_whitespace() || c == '<' || c == '>' || c == '"')
+            .unwrap_or(text.len() - url_start);
+        let url = text[url_start..url_start + url_length]
+            .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', ']', '\'']);
+        output.push_str(&text[position..url_start]);
+        match rewrite(url) {
+            Some(tracked_url) => output.push_str(&tracked_url),
+            None => output.push_str(url),
+        }
+        position = url_start + url.len();
+    }
+    output.push_str(&text[position..]);
+    output
+}
+
+fn find_url_start(text: &str) -> Option<usize> {
+    match (text.find("http://"), text.find("https://")) {
+        (Some(http), Some(https)) => Some(http.min(https)),
+        (http, https) => http.or(https),
+    }
+}
+
+// The pixel goes right before the end of the body, or at the very end for HTML fragments
+pub fn inject_open_pixel(html: &str, pixel_link: &str) -> String {
+    let pixel = format!(
+        r#"<img src="{}" width="1" height="1" alt="" style="border: 0;">"#,
+        pixel_link
+    );
+    match html.to_ascii_lowercase().rfind("</body>") {
+        Some(position) => format!("{}{}{}", &html[..position], pixel, &html[position..]),
+        None => format!("{}{}", html, pixel),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_none, assert_some_eq};
+
+    use super::*;
+
+    fn hmac_secret() -> SecretString {
+        SecretString::new("secret".into())
+    }
+
+    #[test]
+    fn test_open_tokens_can_be_verified() {
+        let (newsletter_issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let token = create_open_token(&hmac_secret(), newsletter_issue_id, subscriber_id);
+
+        assert_some_eq!(
+            verify_open_token(&hmac_secret(), &token),
+            (newsletter_issue_id, subscriber_id)
+        );
+    }
+
+    #[test]
+    fn test_forged_open_tokens_are_rejected() {
+        let token = create_open_token(&hmac_secret(), Uuid::new_v4(), Uuid::new_v4());
+        let forged = format!("{}.{}", Uuid::new_v4(), token.split_once('.').unwrap().1);
+
+        assert_none!(verify_open_token(&hmac_secret(), &forged));
+        assert_none!(verify_open_token(
+            &SecretString::new("another secret".into()),
+            &token
+        ));
+        assert_none!(verify_open_token(&hmac_secret(), "garbage"));
+    }
+
+    #[test]
+    fn test_click_tokens_can_be_verified() {
+        let (newsletter_issue_id, subscriber_id) = (Uuid::new_v4(), Uuid::new_v4());
+        let url = "https://example.com/post?a=1&b=2#top";
+        let token = create_click_token(&hmac_secret(), newsletter_issue_id, subscriber_id, url);
+
+        assert_some_eq!(
+            verify_click_token(&hmac_secret(), &token),
+            (newsletter_issue_id, subscriber_id, url.to_owned())
+        );
+    }
+
+    #[test]
+    fn test_click_tokens_cannot_be_pointed_elsewhere() {
+        let token = create_click_token(
+            &hmac_secret(),
+            Uuid::new_v4(),
+            Uuid::new_v4(),
+            "https://example.com",
+        );
+        let (payload, signature) = token.rsplit_once('.').unwrap();
+        let (ids, _) = payload.rsplit_once('.').unwrap();
+        let evil_url = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode("https://evil.com");
+        let forged = format!("{}.{}.{}", ids, evil_url, signature);
+
+        assert_none!(verify_click_token(&hmac_secret(), &forged));
+        // Open tokens are not valid click tokens
+        let open_token = create_open_token(&hmac_secret(), Uuid::new_v4(), Uuid::new_v4());
+        assert_none!(verify_click_token(&hmac_secret(), &open_token));
+    }
+
+    #[test]
+    fn test_html_links_are_rewritten() {
+        let html = r#"<p><A class="x" HREF="https://a.com/?x=1&amp;y=2">a</A> <abbr>b</abbr>
+            <a href='http://b.com'>b</a> <a href="mailto:me@c.com">c</a>
+            <a name="top">d</a> <img src="https://d.com/i.png"></p>"#;
+
+        let rewritten = rewrite_html_links(html, |url| Some(format!("tracked[{}]", url)));
+
+        assert_eq!(
+            rewritten,
+            r#"<p><A class="x" HREF="tracked[https://a.com/?x=1&y=2]">a</A> <abbr>b</abbr>
+            <a href='tracked[http://b.com]'>b</a> <a href="mailto:me@c.com">c</a>
+            <a name="top">d</a> <img src="https://d.com/i.png"></p>"#
+        );
+    }
+
+    #[test]
+    fn test_links_can_be_left_alone() {
+        let html = r#"<a href="https://a.com">a</a>"#;
+
+        assert_eq!(rewrite_html_links(html, |_| None), html);
+        assert_eq!(
+            rewrite_text_links("See https://a.com.", |_| None),
+            "See https://a.com."
+        );
+    }
+
+    #[test]
+    fn test_text_links_are_rewritten() {
+        let text =
+            "Read https://a.com/post. Or (http://b.com/x?y=1), not ftp://c.com\n[1] https://d.com";
+
+        let rewritten = rewrite_text_links(text, |url| Some(format!("tracked[{}]", url)));
+
+        assert_eq!(
+            rewritten,
+            "Read tracked[https://a.com/post]. Or (tracked[http://b.com/x?y=1]), not ftp://c.com\n\
+            [1] tracked[https://d.com]"
+        );
+    }
+
+    #[test]
+    fn test_pixel_is_injected_before_the_end_of_the_body() {
+        assert_eq!(
+            inject_open_pixel("<html><BODY><p>Hi</p></BODY></html>", "https://x/p.gif"),
+            "<html><BODY><p>Hi</p>\
+            <img src=\"https://x/p.gif\" width=\"1\" height=\"1\" alt=\"\" style=\"border: 0;\">\
+            </BODY></html>"
+        );
+        assert!(inject_open_pixel("<p>Hi</p>", "https://x/p.gif").starts_with("<p>Hi</p><img"));
+    }
+}
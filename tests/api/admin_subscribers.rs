@@ -0,0 +1,322 @@
+use chrono::{DateTime, Duration, Utc};
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+async fn insert_subscriber(
+    app: &TestingApp,
+    email: &str,
+    name: &str,
+    status: &str,
+    subscribed_at: DateTime<Utc>,
+) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO subscriptions (id, email, name, subscribed_at, status, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+        subscriber_id,
+        email,
+        name,
+        subscribed_at,
+        status,
+        Uuid::new_v4().simple().to_string()
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+
+    subscriber_id
+}
+
+fn subscriber_emails(page: &serde_json::Value) -> Vec<&str> {
+    page["subscribers"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|s| s["email"].as_str().unwrap())
+        .collect()
+}
+
+#[actix_web::test]
+async fn test_you_must_be_logged_in_to_manage_subscribers() {
+    let app = spawn_app().await;
+    let subscriber_id = Uuid::new_v4().to_string();
+
+    let response = app.get_admin_subscribers(&()).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_admin_subscriber(&subscriber_id).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.delete_admin_subscriber(&subscriber_id).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_subscribers_are_listed_newest_first() {
+    let app = spawn_app().await;
+    let now = Utc::now();
+    insert_subscriber(
+        &app,
+        "old@example.com",
+        "old",
+        "confirmed",
+        now - Duration::days(2),
+    )
+    .await;
+    insert_subscriber(&app, "new@example.com", "new", "confirmed", now).await;
+    app.log_in_test_user().await;
+
+    let response = app.get_admin_subscribers(&()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let page: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        subscriber_emails(&page),
+        vec!["new@example.com", "old@example.com"]
+    );
+    assert!(page["next_cursor"].is_null());
+}
+
+#[actix_web::test]
+async fn test_subscribers_can_be_filtered() {
+    let app = spawn_app().await;
+    let now = Utc::now();
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "le guin",
+        "confirmed",
+        now - Duration::days(10),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "butler",
+        "confirmed",
+        now - Duration::days(1),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "ted@example.com",
+        "chiang",
+        "pending_confirmation",
+        now,
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "under_score@example.com",
+        "n. k. jemisin",
+        "unsubscribed",
+        now,
+    )
+    .await;
+    app.log_in_test_user().await;
+
+    let page: serde_json::Value = app
+        .get_admin_subscribers(&[("status", "confirmed")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        subscriber_emails(&page),
+        vec!["octavia@example.com", "ursula@example.com"]
+    );
+
+    let page: serde_json::Value = app
+        .get_admin_subscribers(&[("search", "GUIN")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber_emails(&page), vec!["ursula@example.com"]);
+
+    // Wildcards are matched literally
+    let page: serde_json::Value = app
+        .get_admin_subscribers(&[("search", "_")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber_emails(&page), vec!["under_score@example.com"]);
+
+    let subscribed_after = (now - Duration::days(5)).to_rfc3339();
+    let subscribed_before = (now - Duration::hours(1)).to_rfc3339();
+    let page: serde_json::Value = app
+        .get_admin_subscribers(&[
+            ("subscribed_after", subscribed_after.as_str()),
+            ("subscribed_before", subscribed_before.as_str()),
+        ])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(subscriber_emails(&page), vec!["octavia@example.com"]);
+}
+
+#[actix_web::test]
+async fn test_subscribers_are_paginated_with_a_cursor() {
+    let app = spawn_app().await;
+    let now = Utc::now();
+    for i in 0..5 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{}@example.com", i),
+            "name",
+            "confirmed",
+            now - Duration::minutes(i),
+        )
+        .await;
+    }
+    app.log_in_test_user().await;
+
+    let mut emails = vec![];
+    let mut cursor: Option<String> = None;
+    let mut pages = 0;
+    loop {
+        let mut query = vec![("limit", "2".to_string())];
+        if let Some(cursor) = &cursor {
+            query.push(("cursor", cursor.clone()));
+        }
+        let page: serde_json::Value = app
+            .get_admin_subscribers(&query)
+            .await
+            .json()
+            .await
+            .unwrap();
+        pages += 1;
+        emails.extend(subscriber_emails(&page).into_iter().map(String::from));
+        match page["next_cursor"].as_str() {
+            Some(next_cursor) => cursor = Some(next_cursor.to_string()),
+            None => break,
+        }
+    }
+
+    assert_eq!(pages, 3);
+    assert_eq!(
+        emails,
+        (0..5)
+            .map(|i| format!("subscriber{}@example.com", i))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[actix_web::test]
+async fn test_invalid_list_parameters_are_rejected_with_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .get_admin_subscribers(&[("cursor", "not-a-cursor")])
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let response = app.get_admin_subscribers(&[("limit", "0")]).await;
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_subscriber_details_are_returned() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "le guin",
+        "pending_confirmation",
+        Utc::now(),
+    )
+    .await;
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)",
+        "a".repeat(25),
+        subscriber_id
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+    app.log_in_test_user().await;
+
+    let response = app.get_admin_subscriber(&subscriber_id.to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(subscriber["id"], subscriber_id.to_string());
+    assert_eq!(subscriber["email"], "ursula@example.com");
+    assert_eq!(subscriber["name"], "le guin");
+    assert_eq!(subscriber["status"], "pending_confirmation");
+    assert_eq!(
+        subscriber["confirmation_tokens"].as_array().unwrap().len(),
+        1
+    );
+    assert!(subscriber["confirmation_tokens"][0]["consumed_at"].is_null());
+}
+
+#[actix_web::test]
+async fn test_unknown_subscriber_returns_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let subscriber_id = Uuid::new_v4().to_string();
+
+    let response = app.get_admin_subscriber(&subscriber_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.delete_admin_subscriber(&subscriber_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_subscriber_is_deleted_with_its_tokens() {
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "le guin",
+        "pending_confirmation",
+        Utc::now(),
+    )
+    .await;
+    sqlx::query!(
+        "INSERT INTO subscription_tokens (subscription_token, subscriber_id) VALUES ($1, $2)",
+        "a".repeat(25),
+        subscriber_id
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+    app.log_in_test_user().await;
+
+    let response = app
+        .delete_admin_subscriber(&subscriber_id.to_string())
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let response = app.get_admin_subscriber(&subscriber_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 404);
+    let remaining_tokens = sqlx::query!(
+        "SELECT COUNT(*) as \"count!\" FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap();
+    assert_eq!(remaining_tokens.count, 0);
+
+    let audit_event = sqlx::query!(
+        "SELECT action, actor FROM gdpr_audit_log WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap();
+    assert_eq!(audit_event.action, "deleted");
+    assert_eq!(
+        audit_event.actor,
+        format!("admin:{}", app.test_user.user_id)
+    );
+}
@@ -0,0 +1,308 @@
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+const VALID_CSV: &str = "email,name,status,subscribed_at
+ursula@example.com,le guin,confirmed,2020-01-01T10:00:00Z
+octavia@example.com,butler,pending_confirmation,
+ted@example.com,chiang,,
+";
+
+#[actix_web::test]
+async fn test_you_must_be_logged_in_to_import_or_export_subscribers() {
+    let app = spawn_app().await;
+
+    let response = app.post_subscribers_import(&(), VALID_CSV).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_subscribers_export().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_valid_rows_are_imported() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app.post_subscribers_import(&(), VALID_CSV).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 3);
+    assert_eq!(report["errors"], serde_json::json!([]));
+
+    let subscribers =
+        sqlx::query!("SELECT email, status, subscribed_at FROM subscriptions ORDER BY email")
+            .fetch_all(&app.db_connection_pool)
+            .await
+            .unwrap();
+    let subscribers: Vec<_> = subscribers
+        .iter()
+        .map(|s| (s.email.as_str(), s.status.as_str()))
+        .collect();
+    assert_eq!(
+        subscribers,
+        vec![
+            ("octavia@example.com", "pending_confirmation"),
+            ("ted@example.com", "pending_confirmation"),
+            ("ursula@example.com", "confirmed"),
+        ]
+    );
+}
+
+#[actix_web::test]
+async fn test_invalid_rows_are_reported_and_skipped() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let csv = "email,name,status,subscribed_at
+ursula@example.com,le guin,confirmed,
+not-an-email,butler,,
+ted@example.com,,,
+ursula@example.com,le guin,confirmed,
+ann@example.com,leckie,subscribed,
+nk@example.com,jemisin,,yesterday
+";
+
+    let response = app.post_subscribers_import(&(), csv).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 1);
+    let failed_lines: Vec<_> = report["errors"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(failed_lines, vec![3, 4, 5, 6, 7]);
+}
+
+#[actix_web::test]
+async fn test_emails_already_on_the_list_are_reported() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.post_subscribers_import(&(), VALID_CSV).await;
+
+    let response = app.post_subscribers_import(&(), VALID_CSV).await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["errors"].as_array().unwrap().len(), 3);
+}
+
+#[actix_web::test]
+async fn test_emails_already_on_the_list_with_another_case_are_reported() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.post_subscribers_import(&(), VALID_CSV).await;
+
+    let response = app
+        .post_subscribers_import(&(), "email,name\nUrsula@Example.com,le guin\n")
+        .await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(
+        report["errors"][0]["error"],
+        "The email is already on the list"
+    );
+}
+
+#[actix_web::test]
+async fn test_dry_run_does_not_import_anything() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .post_subscribers_import(
+            &[("dry_run", "true"), ("send_confirmation_emails", "true")],
+            VALID_CSV,
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["dry_run"], true);
+    assert_eq!(report["imported"], 3);
+    let subscribers = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscribers.count, 0);
+}
+
+#[actix_web::test]
+async fn test_confirmation_emails_are_sent_to_imported_pending_subscribers() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .post_subscribers_import(&[("send_confirmation_emails", "true")], VALID_CSV)
+        .await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["confirmation_emails_sent"], 2);
+
+    let email_request = &app.mock_email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_email_confirmation_links(email_request);
+    let response = reqwest::get(confirmation_links.html_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn test_no_confirmation_emails_are_sent_by_default() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_subscribers_import(&(), VALID_CSV).await;
+
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["confirmation_emails_sent"], 0);
+}
+
+#[actix_web::test]
+async fn test_exported_csv_can_be_imported_back() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.post_subscribers_import(&(), VALID_CSV).await;
+
+    let response = app.get_subscribers_export().await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("email,name,status,subscribed_at"));
+    assert_eq!(
+        lines.next(),
+        Some("ursula@example.com,le guin,confirmed,2020-01-01T10:00:00Z")
+    );
+    assert_eq!(lines.count(), 2);
+
+    sqlx::query!("DELETE FROM subscriptions")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    let report: serde_json::Value = app
+        .post_subscribers_import(&(), &csv)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["imported"], 3);
+}
+
+#[actix_web::test]
+async fn test_bounced_and_complained_subscribers_are_restored_as_suppressed() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let csv = "email,name,status,subscribed_at
+ursula@example.com,le guin,bounced,2020-01-01T10:00:00Z
+octavia@example.com,butler,complained,2020-01-01T10:00:00Z
+";
+
+    let report: serde_json::Value = app
+        .post_subscribers_import(&(), csv)
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    assert_eq!(report["imported"], 2);
+    let suppressions =
+        sqlx::query!("SELECT email, reason, source FROM suppressions ORDER BY email")
+            .fetch_all(&app.db_connection_pool)
+            .await
+            .unwrap();
+    let suppressions: Vec<_> = suppressions
+        .iter()
+        .map(|s| (s.email.as_str(), s.reason.as_str(), s.source.as_str()))
+        .collect();
+    assert_eq!(
+        suppressions,
+        vec![
+            ("octavia@example.com", "complained", "admin"),
+            ("ursula@example.com", "bounced", "admin"),
+        ]
+    );
+    let memberships = sqlx::query!("SELECT status FROM list_memberships")
+        .fetch_all(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert!(memberships.iter().all(|m| m.status == "unsubscribed"));
+}
+
+#[actix_web::test]
+async fn test_a_backup_with_every_status_can_be_restored() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let csv = "email,name,status,subscribed_at
+ursula@example.com,le guin,confirmed,2020-01-01T10:00:00Z
+octavia@example.com,butler,bounced,2020-01-02T10:00:00Z
+ted@example.com,chiang,complained,2020-01-03T10:00:00Z
+ann@example.com,leckie,unsubscribed,2020-01-04T10:00:00Z
+";
+    app.post_subscribers_import(&(), csv).await;
+    let erased_id = sqlx::query!("SELECT id FROM subscriptions WHERE email = 'ann@example.com'")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .id;
+    let response = app.post_erase_subscriber(&erased_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let backup = app.get_subscribers_export().await.text().await.unwrap();
+    assert!(!backup.contains("erased"));
+
+    sqlx::query!("DELETE FROM subscriptions")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    sqlx::query!("DELETE FROM suppressions")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    let report: serde_json::Value = app
+        .post_subscribers_import(&(), &backup)
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["imported"], 3);
+    assert_eq!(report["errors"], serde_json::json!([]));
+}
+
+#[actix_web::test]
+async fn test_export_of_an_empty_list_only_contains_the_header() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let csv = app.get_subscribers_export().await.text().await.unwrap();
+
+    assert_eq!(csv, "email,name,status,subscribed_at\n");
+}
@@ -0,0 +1,120 @@
+use crate::helpers::{spawn_app, TestingApp};
+
+async fn publish_issue(app: &TestingApp, title: &str, subscribers_only: bool) {
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": title,
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter <em>body</em></p>"
+            },
+            "subscribers_only": subscribers_only
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_web::test]
+async fn test_archive_is_empty_before_anything_is_published() {
+    let app = spawn_app().await;
+
+    let html_page = app.get_archive_html().await;
+
+    assert!(html_page.contains("Nothing has been published yet."));
+}
+
+#[actix_web::test]
+async fn test_published_issues_are_listed_in_the_archive() {
+    let app = spawn_app().await;
+    publish_issue(&app, "Rust 2024: What's New?", false).await;
+
+    let html_page = app.get_archive_html().await;
+
+    assert!(html_page.contains(r#"<a href="/archive/rust-2024-what-s-new">"#));
+}
+
+#[actix_web::test]
+async fn test_archived_issue_page_shows_the_content_and_its_author() {
+    let app = spawn_app().await;
+    publish_issue(&app, "Weekly news", false).await;
+
+    let response = app.get_archived_issue("weekly-news").await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("<p>Newsletter <em>body</em></p>"));
+    assert!(html_page.contains(&format!("by {}", app.test_user.username)));
+}
+
+#[actix_web::test]
+async fn test_markdown_issues_are_not_nested_documents_in_the_archive() {
+    let app = spawn_app().await;
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Weekly news",
+            "content": { "markdown": "Newsletter *body*" }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let html_page = app
+        .get_archived_issue("weekly-news")
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    assert!(html_page.contains("<p>Newsletter <em>body</em></p>"));
+    for tag in ["<!DOCTYPE", "<html", "<head", "<body"] {
+        assert_eq!(html_page.matches(tag).count(), 1, "{} is repeated", tag);
+    }
+}
+
+#[actix_web::test]
+async fn test_issues_sharing_a_title_get_distinct_slugs() {
+    let app = spawn_app().await;
+    publish_issue(&app, "Weekly news", false).await;
+    publish_issue(&app, "Weekly news", false).await;
+
+    for slug in ["weekly-news", "weekly-news-2"] {
+        let response = app.get_archived_issue(slug).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[actix_web::test]
+async fn test_issues_sharing_a_title_published_concurrently_get_distinct_slugs() {
+    let app = spawn_app().await;
+
+    tokio::join!(
+        publish_issue(&app, "Weekly news", false),
+        publish_issue(&app, "Weekly news", false),
+        publish_issue(&app, "Weekly news", false),
+    );
+
+    for slug in ["weekly-news", "weekly-news-2", "weekly-news-3"] {
+        let response = app.get_archived_issue(slug).await;
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
+
+#[actix_web::test]
+async fn test_subscriber_only_issues_are_not_archived() {
+    let app = spawn_app().await;
+    publish_issue(&app, "Members only", true).await;
+
+    let html_page = app.get_archive_html().await;
+    assert!(!html_page.contains("Members only"));
+
+    let response = app.get_archived_issue("members-only").await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+
+    let response = app.get_archived_issue("does-not-exist").await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
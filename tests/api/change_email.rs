@@ -0,0 +1,117 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[actix_web::test]
+async fn test_you_must_be_logged_in_to_change_your_email() {
+    let app = spawn_app().await;
+
+    let response = app.get_change_email().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "new.admin@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_current_password_must_be_valid() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": Uuid::new_v4().to_string(),
+            "email": "new.admin@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>The current password is incorrect.</i></p>"));
+    assert!(html_page.contains(&app.test_user.email));
+}
+
+#[actix_web::test]
+async fn test_invalid_email_is_rejected() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "not-an-email",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>not-an-email is not a valid email.</i></p>"));
+    assert!(html_page.contains(&app.test_user.email));
+}
+
+#[actix_web::test]
+async fn test_email_used_by_another_account_is_rejected() {
+    let app = spawn_app().await;
+    sqlx::query!(
+        "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, $2, $3, $4)",
+        Uuid::new_v4(),
+        "another_admin",
+        "not a hash",
+        "another.admin@example.com",
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "another.admin@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>This email is already used by another account.</i></p>"));
+}
+
+#[actix_web::test]
+async fn test_the_new_email_receives_password_reset_links() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_change_email(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "email": "new.admin@example.com",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/email");
+    let html_page = app.get_change_email_html().await;
+    assert!(html_page.contains("<p><i>Your email has been changed.</i></p>"));
+    assert!(html_page.contains("new.admin@example.com"));
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+    app.post_password_reset_request(&app.test_user.email).await;
+    app.post_password_reset_request("new.admin@example.com")
+        .await;
+
+    let email_request = &app.mock_email_server.received_requests().await.unwrap()[0];
+    let request_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(request_body["To"], "new.admin@example.com");
+}
@@ -0,0 +1,167 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+// Publishes an issue with two links, delivers it and returns its id along with the email sent
+async fn publish_and_deliver(app: &TestingApp) -> (String, serde_json::Value) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Read https://example.com/post?a=1&b=2 or \
+                    unsubscribe at {{ unsubscribe_url }}",
+                "html": "<p><a href=\"https://example.com/post?a=1&amp;b=2\">Read</a> \
+                    <a href=\"https://example.com/docs\">Docs</a> \
+                    <a href=\"mailto:editor@example.com\">Write to us</a> \
+                    <a href=\"{{ unsubscribe_url }}\">Unsubscribe</a></p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    (
+        issue_id.to_string(),
+        serde_json::from_slice(&email_request.body).unwrap(),
+    )
+}
+
+fn get_click_links(body: &str) -> Vec<String> {
+    let links: Vec<_> = linkify::LinkFinder::new()
+        .links(body)
+        .filter(|l| *l.kind() == linkify::LinkKind::Url)
+        .map(|l| l.as_str().to_owned())
+        .filter(|l| l.contains("/t/c/"))
+        .collect();
+    links
+}
+
+fn no_redirect_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn test_links_are_rewritten_to_the_click_tracker() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let (_, email) = publish_and_deliver(&app).await;
+
+    let html_body = email["HtmlBody"].as_str().unwrap();
+    assert_eq!(get_click_links(html_body).len(), 2);
+    assert!(!html_body.contains("https://example.com"));
+    assert!(html_body.contains("mailto:editor@example.com"));
+    assert!(html_body.contains("/subscriptions/unsubscribe?token="));
+    let text_body = email["TextBody"].as_str().unwrap();
+    assert_eq!(get_click_links(text_body).len(), 1);
+    assert!(text_body.contains("/subscriptions/unsubscribe?token="));
+}
+
+#[actix_web::test]
+async fn test_clicks_are_recorded_and_redirected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let (issue_id, email) = publish_and_deliver(&app).await;
+    let html_links = get_click_links(email["HtmlBody"].as_str().unwrap());
+    let text_links = get_click_links(email["TextBody"].as_str().unwrap());
+    let client = no_redirect_client();
+
+    for link in [&html_links[0], &html_links[1], &text_links[0]] {
+        let response = client.get(link).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 302);
+    }
+    let response = client.get(&html_links[0]).send().await.unwrap();
+    assert_eq!(
+        response.headers()["location"],
+        "https://example.com/post?a=1&b=2"
+    );
+
+    app.log_in_test_user().await;
+    let clicks: serde_json::Value = app.get_issue_clicks(&issue_id).await.json().await.unwrap();
+    assert_eq!(
+        clicks["links"],
+        serde_json::json!([
+            {
+                "url": "https://example.com/post?a=1&b=2",
+                "total_clicks": 3,
+                "unique_clicks": 1
+            },
+            {
+                "url": "https://example.com/docs",
+                "total_clicks": 1,
+                "unique_clicks": 1
+            }
+        ])
+    );
+}
+
+#[actix_web::test]
+async fn test_tampered_click_links_are_not_redirected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let (_, email) = publish_and_deliver(&app).await;
+    let link = get_click_links(email["HtmlBody"].as_str().unwrap()).remove(0);
+    let client = no_redirect_client();
+    let (prefix, signature) = link.rsplit_once('.').unwrap();
+    let (prefix, _) = prefix.rsplit_once('.').unwrap();
+    let tampered_links = [
+        format!("{}.aHR0cHM6Ly9ldmlsLmNvbQ.{}", prefix, signature),
+        format!("{}/t/c/https%3A%2F%2Fevil.com", app.server_address),
+    ];
+
+    for tampered_link in tampered_links {
+        let response = client.get(&tampered_link).send().await.unwrap();
+        assert_eq!(response.status().as_u16(), 404);
+    }
+    let clicks = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_clicks")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(clicks.count, 0);
+}
+
+#[actix_web::test]
+async fn test_click_statistics_require_an_authenticated_admin() {
+    let app = spawn_app().await;
+
+    let response = app.get_issue_clicks(&Uuid::new_v4().to_string()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_click_statistics_of_an_unknown_issue_return_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app.get_issue_clicks(&Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
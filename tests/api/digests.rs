@@ -0,0 +1,167 @@
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestingApp};
+
+async fn set_digest_frequency(app: &TestingApp, digest_frequency: &str) {
+    sqlx::query!(
+        "UPDATE subscriptions SET digest_frequency = $1",
+        digest_frequency
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+}
+
+async fn publish_issue(app: &TestingApp, title: &str) {
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": title,
+            "content": {
+                "plain_text": format!("{} body", title),
+                "html": format!("<p>{} body</p>", title)
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+// Pretends the issues waiting for a digest were queued that long ago
+async fn age_digest_queue(app: &TestingApp, days: f64) {
+    sqlx::query!(
+        "UPDATE digest_queue SET queued_at = now() - make_interval(secs => $1)",
+        days * 24.0 * 3600.0
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+}
+
+#[actix_web::test]
+async fn test_digest_subscribers_do_not_receive_issues_straight_away() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    set_digest_frequency(&app, "daily").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    publish_issue(&app, "First issue").await;
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_daily_digests_bundle_the_issues_of_the_past_day() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    set_digest_frequency(&app, "daily").await;
+    publish_issue(&app, "First issue").await;
+    publish_issue(&app, "Second issue").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    age_digest_queue(&app, 1.5).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["Subject"], "Your daily digest");
+    let html_body = email["HtmlBody"].as_str().unwrap();
+    assert!(html_body.contains("<p>First issue body</p>"));
+    assert!(html_body.contains("<p>Second issue body</p>"));
+    assert!(html_body.find("First issue").unwrap() < html_body.find("Second issue").unwrap());
+    let text_body = email["TextBody"].as_str().unwrap();
+    assert!(text_body.contains("First issue body"));
+    assert!(text_body.contains("Second issue body"));
+
+    let deliveries = sqlx::query!("SELECT outcome FROM newsletter_deliveries")
+        .fetch_all(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.len(), 2);
+    assert!(deliveries.iter().all(|d| d.outcome == "delivered"));
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM digest_queue")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+}
+
+#[actix_web::test]
+async fn test_weekly_digests_wait_for_a_full_week() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    set_digest_frequency(&app, "weekly").await;
+    publish_issue(&app, "First issue").await;
+
+    let mock_guard = Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+    age_digest_queue(&app, 3.0).await;
+    app.dispatch_all_pending_emails().await;
+    drop(mock_guard);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+    age_digest_queue(&app, 7.5).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["Subject"], "Your weekly digest");
+}
+
+#[actix_web::test]
+async fn test_subscribers_who_left_before_their_digest_is_due_receive_nothing() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    set_digest_frequency(&app, "daily").await;
+    publish_issue(&app, "First issue").await;
+    sqlx::query!("UPDATE subscriptions SET status = 'unsubscribed'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    age_digest_queue(&app, 1.5).await;
+    app.dispatch_all_pending_emails().await;
+
+    let queued = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM digest_queue")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(queued.count, 0);
+}
@@ -0,0 +1,313 @@
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+// Creates a draft and returns its id
+async fn create_draft(app: &TestingApp) -> String {
+    let response = app
+        .post_admin_issue(&serde_json::json!({
+            "title": "Draft issue",
+            "content": {
+                "plain_text": "Draft body",
+                "html": "<p>Draft body</p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["status"], "draft");
+    issue["newsletter_issue_id"].as_str().unwrap().to_owned()
+}
+
+#[actix_web::test]
+async fn test_draft_endpoints_require_an_authenticated_admin() {
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_admin_issue(&serde_json::json!({
+            "title": "Draft issue",
+            "content": { "plain_text": "Draft body", "html": "<p>Draft body</p>" }
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.get_issue_preview(&issue_id, "html").await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_test_issue(
+            &issue_id,
+            &serde_json::json!({ "recipients": ["seed@example.com"] }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_publish_issue(&issue_id, &serde_json::json!({}))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_drafts_are_not_delivered() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    create_draft(&app).await;
+    assert_eq!(app.publish_due_issues().await, 0);
+    app.dispatch_all_pending_emails().await;
+
+    let issues: serde_json::Value = app
+        .get_admin_issues(&[("status", "draft")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(issues.as_array().unwrap().len(), 1);
+    assert!(issues[0]["slug"].is_null());
+}
+
+#[actix_web::test]
+async fn test_creating_a_draft_with_invalid_data_returns_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let test_cases = [
+        (
+            serde_json::json!({
+                "title": " ",
+                "content": { "plain_text": "Draft body", "html": "<p>Draft body</p>" }
+            }),
+            "empty title",
+        ),
+        (
+            serde_json::json!({
+                "title": "Draft issue",
+                "content": { "plain_text": "Draft body", "html": "<p>Draft body</p>" },
+                "lists": ["nope"]
+            }),
+            "unknown list",
+        ),
+    ];
+
+    for (body, description) in test_cases {
+        let response = app.post_admin_issue(&body).await;
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the payload had an {}.",
+            description
+        );
+    }
+}
+
+#[actix_web::test]
+async fn test_editing_a_draft_with_an_empty_title_returns_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = create_draft(&app).await;
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "title": "   " }))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+
+    let issue = sqlx::query!("SELECT title FROM newsletter_issues")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(issue.title, "Draft issue");
+}
+
+#[actix_web::test]
+async fn test_preview_shows_the_content_subscribers_receive() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = create_draft(&app).await;
+
+    let response = app.get_issue_preview(&issue_id, "html").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/html"));
+    assert_eq!(response.text().await.unwrap(), "<p>Draft body</p>");
+
+    let response = app.get_issue_preview(&issue_id, "text").await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["content-type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain"));
+    assert_eq!(response.text().await.unwrap(), "Draft body");
+}
+
+#[actix_web::test]
+async fn test_preview_fills_placeholders_with_sample_values() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let response = app
+        .post_admin_issue(&serde_json::json!({
+            "title": "Draft issue",
+            "content": { "markdown": "Hi {{ name }}" }
+        }))
+        .await;
+    let issue: serde_json::Value = response.json().await.unwrap();
+    let issue_id = issue["newsletter_issue_id"].as_str().unwrap();
+
+    let response = app.get_issue_preview(issue_id, "text").await;
+
+    assert_eq!(response.text().await.unwrap(), "Hi Sample Subscriber");
+}
+
+#[actix_web::test]
+async fn test_preview_of_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .get_issue_preview(&Uuid::new_v4().to_string(), "html")
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_test_sends_only_reach_the_seed_addresses() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    let issue_id = create_draft(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .post_test_issue(
+            &issue_id,
+            &serde_json::json!({ "recipients": ["seed@example.com", "qa@example.com"] }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    app.dispatch_all_pending_emails().await;
+
+    // The confirmation email sent to the subscriber comes first
+    let requests = app.mock_email_server.received_requests().await.unwrap();
+    let mut recipients = vec![];
+    for request in &requests[1..] {
+        let body: serde_json::Value = serde_json::from_slice(&request.body).unwrap();
+        assert_eq!(body["Subject"], "Draft issue");
+        assert_eq!(body["HtmlBody"], "<p>Draft body</p>");
+        assert_eq!(body["TextBody"], "Draft body");
+        recipients.push(body["To"].as_str().unwrap().to_owned());
+    }
+    recipients.sort();
+    assert_eq!(recipients, ["qa@example.com", "seed@example.com"]);
+
+    // Test sends leave the draft untouched
+    let issues: serde_json::Value = app.get_admin_issues(&()).await.json().await.unwrap();
+    assert_eq!(issues[0]["status"], "draft");
+}
+
+#[actix_web::test]
+async fn test_test_sends_with_invalid_recipients_return_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = create_draft(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    for recipients in [serde_json::json!([]), serde_json::json!(["not-an-email"])] {
+        let response = app
+            .post_test_issue(&issue_id, &serde_json::json!({ "recipients": recipients }))
+            .await;
+        assert_eq!(response.status().as_u16(), 400);
+    }
+}
+
+#[actix_web::test]
+async fn test_drafts_can_be_edited_and_published() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    let issue_id = create_draft(&app).await;
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "title": "Final issue" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .post_publish_issue(&issue_id, &serde_json::json!({}))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["status"], "published");
+    assert_eq!(issue["slug"], "final-issue");
+    app.dispatch_all_pending_emails().await;
+
+    // A published issue is no longer a draft
+    let response = app
+        .post_publish_issue(&issue_id, &serde_json::json!({}))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn test_drafts_can_be_scheduled() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = create_draft(&app).await;
+
+    let response = app
+        .post_publish_issue(
+            &issue_id,
+            &serde_json::json!({ "send_at": Utc::now() + Duration::hours(1) }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["status"], "scheduled");
+    assert!(issue["send_at"].is_string());
+}
+
+#[actix_web::test]
+async fn test_publishing_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_publish_issue(&Uuid::new_v4().to_string(), &serde_json::json!({}))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,279 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestingApp};
+
+const SUBSCRIBER_EMAIL: &str = "ursula_le_guin@gmail.com";
+
+async fn deliver_a_newsletter(app: &TestingApp) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+    app.send_newsletter(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    app.dispatch_all_pending_emails().await;
+}
+
+async fn get_data_export_link(app: &TestingApp) -> reqwest::Url {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+    let response = app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let email_request = &app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_email_confirmation_links(email_request).html_link
+}
+
+async fn get_audit_actions(app: &TestingApp, subscriber_id: Uuid) -> Vec<String> {
+    sqlx::query!(
+        "SELECT action FROM gdpr_audit_log WHERE subscriber_id = $1 ORDER BY occurred_at",
+        subscriber_id
+    )
+    .fetch_all(&app.db_connection_pool)
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|r| r.action)
+    .collect()
+}
+
+#[actix_web::test]
+async fn test_data_export_request_for_unknown_email_sends_nothing() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_web::test]
+async fn test_a_failed_data_export_email_gives_the_same_answer_as_an_unknown_address() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_web::test]
+async fn test_data_export_requests_are_rate_limited() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_email
+            .capacity = 2;
+    })
+    .await;
+    // Takes the first token of the address
+    app.create_confirmed_subscriber().await;
+    get_data_export_link(&app).await;
+
+    let response = app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[actix_web::test]
+async fn test_data_export_emails_count_towards_the_daily_email_cap() {
+    let app = spawn_app_with(|configuration| {
+        let rate_limit = &mut configuration.application.subscription_rate_limit;
+        rate_limit.per_email.capacity = 10;
+        rate_limit.max_confirmation_emails_per_day = 2;
+    })
+    .await;
+    // The confirmation email takes the first slot
+    app.create_confirmed_subscriber().await;
+    let data_export_link = get_data_export_link(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // The link that was sent is still valid
+    let response = reqwest::get(data_export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn test_data_export_link_returns_the_subscriber_data() {
+    let app = spawn_app().await;
+    let subscriber_id = app.create_confirmed_subscriber().await;
+    deliver_a_newsletter(&app).await;
+
+    let data_export_link = get_data_export_link(&app).await;
+    let response = reqwest::get(data_export_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .contains("attachment"));
+    let bundle: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(bundle["subscription"]["id"], subscriber_id.to_string());
+    assert_eq!(bundle["subscription"]["email"], SUBSCRIBER_EMAIL);
+    assert_eq!(bundle["subscription"]["status"], "confirmed");
+    assert_eq!(bundle["subscription_tokens"].as_array().unwrap().len(), 1);
+    assert_eq!(bundle["deliveries"].as_array().unwrap().len(), 1);
+    assert_eq!(bundle["deliveries"][0]["title"], "Newsletter title");
+    assert_eq!(bundle["deliveries"][0]["outcome"], "delivered");
+
+    assert_eq!(
+        get_audit_actions(&app, subscriber_id).await,
+        vec!["data_export_requested", "data_exported"]
+    );
+}
+
+#[actix_web::test]
+async fn test_data_export_link_can_only_be_used_once() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let data_export_link = get_data_export_link(&app).await;
+    let response = reqwest::get(data_export_link.clone()).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let response = reqwest::get(data_export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_expired_data_export_link_is_rejected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let data_export_link = get_data_export_link(&app).await;
+    sqlx::query!("UPDATE data_export_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+
+    let response = reqwest::get(data_export_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_you_must_be_logged_in_to_erase_a_subscriber() {
+    let app = spawn_app().await;
+
+    let response = app.post_erase_subscriber(&Uuid::new_v4().to_string()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_erasing_an_unknown_subscriber_returns_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app.post_erase_subscriber(&Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[actix_web::test]
+async fn test_erasure_anonymises_the_subscriber_and_keeps_counts() {
+    let app = spawn_app().await;
+    let subscriber_id = app.create_confirmed_subscriber().await;
+    deliver_a_newsletter(&app).await;
+    app.log_in_test_user().await;
+
+    let response = app.post_erase_subscriber(&subscriber_id.to_string()).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    let subscriber = sqlx::query!(
+        "SELECT email, name, status FROM subscriptions WHERE id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap();
+    assert_ne!(subscriber.email, SUBSCRIBER_EMAIL);
+    assert_ne!(subscriber.name, "le guin");
+    assert_eq!(subscriber.status, "erased");
+
+    let tokens = sqlx::query!(
+        "SELECT COUNT(*) as \"count!\" FROM subscription_tokens WHERE subscriber_id = $1",
+        subscriber_id
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap();
+    assert_eq!(tokens.count, 0);
+    let deliveries = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM newsletter_deliveries")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(deliveries.count, 1);
+
+    assert_eq!(get_audit_actions(&app, subscriber_id).await, vec!["erased"]);
+}
+
+#[actix_web::test]
+async fn test_erased_subscriber_no_longer_receives_anything() {
+    let app = spawn_app().await;
+    let subscriber_id = app.create_confirmed_subscriber().await;
+    app.log_in_test_user().await;
+    app.post_erase_subscriber(&subscriber_id.to_string())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.post_data_export_request(SUBSCRIBER_EMAIL).await;
+    app.send_newsletter(serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+}
@@ -1,11 +1,22 @@
+use std::sync::Arc;
+
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
 use once_cell::sync::Lazy;
 use reqwest::Url;
+use secrecy::{ExposeSecret, SecretString};
 use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::{
+    matchers::{method, path},
+    Mock, MockServer, ResponseTemplate,
+};
 
 use rust_zero2prod::{
-    configuration::{self, DatabaseSettings},
+    configuration::{self, DatabaseSettings, Settings},
+    email_client::EmailSender,
+    issue_delivery_worker::{try_execute_digest_task, try_execute_task, ExecutionOutcome},
+    issue_scheduler::publish_due_issues,
+    routes::delete_expired_subscription_tokens,
     startup::{get_db_connection_pool, Application},
     telemetry,
 };
@@ -37,6 +48,55 @@ pub struct TestingApp {
     pub server_port: u16,
     pub db_connection_pool: PgPool,
     pub mock_email_server: MockServer,
+    pub test_user: TestUser,
+    pub api_client: reqwest::Client,
+    pub email_client: Arc<dyn EmailSender>,
+    pub hmac_secret: SecretString,
+    pub subscription_token_ttl: chrono::Duration,
+    pub webhook_username: String,
+    pub webhook_password: String,
+}
+
+pub struct TestUser {
+    pub user_id: Uuid,
+    pub username: String,
+    pub password: String,
+    pub email: String,
+}
+
+impl TestUser {
+    pub fn generate() -> Self {
+        Self {
+            user_id: Uuid::new_v4(),
+            username: Uuid::new_v4().to_string(),
+            password: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", Uuid::new_v4()),
+        }
+    }
+
+    async fn store(&self, db_connection_pool: &PgPool) {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        // Same parameters as the production hashing, so verification costs the same
+        let password_hash = Argon2::new(
+            Algorithm::Argon2id,
+            Version::V0x13,
+            Params::new(15000, 2, 1, None).unwrap(),
+        )
+        .hash_password(self.password.as_bytes(), &salt)
+        .unwrap()
+        .to_string();
+
+        sqlx::query!(
+            "INSERT INTO users (user_id, username, password_hash, email) VALUES ($1, $2, $3, $4)",
+            self.user_id,
+            self.username,
+            password_hash,
+            self.email,
+        )
+        .execute(db_connection_pool)
+        .await
+        .expect("Failed to store test user");
+    }
 }
 
 pub struct ConfirmationLinks {
@@ -45,9 +105,48 @@ pub struct ConfirmationLinks {
 }
 
 impl TestingApp {
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.db_connection_pool,
+                self.email_client.as_ref(),
+                &self.server_address,
+                &self.hmac_secret,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_digest_task(
+                &self.db_connection_pool,
+                self.email_client.as_ref(),
+                &self.server_address,
+                &self.hmac_secret,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    pub async fn publish_due_issues(&self) -> usize {
+        publish_due_issues(&self.db_connection_pool).await.unwrap()
+    }
+
+    pub async fn delete_expired_subscription_tokens(&self) -> u64 {
+        delete_expired_subscription_tokens(&self.db_connection_pool, self.subscription_token_ttl)
+            .await
+            .unwrap()
+    }
+
     pub async fn send_subscription_request(&self, body: String) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/subscriptions", &self.server_address))
+            .post(format!("{}/subscriptions", &self.server_address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -55,15 +154,560 @@ impl TestingApp {
             .expect("Failed to execute request")
     }
 
+    pub async fn create_unconfirmed_subscriber_with_email(&self, email: &str) -> ConfirmationLinks {
+        let body = format!("name=le%20guin&email={}", email.replace('@', "%40"));
+
+        let _mock_guard = Mock::given(path("/email"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200))
+            .named("Create unconfirmed subscriber")
+            .expect(1)
+            .mount_as_scoped(&self.mock_email_server)
+            .await;
+
+        self.send_subscription_request(body)
+            .await
+            .error_for_status()
+            .unwrap();
+
+        let email_request = &self
+            .mock_email_server
+            .received_requests()
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
+        self.get_email_confirmation_links(email_request)
+    }
+
+    pub async fn create_unconfirmed_subscriber(&self) -> ConfirmationLinks {
+        self.create_unconfirmed_subscriber_with_email("ursula_le_guin@gmail.com")
+            .await
+    }
+
+    // Returns the id of the new subscriber
+    pub async fn create_confirmed_subscriber_with_email(&self, email: &str) -> Uuid {
+        let confirmation_links = self.create_unconfirmed_subscriber_with_email(email).await;
+        reqwest::get(confirmation_links.html_link)
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+
+        sqlx::query!("SELECT id FROM subscriptions WHERE email = $1", email)
+            .fetch_one(&self.db_connection_pool)
+            .await
+            .unwrap()
+            .id
+    }
+
+    pub async fn create_confirmed_subscriber(&self) -> Uuid {
+        self.create_confirmed_subscriber_with_email("ursula_le_guin@gmail.com")
+            .await
+    }
+
+    pub async fn get_unsubscribe_page(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/subscriptions/unsubscribe",
+                &self.server_address
+            ))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    // Mirrors the request sent by mail clients implementing RFC 8058
+    pub async fn post_one_click_unsubscribe(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/unsubscribe",
+                &self.server_address
+            ))
+            .query(&[("token", token)])
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("List-Unsubscribe=One-Click")
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_data_export_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/data-export",
+                &self.server_address
+            ))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_preferences_link_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/preferences/link",
+                &self.server_address
+            ))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_preferences_page(&self, token: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/subscriptions/preferences",
+                &self.server_address
+            ))
+            .query(&[("token", token)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_preferences_html(&self, token: &str) -> String {
+        self.get_preferences_page(token).await.text().await.unwrap()
+    }
+
+    pub async fn post_preferences(&self, token: &str, form: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/subscriptions/preferences",
+                &self.server_address
+            ))
+            .query(&[("token", token)])
+            .form(form)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub async fn send_newsletter(&self, body: serde_json::Value) -> reqwest::Response {
         reqwest::Client::new()
-            .post(&format!("{}/newsletters", &self.server_address))
+            .post(format!("{}/newsletters", &self.server_address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
             .json(&body)
             .send()
             .await
             .expect("Failed to execute request")
     }
 
+    pub async fn send_newsletter_with_idempotency_key(
+        &self,
+        body: &serde_json::Value,
+        idempotency_key: &str,
+    ) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/newsletters", &self.server_address))
+            .basic_auth(&self.test_user.username, Some(&self.test_user.password))
+            .header("Idempotency-Key", idempotency_key)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_postmark_webhook(&self, body: &serde_json::Value) -> reqwest::Response {
+        reqwest::Client::new()
+            .post(format!("{}/webhooks/postmark", &self.server_address))
+            .basic_auth(&self.webhook_username, Some(&self.webhook_password))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_archive_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/archive", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_archived_issue(&self, slug: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/archive/{}", &self.server_address, slug))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_login<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", &self.server_address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn log_in_test_user(&self) -> reqwest::Response {
+        self.post_login(&serde_json::json!({
+            "username": &self.test_user.username,
+            "password": &self.test_user.password,
+        }))
+        .await
+    }
+
+    pub async fn get_login_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/login", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn get_admin_dashboard(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/dashboard", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_dashboard_html(&self) -> String {
+        self.get_admin_dashboard().await.text().await.unwrap()
+    }
+
+    pub async fn get_admin_subscribers<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.server_address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_subscriber(&self, subscriber_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.server_address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn delete_admin_subscriber(&self, subscriber_id: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!(
+                "{}/admin/subscribers/{}",
+                &self.server_address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_subscribers_import<Query>(
+        &self,
+        query: &Query,
+        csv: &str,
+    ) -> reqwest::Response
+    where
+        Query: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.server_address))
+            .query(query)
+            .header("Content-Type", "text/csv")
+            .body(csv.to_string())
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_subscribers_export(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/export.csv",
+                &self.server_address
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_erase_subscriber(&self, subscriber_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/erase",
+                &self.server_address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_lists(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_admin_list(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.server_address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_suppressions<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .get(format!("{}/admin/suppressions", &self.server_address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_admin_suppression(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/suppressions", &self.server_address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn delete_admin_suppression(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .delete(format!(
+                "{}/admin/suppressions/{}",
+                &self.server_address, email
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_admin_issues<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .get(format!("{}/admin/issues", &self.server_address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn patch_admin_issue(
+        &self,
+        issue_id: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .patch(format!(
+                "{}/admin/issues/{}",
+                &self.server_address, issue_id
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_cancel_issue(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}/cancel",
+                &self.server_address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_admin_issue(&self, body: &serde_json::Value) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/issues", &self.server_address))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_preview(&self, issue_id: &str, format: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/issues/{}/preview",
+                &self.server_address, issue_id
+            ))
+            .query(&[("format", format)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_test_issue(
+        &self,
+        issue_id: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}/test",
+                &self.server_address, issue_id
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_publish_issue(
+        &self,
+        issue_id: &str,
+        body: &serde_json::Value,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/issues/{}/publish",
+                &self.server_address, issue_id
+            ))
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_opens(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/issues/{}/opens",
+                &self.server_address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_issue_clicks(&self, issue_id: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/issues/{}/clicks",
+                &self.server_address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_logout(&self) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/logout", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_password(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/password", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_password_html(&self) -> String {
+        self.get_change_password().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/password", &self.server_address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_email(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/email", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_change_email_html(&self) -> String {
+        self.get_change_email().await.text().await.unwrap()
+    }
+
+    pub async fn post_change_email<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/email", &self.server_address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn post_password_reset_request(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/password-reset", &self.server_address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    pub async fn get_password_reset_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/password-reset", &self.server_address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_password_reset_confirm<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/password-reset/confirm", &self.server_address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
     pub fn get_email_confirmation_links(&self, request: &wiremock::Request) -> ConfirmationLinks {
         let request_body: serde_json::Value =
             serde_json::from_slice(&request.body).expect("Failed to get request body");
@@ -92,6 +736,11 @@ impl TestingApp {
 }
 
 pub async fn spawn_app() -> TestingApp {
+    spawn_app_with(|_| {}).await
+}
+
+// Lets a test adjust the configuration before the application is built
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestingApp {
     Lazy::force(&TRACING);
 
     let mock_email_server = MockServer::start().await;
@@ -102,6 +751,8 @@ pub async fn spawn_app() -> TestingApp {
     configuration.database.name = Uuid::new_v4().to_string();
     configuration.application.port = 0;
     configuration.email_client.base_url = mock_email_server.uri();
+    configuration.email_client.webhook_password = SecretString::new(Uuid::new_v4().to_string());
+    configure(&mut configuration);
 
     create_testing_database(&configuration.database).await;
 
@@ -112,14 +763,42 @@ pub async fn spawn_app() -> TestingApp {
     let server_port = application.get_port();
     let server_address = format!("http://127.0.0.1:{}", server_port);
 
-    let _ = tokio::spawn(application.run_server());
+    tokio::spawn(application.run_server());
+
+    let api_client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap();
 
-    TestingApp {
+    let testing_app = TestingApp {
         db_connection_pool: get_db_connection_pool(&configuration.database),
         server_port,
         server_address,
         mock_email_server,
-    }
+        test_user: TestUser::generate(),
+        api_client,
+        email_client: configuration.email_client.client(),
+        hmac_secret: configuration.application.hmac_secret.clone(),
+        subscription_token_ttl: configuration.application.get_subscription_token_ttl(),
+        webhook_username: configuration.email_client.webhook_username.clone(),
+        webhook_password: configuration
+            .email_client
+            .webhook_password
+            .expose_secret()
+            .clone(),
+    };
+    testing_app
+        .test_user
+        .store(&testing_app.db_connection_pool)
+        .await;
+
+    testing_app
+}
+
+pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
+    assert_eq!(response.status().as_u16(), 303);
+    assert_eq!(response.headers().get("Location").unwrap(), location);
 }
 
 async fn create_testing_database(db_configuration: &DatabaseSettings) -> PgPool {
@@ -0,0 +1,306 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+async fn create_list(app: &TestingApp, slug: &str) {
+    let response = app
+        .post_admin_list(&serde_json::json!({ "slug": slug, "name": "Release notes" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+// Subscribes the address to the list and follows the link of the confirmation email
+async fn join_list(app: &TestingApp, email: &str, list: &str) {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+
+    let body = format!(
+        "name=le%20guin&email={}&list={}",
+        email.replace('@', "%40"),
+        list
+    );
+    app.send_subscription_request(body)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_email_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn get_membership_status(app: &TestingApp, email: &str, list: &str) -> Option<String> {
+    sqlx::query!(
+        r#"
+        SELECT m.status
+        FROM list_memberships m
+        JOIN subscriptions s ON s.id = m.subscriber_id
+        JOIN lists l ON l.id = m.list_id
+        WHERE s.email = $1 AND l.slug = $2
+        "#,
+        email,
+        list
+    )
+    .fetch_optional(&app.db_connection_pool)
+    .await
+    .unwrap()
+    .map(|r| r.status)
+}
+
+#[actix_web::test]
+async fn test_lists_require_an_authenticated_admin() {
+    let app = spawn_app().await;
+
+    let response = app.get_admin_lists().await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_admin_list(&serde_json::json!({ "slug": "releases", "name": "Releases" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_created_lists_are_listed_next_to_the_default_one() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    create_list(&app, "releases").await;
+
+    let lists: serde_json::Value = app.get_admin_lists().await.json().await.unwrap();
+    let slugs: Vec<_> = lists
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|l| l["slug"].as_str().unwrap())
+        .collect();
+    assert_eq!(slugs, vec!["default", "releases"]);
+}
+
+#[actix_web::test]
+async fn test_creating_a_list_with_an_existing_slug_returns_409() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    create_list(&app, "releases").await;
+
+    let response = app
+        .post_admin_list(&serde_json::json!({ "slug": "releases", "name": "Other" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn test_creating_a_list_with_an_invalid_slug_returns_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_admin_list(&serde_json::json!({ "slug": "Release Notes", "name": "Releases" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_subscribing_to_an_unknown_list_returns_400() {
+    let app = spawn_app().await;
+
+    let response = app
+        .send_subscription_request("name=le%20guin&email=ursula%40example.com&list=nope".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_subscribing_without_a_list_joins_the_default_list() {
+    let app = spawn_app().await;
+
+    join_list(&app, "ursula@example.com", "default").await;
+
+    assert_eq!(
+        get_membership_status(&app, "ursula@example.com", "default")
+            .await
+            .as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[actix_web::test]
+async fn test_confirmed_subscribers_confirm_again_to_join_another_list() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    create_list(&app, "releases").await;
+    join_list(&app, "ursula@example.com", "default").await;
+
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+    app.send_subscription_request("name=le%20guin&email=ursula%40example.com&list=releases".into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(
+        get_membership_status(&app, "ursula@example.com", "releases")
+            .await
+            .as_deref(),
+        Some("pending_confirmation")
+    );
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_email_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    assert_eq!(
+        get_membership_status(&app, "ursula@example.com", "releases")
+            .await
+            .as_deref(),
+        Some("confirmed")
+    );
+}
+
+#[actix_web::test]
+async fn test_newsletters_are_only_delivered_to_members_of_the_target_lists() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    create_list(&app, "releases").await;
+    join_list(&app, "ursula@example.com", "default").await;
+    join_list(&app, "octavia@example.com", "releases").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            },
+            "lists": ["releases"]
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email_body["To"], "octavia@example.com");
+}
+
+#[actix_web::test]
+async fn test_members_of_several_target_lists_receive_the_issue_once() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    create_list(&app, "releases").await;
+    join_list(&app, "ursula@example.com", "default").await;
+    join_list(&app, "ursula@example.com", "releases").await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            },
+            "lists": ["default", "releases"]
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_publishing_to_an_unknown_list_returns_400() {
+    let app = spawn_app().await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            },
+            "lists": ["nope"]
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_unsubscribing_leaves_every_list() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    create_list(&app, "releases").await;
+    join_list(&app, "ursula@example.com", "default").await;
+    join_list(&app, "ursula@example.com", "releases").await;
+
+    let unsubscribe_token = sqlx::query!(
+        "SELECT unsubscribe_token FROM subscriptions WHERE email = 'ursula@example.com'"
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap()
+    .unsubscribe_token;
+    app.post_one_click_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    for list in ["default", "releases"] {
+        assert_eq!(
+            get_membership_status(&app, "ursula@example.com", list)
+                .await
+                .as_deref(),
+            Some("unsubscribed")
+        );
+    }
+}
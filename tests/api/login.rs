@@ -0,0 +1,50 @@
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[actix_web::test]
+async fn test_an_error_message_is_shown_on_failed_login() {
+    let app = spawn_app().await;
+
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+    let response = app.post_login(&login_body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+
+    // The error message is only shown once
+    let html_page = app.get_login_html().await;
+    assert!(!html_page.contains("Authentication failed"));
+}
+
+#[actix_web::test]
+async fn test_redirect_to_admin_dashboard_after_login_success() {
+    let app = spawn_app().await;
+
+    let response = app.log_in_test_user().await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[actix_web::test]
+async fn test_session_is_rotated_on_login() {
+    let app = spawn_app().await;
+
+    // A failed login leaves an anonymous session behind, holding the error message
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "random-username",
+            "password": "random-password"
+        }))
+        .await;
+    let anonymous_session_cookie = response.cookies().next().unwrap().value().to_owned();
+
+    let response = app.log_in_test_user().await;
+    let authenticated_session_cookie = response.cookies().next().unwrap().value().to_owned();
+
+    assert_ne!(anonymous_session_cookie, authenticated_session_cookie);
+}
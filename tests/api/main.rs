@@ -1,5 +1,25 @@
+mod admin_dashboard;
+mod admin_subscribers;
+mod admin_subscribers_csv;
+mod archive;
+mod change_email;
+mod change_password;
+mod click_tracking;
+mod digests;
+mod draft_issues;
+mod gdpr;
 mod health_check;
 mod helpers;
+mod lists;
+mod login;
 mod newsletter;
+mod open_tracking;
+mod password_reset;
+mod postmark_webhook;
+mod preferences;
+mod rate_limiting;
+mod scheduled_issues;
 mod subscriptions;
 mod subscriptions_confirm;
+mod suppressions;
+mod unsubscribe;
@@ -1,74 +1,147 @@
 use rstest::*;
+use uuid::Uuid;
 use wiremock::{
     matchers::{any, method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::{spawn_app, ConfirmationLinks, TestingApp};
+use crate::helpers::spawn_app;
 
-async fn create_unconfirmed_subscriber(app: &TestingApp) -> ConfirmationLinks {
-    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+#[actix_web::test]
+async fn test_newsletters_are_not_delivered_to_unconfirmed_subscribers() {
+    let app = spawn_app().await;
+    app.create_unconfirmed_subscriber().await;
 
-    let _mock_guard = Mock::given(path("/email"))
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+
+    let response = app.send_newsletter(newsletter_body).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_newsletters_are_delivered_to_confirmed_subscribers() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
         .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .named("Create unconfirmed subscriber")
         .expect(1)
-        .mount_as_scoped(&app.mock_email_server)
+        .mount(&app.mock_email_server)
         .await;
 
-    app.send_subscription_request(body.into())
-        .await
-        .error_for_status()
-        .unwrap();
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+
+    let response = app.send_newsletter(newsletter_body).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_markdown_content_is_rendered_to_html_and_plain_text() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "markdown": "Read **the** [post](https://example.com/post)\n\n<script>alert(1)</script>"
+        }
+    });
+
+    let response = app.send_newsletter(newsletter_body).await;
 
-    let mock_email_requests = &app
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
         .mock_email_server
         .received_requests()
         .await
         .unwrap()
         .pop()
         .unwrap();
-    app.get_email_confirmation_links(mock_email_requests)
-}
-
-async fn create_confirmed_subscriber(app: &TestingApp) {
-    let confirmation_links = create_unconfirmed_subscriber(app).await;
-    reqwest::get(confirmation_links.html_link)
-        .await
-        .unwrap()
-        .error_for_status()
-        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let html_body = email["HtmlBody"].as_str().unwrap();
+    assert!(html_body.starts_with("<!DOCTYPE html>"));
+    assert!(html_body.contains("<strong>the</strong>"));
+    assert!(!html_body.contains("<script"));
+    // The footnoted link goes through the click tracker
+    let text_body = email["TextBody"].as_str().unwrap();
+    assert!(text_body.starts_with("Read the post [1]\n\n[1] http://"));
+    assert!(text_body.contains("/t/c/"));
 }
 
 #[actix_web::test]
-async fn test_newsletters_are_not_delivered_to_unconfirmed_subscribers() {
+async fn test_explicit_content_overrides_the_rendered_markdown() {
     let app = spawn_app().await;
-    create_unconfirmed_subscriber(&app).await;
+    app.create_confirmed_subscriber().await;
 
-    Mock::given(any())
+    Mock::given(path("/email"))
+        .and(method("POST"))
         .respond_with(ResponseTemplate::new(200))
-        .expect(0)
+        .expect(1)
         .mount(&app.mock_email_server)
         .await;
 
     let newsletter_body = serde_json::json!({
         "title": "Newsletter title",
         "content": {
-            "plain_text": "Newsletter body",
-            "html": "<p>Newsletter body</p>"
+            "markdown": "Newsletter *body*",
+            "plain_text": "Hand written body"
         }
     });
 
     let response = app.send_newsletter(newsletter_body).await;
 
-    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(email["HtmlBody"]
+        .as_str()
+        .unwrap()
+        .contains("<em>body</em>"));
+    assert_eq!(email["TextBody"], "Hand written body");
 }
 
 #[actix_web::test]
-async fn test_newsletters_are_delivered_to_confirmed_subscribers() {
+async fn test_placeholders_are_personalized_for_every_subscriber() {
     let app = spawn_app().await;
-    create_confirmed_subscriber(&app).await;
+    app.create_confirmed_subscriber().await;
 
     Mock::given(path("/email"))
         .and(method("POST"))
@@ -78,16 +151,64 @@ async fn test_newsletters_are_delivered_to_confirmed_subscribers() {
         .await;
 
     let newsletter_body = serde_json::json!({
-        "title": "Newsletter title",
+        "title": "News for {{ name }}",
         "content": {
-            "plain_text": "Newsletter body",
-            "html": "<p>Newsletter body</p>"
+            "markdown": "Hi {{ name }}, [leave]({{ unsubscribe_url }}) or \
+                [read online]({{archive_url}})"
         }
     });
 
     let response = app.send_newsletter(newsletter_body).await;
 
-    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(email["Subject"], "News for le guin");
+    let html_body = email["HtmlBody"].as_str().unwrap();
+    assert!(html_body.contains("Hi le guin"));
+    assert!(html_body.contains("/subscriptions/unsubscribe?token="));
+    // The archive link goes through the click tracker, the unsubscribe link does not
+    assert!(html_body.contains("/t/c/"));
+    let text_body = email["TextBody"].as_str().unwrap();
+    assert!(text_body.starts_with("Hi le guin, leave [1] or read online [2]"));
+    assert!(!text_body.contains("{{"));
+}
+
+#[rstest]
+#[case(serde_json::json!({
+    "title": "News for {{ email }}",
+    "content": {"plain_text": "text", "html": "html"}
+}), "title")]
+#[case(serde_json::json!({
+    "title": "title",
+    "content": {"plain_text": "Hi {{ nmae }}", "html": "html"}
+}), "plain text")]
+#[case(serde_json::json!({
+    "title": "title",
+    "content": {"markdown": "Hi {{ name | upper }}"}
+}), "markdown")]
+#[actix_web::test]
+async fn test_unknown_placeholders_are_rejected(
+    #[case] email_body: serde_json::Value,
+    #[case] field: String,
+) {
+    let app = spawn_app().await;
+
+    let response = app.send_newsletter(email_body).await;
+
+    assert_eq!(
+        response.status().as_u16(),
+        400,
+        "The API did not fail with 400 error for an unknown placeholder in the {}",
+        field
+    );
 }
 
 #[rstest]
@@ -111,3 +232,220 @@ async fn test_newsletter_endpoint_returns_400_for_invalid_email_data(
         error
     );
 }
+
+#[actix_web::test]
+async fn test_requests_missing_authorization_are_rejected() {
+    let app = spawn_app().await;
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.server_address))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[actix_web::test]
+async fn test_non_existing_user_is_rejected() {
+    let app = spawn_app().await;
+
+    let username = Uuid::new_v4().to_string();
+    let password = Uuid::new_v4().to_string();
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.server_address))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[actix_web::test]
+async fn test_invalid_password_is_rejected() {
+    let app = spawn_app().await;
+
+    let username = &app.test_user.username;
+    let password = Uuid::new_v4().to_string();
+    assert_ne!(app.test_user.password, password);
+
+    let response = reqwest::Client::new()
+        .post(format!("{}/newsletters", &app.server_address))
+        .basic_auth(username, Some(password))
+        .json(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request");
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        r#"Basic realm="publish""#,
+        response.headers()["WWW-Authenticate"]
+    );
+}
+
+#[actix_web::test]
+async fn test_newsletter_publishing_is_idempotent() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let response = app
+        .send_newsletter_with_idempotency_key(&newsletter_body, &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    // Retrying returns the stored response without publishing the issue again
+    let response = app
+        .send_newsletter_with_idempotency_key(&newsletter_body, &idempotency_key)
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_concurrent_newsletter_publishing_is_handled_gracefully() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+    let idempotency_key = Uuid::new_v4().to_string();
+
+    let (first_response, second_response) = tokio::join!(
+        app.send_newsletter_with_idempotency_key(&newsletter_body, &idempotency_key),
+        app.send_newsletter_with_idempotency_key(&newsletter_body, &idempotency_key)
+    );
+
+    assert_eq!(first_response.status(), second_response.status());
+    assert_eq!(
+        first_response.text().await.unwrap(),
+        second_response.text().await.unwrap()
+    );
+
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_invalid_idempotency_key_is_rejected() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+
+    let response = app
+        .send_newsletter_with_idempotency_key(&newsletter_body, &"a".repeat(50))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_a_failed_delivery_does_not_stop_the_others() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber_with_email("first@example.com")
+        .await;
+    app.create_confirmed_subscriber_with_email("second@example.com")
+        .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(400))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let newsletter_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    });
+    let response = app.send_newsletter(newsletter_body).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    app.dispatch_all_pending_emails().await;
+
+    let pending_tasks = sqlx::query!("SELECT COUNT(*) as \"count!\" FROM issue_delivery_queue")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(pending_tasks.count, 0);
+}
@@ -0,0 +1,144 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+// Publishes an issue, delivers it and returns its id along with the HTML body that was sent
+async fn publish_and_deliver(app: &TestingApp, track_opens: bool) -> (String, String) {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<html><body><p>Newsletter body</p></body></html>"
+            },
+            "track_opens": track_opens
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let email: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let issue_id = sqlx::query!("SELECT newsletter_issue_id FROM newsletter_issues")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .newsletter_issue_id;
+
+    (
+        issue_id.to_string(),
+        email["HtmlBody"].as_str().unwrap().to_owned(),
+    )
+}
+
+fn get_pixel_link(html_body: &str) -> String {
+    let start = html_body.find("http://").unwrap();
+    let end = start + html_body[start..].find(".gif").unwrap() + ".gif".len();
+    html_body[start..end].to_owned()
+}
+
+#[actix_web::test]
+async fn test_opens_are_recorded_and_summarised() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let (issue_id, html_body) = publish_and_deliver(&app, true).await;
+    let pixel_link = get_pixel_link(&html_body);
+    assert!(pixel_link.contains("/t/o/"));
+    assert!(html_body.ends_with("</body></html>"));
+
+    let client = reqwest::Client::new();
+    for _ in 0..2 {
+        let response = client
+            .get(&pixel_link)
+            .header("User-Agent", "Test Mail Client")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(response.headers()["content-type"], "image/gif");
+        assert!(response.headers()["cache-control"]
+            .to_str()
+            .unwrap()
+            .contains("no-store"));
+    }
+
+    let open = sqlx::query!("SELECT open_count, user_agent FROM newsletter_opens")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(open.open_count, 2);
+    assert_eq!(open.user_agent.as_deref(), Some("Test Mail Client"));
+
+    app.log_in_test_user().await;
+    let opens: serde_json::Value = app.get_issue_opens(&issue_id).await.json().await.unwrap();
+    assert_eq!(opens["track_opens"], true);
+    assert_eq!(opens["delivered"], 1);
+    assert_eq!(opens["unique_opens"], 1);
+    assert_eq!(opens["total_opens"], 2);
+    assert_eq!(opens["open_rate"], 1.0);
+}
+
+#[actix_web::test]
+async fn test_issues_are_not_tracked_unless_asked_to() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let (_, html_body) = publish_and_deliver(&app, false).await;
+
+    assert!(!html_body.contains("/t/o/"));
+}
+
+#[actix_web::test]
+async fn test_forged_tokens_get_the_pixel_but_are_not_recorded() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let (_, html_body) = publish_and_deliver(&app, true).await;
+    let pixel_link = get_pixel_link(&html_body);
+    let forged_link = pixel_link.replacen("/t/o/", &format!("/t/o/{}", Uuid::new_v4()), 1);
+
+    let response = reqwest::get(&forged_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(response.headers()["content-type"], "image/gif");
+    let opens = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM newsletter_opens")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(opens.count, 0);
+}
+
+#[actix_web::test]
+async fn test_open_statistics_require_an_authenticated_admin() {
+    let app = spawn_app().await;
+
+    let response = app.get_issue_opens(&Uuid::new_v4().to_string()).await;
+
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_open_statistics_of_an_unknown_issue_return_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app.get_issue_opens(&Uuid::new_v4().to_string()).await;
+
+    assert_eq!(response.status().as_u16(), 404);
+}
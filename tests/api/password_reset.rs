@@ -0,0 +1,176 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+const RESET_REQUESTED_MESSAGE: &str =
+    "If an account with that email exists, a password reset link has been sent to it.";
+
+async fn request_password_reset_link(app: &TestingApp) -> reqwest::Url {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_password_reset_request(&app.test_user.email).await;
+    assert_is_redirect_to(&response, "/password-reset");
+
+    let email_request = &app.mock_email_server.received_requests().await.unwrap()[0];
+    app.get_email_confirmation_links(email_request).html_link
+}
+
+fn get_token(password_reset_link: &reqwest::Url) -> String {
+    password_reset_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+        .unwrap()
+}
+
+#[actix_web::test]
+async fn test_reset_request_for_unknown_email_does_not_send_email() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app
+        .post_password_reset_request("someone.else@example.com")
+        .await;
+    assert_is_redirect_to(&response, "/password-reset");
+
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+}
+
+#[actix_web::test]
+async fn test_reset_request_for_known_email_sends_a_link() {
+    let app = spawn_app().await;
+
+    let password_reset_link = request_password_reset_link(&app).await;
+    assert_eq!(password_reset_link.path(), "/password-reset/confirm");
+
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+
+    // Only the hash of the token is persisted
+    let stored_token = sqlx::query!("SELECT token_hash FROM password_reset_tokens")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_ne!(stored_token.token_hash, get_token(&password_reset_link));
+}
+
+#[actix_web::test]
+async fn test_password_reset_link_sets_a_new_password_only_once() {
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let password_reset_link = request_password_reset_link(&app).await;
+    let html_page = reqwest::get(password_reset_link.clone())
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(r#"name="new_password""#));
+
+    let reset_body = serde_json::json!({
+        "token": get_token(&password_reset_link),
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+    let response = app.post_password_reset_confirm(&reset_body).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // The link cannot be used a second time
+    let html_page = reqwest::get(password_reset_link)
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("This password reset link is invalid or has expired."));
+
+    let response = app.post_password_reset_confirm(&reset_body).await;
+    assert_is_redirect_to(&response, "/password-reset");
+}
+
+#[actix_web::test]
+async fn test_expired_password_reset_link_is_rejected() {
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let password_reset_link = request_password_reset_link(&app).await;
+    sqlx::query!("UPDATE password_reset_tokens SET expires_at = now() - interval '1 minute'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": get_token(&password_reset_link),
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/password-reset");
+
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains("This password reset link is invalid or has expired."));
+}
+
+#[actix_web::test]
+async fn test_a_failed_email_gives_the_same_answer_as_an_unknown_address() {
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_password_reset_request(&app.test_user.email).await;
+    assert_is_redirect_to(&response, "/password-reset");
+
+    let html_page = app.get_password_reset_html().await;
+    assert!(html_page.contains(RESET_REQUESTED_MESSAGE));
+}
+
+#[actix_web::test]
+async fn test_the_token_is_encoded_when_redirecting_back_to_the_form() {
+    let app = spawn_app().await;
+    let new_password = Uuid::new_v4().to_string();
+
+    let response = app
+        .post_password_reset_confirm(&serde_json::json!({
+            "token": "a&next=https://example.com/\r\nSet-Cookie: x",
+            "new_password": &new_password,
+            "new_password_check": "something else",
+        }))
+        .await;
+
+    assert_is_redirect_to(
+        &response,
+        concat!(
+            "/password-reset/confirm?token=",
+            "a%26next%3Dhttps%3A%2F%2Fexample.com%2F%0D%0ASet-Cookie%3A+x"
+        ),
+    );
+}
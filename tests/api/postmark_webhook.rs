@@ -0,0 +1,288 @@
+use wiremock::{
+    matchers::{any, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestingApp};
+
+const EMAIL: &str = "ursula_le_guin@gmail.com";
+
+async fn get_subscription_status(app: &TestingApp) -> String {
+    sqlx::query!("SELECT status FROM subscriptions WHERE email = $1", EMAIL)
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .status
+}
+
+fn hard_bounce() -> serde_json::Value {
+    serde_json::json!({
+        "RecordType": "Bounce",
+        "ID": 4323372036854775807u64,
+        "Type": "HardBounce",
+        "TypeCode": 1,
+        "Name": "Hard bounce",
+        "Email": EMAIL,
+        "Inactive": true,
+        "CanActivate": true,
+        "Subject": "Confirm your subscription",
+        "BouncedAt": "2024-12-28T10:00:00Z"
+    })
+}
+
+#[tokio::test]
+async fn test_requests_without_valid_credentials_are_rejected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let test_cases = [
+        (None, "no credentials"),
+        (
+            Some((app.webhook_username.clone(), "wrong")),
+            "wrong password",
+        ),
+        (
+            Some(("wrong".to_string(), app.webhook_password.as_str())),
+            "wrong username",
+        ),
+    ];
+    for (credentials, description) in test_cases {
+        let mut request = reqwest::Client::new()
+            .post(format!("{}/webhooks/postmark", &app.server_address))
+            .json(&hard_bounce());
+        if let Some((username, password)) = credentials {
+            request = request.basic_auth(username, Some(password));
+        }
+        let response = request.send().await.unwrap();
+
+        assert_eq!(
+            response.status().as_u16(),
+            401,
+            "The webhook did not fail with 401 with {}",
+            description
+        );
+        assert_eq!(
+            response.headers()["WWW-Authenticate"],
+            r#"Basic realm="webhooks""#
+        );
+    }
+    assert_eq!(get_subscription_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn test_malformed_payloads_are_rejected() {
+    let app = spawn_app().await;
+
+    let test_cases = [
+        (serde_json::json!({"Email": EMAIL}), "missing record type"),
+        (
+            serde_json::json!({"RecordType": "SpamComplaint"}),
+            "missing email",
+        ),
+    ];
+    for (body, description) in test_cases {
+        let response = app.post_postmark_webhook(&body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The webhook did not fail with 400 with {}",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_a_hard_bounce_marks_the_subscription_as_bounced() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let response = app.post_postmark_webhook(&hard_bounce()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(get_subscription_status(&app).await, "bounced");
+}
+
+#[tokio::test]
+async fn test_a_soft_bounce_is_ignored() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "Bounce",
+            "Type": "SoftBounce",
+            "TypeCode": 4096,
+            "Email": EMAIL,
+            "Inactive": false
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(get_subscription_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn test_a_spam_complaint_marks_the_subscription_as_complained() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let response = app
+        .post_postmark_webhook(&serde_json::json!({
+            "RecordType": "SpamComplaint",
+            "Type": "SpamComplaint",
+            "Email": EMAIL,
+            "Inactive": true
+        }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(get_subscription_status(&app).await, "complained");
+}
+
+#[tokio::test]
+async fn test_subscription_changes_are_applied_according_to_their_reason() {
+    let test_cases = [
+        ("HardBounce", "bounced"),
+        ("SpamComplaint", "complained"),
+        ("ManualSuppression", "unsubscribed"),
+    ];
+    for (reason, expected_status) in test_cases {
+        let app = spawn_app().await;
+        app.create_confirmed_subscriber().await;
+
+        let response = app
+            .post_postmark_webhook(&serde_json::json!({
+                "RecordType": "SubscriptionChange",
+                "Recipient": EMAIL,
+                "SuppressSending": true,
+                "SuppressionReason": reason,
+                "ChangedAt": "2024-12-28T10:00:00Z"
+            }))
+            .await;
+
+        assert_eq!(response.status().as_u16(), 200);
+        assert_eq!(
+            get_subscription_status(&app).await,
+            expected_status,
+            "Unexpected status after a {} suppression",
+            reason
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_reactivations_and_other_record_types_are_acknowledged_and_ignored() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    let test_cases = [
+        serde_json::json!({
+            "RecordType": "SubscriptionChange",
+            "Recipient": EMAIL,
+            "SuppressSending": false,
+            "SuppressionReason": null
+        }),
+        serde_json::json!({"RecordType": "Delivery", "Recipient": EMAIL}),
+        serde_json::json!({"RecordType": "Open", "Recipient": EMAIL}),
+    ];
+    for body in test_cases {
+        let response = app.post_postmark_webhook(&body).await;
+
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    assert_eq!(get_subscription_status(&app).await, "confirmed");
+}
+
+#[tokio::test]
+async fn test_events_for_unknown_addresses_are_acknowledged() {
+    let app = spawn_app().await;
+
+    let response = app.post_postmark_webhook(&hard_bounce()).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn test_a_bounce_does_not_replace_a_complaint() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.post_postmark_webhook(&serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": EMAIL
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    app.post_postmark_webhook(&hard_bounce())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    assert_eq!(get_subscription_status(&app).await, "complained");
+}
+
+#[tokio::test]
+async fn test_bounced_subscribers_do_not_receive_issues() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.post_postmark_webhook(&hard_bounce())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn test_confirmation_links_stop_working_once_the_address_bounced() {
+    let app = spawn_app().await;
+    let confirmation_links = app.create_unconfirmed_subscriber().await;
+    app.post_postmark_webhook(&hard_bounce())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = reqwest::get(confirmation_links.html_link).await.unwrap();
+
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(get_subscription_status(&app).await, "bounced");
+}
+
+#[tokio::test]
+async fn test_subscribing_again_with_a_bounced_address_sends_no_email() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.post_postmark_webhook(&hard_bounce())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(path("/email"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .send_subscription_request("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    assert_eq!(get_subscription_status(&app).await, "bounced");
+}
@@ -0,0 +1,281 @@
+use chrono::Utc;
+use rust_zero2prod::{configuration, signing};
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, spawn_app_with, TestingApp};
+
+const SUBSCRIBER_EMAIL: &str = "ursula_le_guin@gmail.com";
+
+// Asks for a preferences link and returns the token it carries
+async fn get_preferences_token(app: &TestingApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.mock_email_server)
+        .await;
+    let response = app.post_preferences_link_request(SUBSCRIBER_EMAIL).await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let email_request = &app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_email_confirmation_links(email_request);
+    assert_eq!(links.html_link.path(), "/subscriptions/preferences");
+    links
+        .html_link
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[actix_web::test]
+async fn test_preferences_link_is_not_sent_to_unknown_addresses() {
+    let app = spawn_app().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_preferences_link_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_web::test]
+async fn test_preferences_page_shows_the_current_preferences() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    let html_page = app.get_preferences_html(&token).await;
+
+    assert!(html_page.contains(r#"value="le guin""#));
+    assert!(html_page.contains(r#"value="default" checked"#));
+    assert!(html_page.contains(r#"value="immediate" selected"#));
+}
+
+#[actix_web::test]
+async fn test_tampered_preferences_link_is_rejected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    let (payload, _) = token.rsplit_once('.').unwrap();
+    let tampered_token = format!("{}.{}", payload, "0".repeat(64));
+    let response = app.get_preferences_page(&tampered_token).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app
+        .post_preferences(
+            &tampered_token,
+            &[("name", "hacker"), ("digest_frequency", "daily")],
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_expired_preferences_link_is_rejected() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    // Correctly signed, but for an expiry in the past
+    let (subscriber_id, _) = token.split_once('.').unwrap();
+    let payload = format!("{}.{}", subscriber_id, Utc::now().timestamp() - 1);
+    let hmac_secret = configuration::get_configuration()
+        .unwrap()
+        .application
+        .hmac_secret;
+    let signature = signing::sign(&hmac_secret, &format!("preferences.{}", payload));
+    let response = app
+        .get_preferences_page(&format!("{}.{}", payload, signature))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_subscribers_can_update_their_preferences() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    let response = app
+        .post_preferences(
+            &token,
+            &[
+                ("name", "Ursula K. Le Guin"),
+                ("digest_frequency", "weekly"),
+            ],
+        )
+        .await;
+    assert_is_redirect_to(
+        &response,
+        &format!("/subscriptions/preferences?token={}", token),
+    );
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains("Your preferences have been saved."));
+
+    let saved = sqlx::query!(
+        r#"
+        SELECT s.name, s.digest_frequency, m.status AS membership_status
+        FROM subscriptions s
+        JOIN list_memberships m ON m.subscriber_id = s.id
+        "#
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap();
+    assert_eq!(saved.name, "Ursula K. Le Guin");
+    assert_eq!(saved.digest_frequency, "weekly");
+    assert_eq!(saved.membership_status, "unsubscribed");
+}
+
+#[actix_web::test]
+async fn test_invalid_preferences_are_not_saved() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    for (form, error_message) in [
+        (
+            [("name", ""), ("digest_frequency", "daily")],
+            "is not a valid subscriber name",
+        ),
+        (
+            [("name", "le guin"), ("digest_frequency", "hourly")],
+            "hourly is not a valid digest frequency",
+        ),
+    ] {
+        let response = app.post_preferences(&token, &form).await;
+        assert_eq!(response.status().as_u16(), 303);
+
+        let html_page = app.get_preferences_html(&token).await;
+        assert!(html_page.contains(error_message));
+    }
+
+    let saved = sqlx::query!("SELECT name, digest_frequency FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.name, "le guin");
+    assert_eq!(saved.digest_frequency, "immediate");
+}
+
+#[actix_web::test]
+async fn test_subscribers_can_join_another_list_from_their_preferences() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.post_admin_list(&serde_json::json!({ "slug": "releases", "name": "Releases" }))
+        .await
+        .error_for_status()
+        .unwrap();
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    app.post_preferences(
+        &token,
+        &[
+            ("name", "le guin"),
+            ("list", "default"),
+            ("list", "releases"),
+            ("digest_frequency", "immediate"),
+        ],
+    )
+    .await;
+
+    let html_page = app.get_preferences_html(&token).await;
+    assert!(html_page.contains(r#"value="default" checked"#));
+    assert!(html_page.contains(r#"value="releases" checked"#));
+}
+
+#[actix_web::test]
+async fn test_preferences_link_stops_working_after_unsubscribing() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let token = get_preferences_token(&app).await;
+
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    app.post_one_click_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = app.get_preferences_page(&token).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_a_failed_preferences_email_gives_the_same_answer_as_an_unknown_address() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_preferences_link_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[actix_web::test]
+async fn test_preferences_link_requests_are_rate_limited() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_email
+            .capacity = 2;
+    })
+    .await;
+    // Takes the first token of the address
+    app.create_confirmed_subscriber().await;
+    get_preferences_token(&app).await;
+
+    let response = app.post_preferences_link_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 429);
+}
+
+#[actix_web::test]
+async fn test_preferences_emails_count_towards_the_daily_email_cap() {
+    let app = spawn_app_with(|configuration| {
+        let rate_limit = &mut configuration.application.subscription_rate_limit;
+        rate_limit.per_email.capacity = 10;
+        rate_limit.max_confirmation_emails_per_day = 2;
+    })
+    .await;
+    // The confirmation email takes the first slot
+    app.create_confirmed_subscriber().await;
+    get_preferences_token(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app.post_preferences_link_request(SUBSCRIBER_EMAIL).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+}
@@ -0,0 +1,222 @@
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app_with, TestingApp};
+
+fn subscription_body(email: &str) -> String {
+    format!("name=le%20guin&email={}", email.replace('@', "%40"))
+}
+
+async fn send_subscription_request_from(
+    app: &TestingApp,
+    body: String,
+    forwarded_for: &str,
+) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}/subscriptions", &app.server_address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("X-Forwarded-For", forwarded_for)
+        .body(body)
+        .send()
+        .await
+        .expect("Failed to execute request")
+}
+
+fn assert_is_rate_limited(response: &reqwest::Response) {
+    assert_eq!(response.status().as_u16(), 429);
+    let retry_after: u64 = response.headers()["Retry-After"]
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0);
+}
+
+#[tokio::test]
+async fn test_repeated_requests_for_the_same_email_are_rate_limited() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_email
+            .capacity = 2;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_email_server)
+        .await;
+
+    for _ in 0..2 {
+        let response = app
+            .send_subscription_request(subscription_body("ursula_le_guin@gmail.com"))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    // Changing the case of the address does not get around the limit
+    let response = app
+        .send_subscription_request(subscription_body("Ursula_Le_Guin@gmail.com"))
+        .await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn test_repeated_requests_from_the_same_client_are_rate_limited() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_ip
+            .capacity = 3;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    for i in 0..3 {
+        let response = app
+            .send_subscription_request(subscription_body(&format!("reader{}@gmail.com", i)))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+    let response = app
+        .send_subscription_request(subscription_body("reader3@gmail.com"))
+        .await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn test_forwarded_addresses_are_ignored_unless_the_header_is_trusted() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_ip
+            .capacity = 1;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response =
+        send_subscription_request_from(&app, subscription_body("reader0@gmail.com"), "203.0.113.1")
+            .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response =
+        send_subscription_request_from(&app, subscription_body("reader1@gmail.com"), "203.0.113.2")
+            .await;
+
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn test_clients_behind_a_trusted_proxy_are_limited_separately() {
+    let app = spawn_app_with(|configuration| {
+        let rate_limit = &mut configuration.application.subscription_rate_limit;
+        rate_limit.per_ip.capacity = 1;
+        rate_limit.trusted_proxy_header = Some("X-Forwarded-For".into());
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response =
+        send_subscription_request_from(&app, subscription_body("reader0@gmail.com"), "203.0.113.1")
+            .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let response =
+        send_subscription_request_from(&app, subscription_body("reader1@gmail.com"), "203.0.113.2")
+            .await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    // Only the address added by the proxy counts, not the ones sent by the client
+    let response = send_subscription_request_from(
+        &app,
+        subscription_body("reader2@gmail.com"),
+        "198.51.100.1, 203.0.113.1",
+    )
+    .await;
+    assert_is_rate_limited(&response);
+}
+
+#[tokio::test]
+async fn test_confirmation_emails_are_capped_per_address_and_day() {
+    let app = spawn_app_with(|configuration| {
+        let rate_limit = &mut configuration.application.subscription_rate_limit;
+        rate_limit.per_email.capacity = 10;
+        rate_limit.max_confirmation_emails_per_day = 2;
+    })
+    .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_email_server)
+        .await;
+
+    for _ in 0..4 {
+        let response = app
+            .send_subscription_request(subscription_body("ursula_le_guin@gmail.com"))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+}
+
+#[tokio::test]
+async fn test_already_subscribed_notices_are_capped_per_address_and_day() {
+    let app = spawn_app_with(|configuration| {
+        let rate_limit = &mut configuration.application.subscription_rate_limit;
+        rate_limit.per_email.capacity = 10;
+        rate_limit.max_confirmation_emails_per_day = 2;
+    })
+    .await;
+    // Confirming the subscription already used one of the two emails
+    app.create_confirmed_subscriber().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    for _ in 0..3 {
+        let response = app
+            .send_subscription_request(subscription_body("ursula_le_guin@gmail.com"))
+            .await;
+        assert_eq!(response.status().as_u16(), 201);
+    }
+}
+
+#[tokio::test]
+async fn test_other_endpoints_are_not_rate_limited() {
+    let app = spawn_app_with(|configuration| {
+        configuration
+            .application
+            .subscription_rate_limit
+            .per_ip
+            .capacity = 1;
+    })
+    .await;
+
+    for _ in 0..3 {
+        let response = reqwest::get(format!("{}/health", &app.server_address))
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 200);
+    }
+}
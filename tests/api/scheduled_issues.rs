@@ -0,0 +1,278 @@
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+// Schedules an issue an hour from now and returns its id
+async fn schedule_issue(app: &TestingApp) -> String {
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Monday issue",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            },
+            "send_at": Utc::now() + Duration::hours(1)
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let issues: serde_json::Value = app
+        .get_admin_issues(&[("status", "scheduled")])
+        .await
+        .json()
+        .await
+        .unwrap();
+    issues[0]["newsletter_issue_id"]
+        .as_str()
+        .unwrap()
+        .to_owned()
+}
+
+async fn make_issue_due(app: &TestingApp, issue_id: &str) {
+    let response = app
+        .patch_admin_issue(
+            issue_id,
+            &serde_json::json!({ "send_at": Utc::now() - Duration::seconds(1) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[actix_web::test]
+async fn test_issues_api_requires_an_authenticated_admin() {
+    let app = spawn_app().await;
+    let issue_id = Uuid::new_v4().to_string();
+
+    let response = app.get_admin_issues(&()).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "title": "New title" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app.post_cancel_issue(&issue_id).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[actix_web::test]
+async fn test_scheduled_issues_are_not_delivered_before_their_send_time() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    schedule_issue(&app).await;
+    assert_eq!(app.publish_due_issues().await, 0);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_scheduled_issues_are_delivered_once_their_send_time_is_reached() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    let issue_id = schedule_issue(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    make_issue_due(&app, &issue_id).await;
+    assert_eq!(app.publish_due_issues().await, 1);
+    app.dispatch_all_pending_emails().await;
+
+    // Published issues are not picked up again
+    assert_eq!(app.publish_due_issues().await, 0);
+    let issues: serde_json::Value = app.get_admin_issues(&()).await.json().await.unwrap();
+    assert_eq!(issues[0]["status"], "published");
+    assert_eq!(issues[0]["slug"], "monday-issue");
+}
+
+#[actix_web::test]
+async fn test_an_issue_failing_to_publish_does_not_hold_back_the_others() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let broken_issue_id = schedule_issue(&app).await;
+    let response = app
+        .patch_admin_issue(
+            &broken_issue_id,
+            &serde_json::json!({ "title": "Broken issue" }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    make_issue_due(&app, &broken_issue_id).await;
+    schedule_issue(&app).await;
+    let issue_id = sqlx::query!(
+        "SELECT newsletter_issue_id FROM newsletter_issues WHERE title = 'Monday issue'"
+    )
+    .fetch_one(&app.db_connection_pool)
+    .await
+    .unwrap()
+    .newsletter_issue_id;
+    make_issue_due(&app, &issue_id.to_string()).await;
+    sqlx::raw_sql(
+        r#"
+        CREATE FUNCTION fail_to_publish() RETURNS trigger AS $$
+        BEGIN
+            RAISE EXCEPTION 'Cannot publish this issue';
+        END;
+        $$ LANGUAGE plpgsql;
+        CREATE TRIGGER fail_to_publish BEFORE UPDATE ON newsletter_issues
+        FOR EACH ROW WHEN (NEW.title = 'Broken issue' AND NEW.status = 'published')
+        EXECUTE FUNCTION fail_to_publish();
+        "#,
+    )
+    .execute(&app.db_connection_pool)
+    .await
+    .unwrap();
+
+    assert_eq!(app.publish_due_issues().await, 1);
+
+    let statuses = sqlx::query!("SELECT title, status FROM newsletter_issues ORDER BY title")
+        .fetch_all(&app.db_connection_pool)
+        .await
+        .unwrap();
+    let statuses: Vec<_> = statuses
+        .iter()
+        .map(|i| (i.title.as_str(), i.status.as_str()))
+        .collect();
+    assert_eq!(
+        statuses,
+        vec![("Broken issue", "scheduled"), ("Monday issue", "published")]
+    );
+}
+
+#[actix_web::test]
+async fn test_send_time_in_the_past_publishes_straight_away() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Late issue",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            },
+            "send_at": Utc::now() - Duration::hours(1)
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+
+    let issues: serde_json::Value = app.get_admin_issues(&()).await.json().await.unwrap();
+    assert_eq!(issues[0]["status"], "published");
+}
+
+#[actix_web::test]
+async fn test_scheduled_issues_can_be_edited() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = schedule_issue(&app).await;
+
+    let response = app
+        .patch_admin_issue(
+            &issue_id,
+            &serde_json::json!({
+                "title": "Tuesday issue",
+                "content": {
+                    "plain_text": "New body",
+                    "html": "<p>New body</p>"
+                }
+            }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(issue["title"], "Tuesday issue");
+    assert_eq!(issue["status"], "scheduled");
+    let saved = sqlx::query!("SELECT html_content, text_content FROM newsletter_issues")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(saved.html_content, "<p>New body</p>");
+    assert_eq!(saved.text_content, "New body");
+}
+
+#[actix_web::test]
+async fn test_editing_an_issue_with_an_unknown_list_returns_400() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = schedule_issue(&app).await;
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "lists": ["nope"] }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_cancelled_issues_are_never_delivered() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    let issue_id = schedule_issue(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.post_cancel_issue(&issue_id).await;
+    assert_eq!(response.status().as_u16(), 204);
+
+    sqlx::query!("UPDATE newsletter_issues SET send_at = now() - interval '1 minute'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(app.publish_due_issues().await, 0);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_issues_that_are_no_longer_scheduled_cannot_be_changed() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = schedule_issue(&app).await;
+    make_issue_due(&app, &issue_id).await;
+    app.publish_due_issues().await;
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "title": "Too late" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 409);
+
+    let response = app.post_cancel_issue(&issue_id).await;
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[actix_web::test]
+async fn test_changing_an_unknown_issue_returns_404() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    let issue_id = Uuid::new_v4().to_string();
+
+    let response = app
+        .patch_admin_issue(&issue_id, &serde_json::json!({ "title": "New title" }))
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+
+    let response = app.post_cancel_issue(&issue_id).await;
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -113,7 +113,31 @@ async fn test_trying_to_subscribe_with_pending_confirmation_does_not_fail() {
 }
 
 #[actix_web::test]
-async fn test_trying_to_subscribe_with_confirmed_status_fails() {
+async fn test_trying_to_subscribe_with_pending_confirmation_resends_the_same_link() {
+    let app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_subscription_request(body.into()).await;
+    app.send_subscription_request(body.into()).await;
+
+    let email_server_requests = app.mock_email_server.received_requests().await.unwrap();
+    let first_confirmation_links = app.get_email_confirmation_links(&email_server_requests[0]);
+    let second_confirmation_links = app.get_email_confirmation_links(&email_server_requests[1]);
+    assert_eq!(
+        first_confirmation_links.html_link,
+        second_confirmation_links.html_link
+    );
+}
+
+#[actix_web::test]
+async fn test_trying_to_subscribe_with_confirmed_status_sends_a_notice() {
     let app = spawn_app().await;
 
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
@@ -141,7 +165,84 @@ async fn test_trying_to_subscribe_with_confirmed_status_fails() {
 
     // Subscribe a second time once confirmed
     let response = app.send_subscription_request(body.into()).await;
-    assert_eq!(response.status().as_u16(), 500);
+    assert_eq!(response.status().as_u16(), 201);
+
+    let email_server_requests = app.mock_email_server.received_requests().await.unwrap();
+    assert_eq!(email_server_requests.len(), 2);
+    let notice: serde_json::Value = serde_json::from_slice(&email_server_requests[1].body).unwrap();
+    assert_eq!(notice["Subject"], "You are already subscribed");
+    assert!(!notice["TextBody"]
+        .as_str()
+        .unwrap()
+        .contains("/subscriptions/confirm"));
+
+    let database_subscriptor = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(database_subscriptor.status, "confirmed");
+}
+
+#[actix_web::test]
+async fn test_unsubscribed_email_subscribing_again_goes_through_double_opt_in() {
+    let app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_subscription_request(body.into()).await;
+    let email_server_first_request = &app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_email_confirmation_links(email_server_first_request);
+    reqwest::get(confirmation_links.html_link)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    let unsubscribe_token = sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token;
+    app.post_one_click_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let response = app.send_subscription_request(body.into()).await;
+    assert_eq!(response.status().as_u16(), 201);
+
+    let database_subscriptor = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(database_subscriptor.status, "pending_confirmation");
+
+    let email_server_last_request = &app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_email_confirmation_links(email_server_last_request);
+    let response = reqwest::get(confirmation_links.html_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    let database_subscriptor = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(database_subscriptor.status, "confirmed");
 }
 
 #[rstest]
@@ -195,3 +296,29 @@ async fn test_database_error_causes_endpoint_to_return_500() {
 
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[actix_web::test]
+async fn test_concurrent_requests_for_a_new_email_are_handled_gracefully() {
+    let app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    let (first_response, second_response) = tokio::join!(
+        app.send_subscription_request(body.into()),
+        app.send_subscription_request(body.into())
+    );
+
+    assert_eq!(first_response.status().as_u16(), 201);
+    assert_eq!(second_response.status().as_u16(), 201);
+    let saved = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_all(&app.db_connection_pool)
+        .await
+        .expect("Failed to fetch saved subscription");
+    assert_eq!(saved.len(), 1);
+    assert_eq!(saved[0].status, "pending_confirmation");
+}
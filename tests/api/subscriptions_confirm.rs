@@ -36,7 +36,7 @@ async fn test_link_returned_by_subscribe_endpoint_returns_200() {
 }
 
 #[actix_web::test]
-async fn test_confirmation_link_hit_twice_works() {
+async fn test_confirmation_link_hit_twice_returns_410() {
     let app = spawn_app().await;
 
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
@@ -66,7 +66,7 @@ async fn test_confirmation_link_hit_twice_works() {
     let confirm_endpoint_response = reqwest::get(confirmation_links.html_link)
         .await
         .expect("Failed to hit subscription confirmation endpoint");
-    assert_eq!(confirm_endpoint_response.status().as_u16(), 200);
+    assert_eq!(confirm_endpoint_response.status().as_u16(), 410);
 }
 
 #[actix_web::test]
@@ -143,3 +143,104 @@ async fn test_confirmation_endpoint_confirms_subscriber_in_database() {
     assert_eq!(database_subscriptor.name, "le guin");
     assert_eq!(database_subscriptor.status, "confirmed");
 }
+
+#[actix_web::test]
+async fn test_expired_confirmation_link_returns_410() {
+    let app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_subscription_request(body.into()).await;
+
+    let email_server_first_request = &app
+        .mock_email_server
+        .received_requests()
+        .await
+        .expect("No received requests in the email server")
+        .first()
+        .cloned()
+        .expect("Unable to extract first email server request");
+    let confirmation_links = app.get_email_confirmation_links(email_server_first_request);
+
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '2 days'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+
+    let confirm_endpoint_response = reqwest::get(confirmation_links.html_link)
+        .await
+        .expect("Failed to hit subscription confirmation endpoint");
+    assert_eq!(confirm_endpoint_response.status().as_u16(), 410);
+
+    let database_subscriptor = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(database_subscriptor.status, "pending_confirmation");
+}
+
+#[actix_web::test]
+async fn test_confirmation_removes_the_other_tokens_of_the_subscriber() {
+    let app = spawn_app().await;
+
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_subscription_request(body.into()).await;
+    // An expired token is not reused, so subscribing again issues a new one
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '2 days'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    app.send_subscription_request(body.into()).await;
+
+    let email_server_requests = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .expect("No received requests in the email server");
+    let first_confirmation_links = app.get_email_confirmation_links(&email_server_requests[0]);
+    let second_confirmation_links = app.get_email_confirmation_links(&email_server_requests[1]);
+
+    let confirm_endpoint_response = reqwest::get(second_confirmation_links.html_link)
+        .await
+        .expect("Failed to hit subscription confirmation endpoint");
+    assert_eq!(confirm_endpoint_response.status().as_u16(), 200);
+
+    let confirm_endpoint_response = reqwest::get(first_confirmation_links.html_link)
+        .await
+        .expect("Failed to hit subscription confirmation endpoint");
+    assert_eq!(confirm_endpoint_response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_expired_tokens_are_deleted_whether_they_were_used_or_not() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    app.create_unconfirmed_subscriber_with_email("octavia_butler@gmail.com")
+        .await;
+
+    // Tokens still within their TTL are kept, even the consumed one
+    assert_eq!(app.delete_expired_subscription_tokens().await, 0);
+
+    sqlx::query!("UPDATE subscription_tokens SET created_at = now() - interval '2 days'")
+        .execute(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(app.delete_expired_subscription_tokens().await, 2);
+
+    let tokens = sqlx::query!("SELECT COUNT(*) AS \"count!\" FROM subscription_tokens")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(tokens.count, 0);
+}
@@ -0,0 +1,304 @@
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestingApp};
+
+const SUBSCRIPTION_BODY: &str = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+async fn suppress(app: &TestingApp, email: &str) {
+    let response = app
+        .post_admin_suppression(&serde_json::json!({ "email": email }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+}
+
+async fn get_suppressions(app: &TestingApp) -> Vec<serde_json::Value> {
+    let response = app
+        .get_admin_suppressions(&serde_json::json!({}))
+        .await
+        .error_for_status()
+        .unwrap();
+    response.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_you_must_be_logged_in_to_manage_suppressions() {
+    let app = spawn_app().await;
+
+    let response = app.get_admin_suppressions(&serde_json::json!({})).await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .post_admin_suppression(&serde_json::json!({ "email": "ursula_le_guin@gmail.com" }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    let response = app
+        .delete_admin_suppression("ursula_le_guin@gmail.com")
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn test_admins_can_add_list_and_remove_suppressions() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let response = app
+        .post_admin_suppression(&serde_json::json!({
+            "email": "Ursula_Le_Guin@gmail.com",
+            "reason": "complained"
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let suppression: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(suppression["email"], "ursula_le_guin@gmail.com");
+    assert_eq!(suppression["reason"], "complained");
+    assert_eq!(suppression["source"], "admin");
+
+    let suppressions = get_suppressions(&app).await;
+    assert_eq!(suppressions.len(), 1);
+    assert_eq!(suppressions[0]["email"], "ursula_le_guin@gmail.com");
+
+    let response = app
+        .delete_admin_suppression("ursula_le_guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 204);
+    assert!(get_suppressions(&app).await.is_empty());
+
+    let response = app
+        .delete_admin_suppression("ursula_le_guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn test_suppressions_can_be_filtered_by_reason() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+    app.post_admin_suppression(&serde_json::json!({
+        "email": "octavia_butler@gmail.com",
+        "reason": "bounced"
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    let response = app
+        .get_admin_suppressions(&serde_json::json!({ "reason": "bounced" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let suppressions: Vec<serde_json::Value> = response.json().await.unwrap();
+    assert_eq!(suppressions.len(), 1);
+    assert_eq!(suppressions[0]["email"], "octavia_butler@gmail.com");
+}
+
+#[tokio::test]
+async fn test_adding_an_address_twice_is_a_conflict() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+
+    let response = app
+        .post_admin_suppression(&serde_json::json!({ "email": "URSULA_LE_GUIN@gmail.com" }))
+        .await;
+
+    assert_eq!(response.status().as_u16(), 409);
+}
+
+#[tokio::test]
+async fn test_invalid_suppressions_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+
+    let test_cases = [
+        (
+            serde_json::json!({ "email": "not-an-email" }),
+            "invalid email",
+        ),
+        (
+            serde_json::json!({ "email": "ursula_le_guin@gmail.com", "reason": "annoying" }),
+            "unknown reason",
+        ),
+    ];
+    for (body, description) in test_cases {
+        let response = app.post_admin_suppression(&body).await;
+
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 with {}",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_suppressed_addresses_cannot_subscribe() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    suppress(&app, "URSULA_LE_GUIN@gmail.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .send_subscription_request(SUBSCRIPTION_BODY.into())
+        .await;
+
+    assert_eq!(response.status().as_u16(), 201);
+    let subscriptions = sqlx::query!("SELECT id FROM subscriptions")
+        .fetch_all(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert!(subscriptions.is_empty());
+}
+
+#[tokio::test]
+async fn test_removing_a_suppression_allows_subscribing_again() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    app.post_postmark_webhook(&serde_json::json!({
+        "RecordType": "SpamComplaint",
+        "Email": "ursula_le_guin@gmail.com"
+    }))
+    .await
+    .error_for_status()
+    .unwrap();
+    let suppressions = get_suppressions(&app).await;
+    assert_eq!(suppressions[0]["reason"], "complained");
+    assert_eq!(suppressions[0]["source"], "postmark");
+
+    app.delete_admin_suppression("ursula_le_guin@gmail.com")
+        .await
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+    app.send_subscription_request(SUBSCRIPTION_BODY.into())
+        .await
+        .error_for_status()
+        .unwrap();
+
+    let subscription = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscription.status, "pending_confirmation");
+}
+
+#[tokio::test]
+async fn test_suppressed_subscribers_do_not_receive_issues() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .send_newsletter(serde_json::json!({
+            "title": "Newsletter title",
+            "content": {
+                "plain_text": "Newsletter body",
+                "html": "<p>Newsletter body</p>"
+            }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn test_suppressed_subscribers_are_not_sent_links() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    app.create_confirmed_subscriber().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .post_preferences_link_request("ursula_le_guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+    let response = app
+        .post_data_export_request("ursula_le_guin@gmail.com")
+        .await;
+    assert_eq!(response.status().as_u16(), 202);
+}
+
+#[tokio::test]
+async fn test_suppressed_addresses_are_not_imported() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .post_subscribers_import(
+            &serde_json::json!({ "send_confirmation_emails": true }),
+            "email,name\nursula_le_guin@gmail.com,Ursula\n",
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let report: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(report["imported"], 0);
+    assert_eq!(report["confirmation_emails_sent"], 0);
+    assert_eq!(report["errors"][0]["line"], 2);
+    assert_eq!(
+        report["errors"][0]["error"],
+        "The email is on the suppression list"
+    );
+}
+
+#[tokio::test]
+async fn test_test_sends_to_suppressed_addresses_are_rejected() {
+    let app = spawn_app().await;
+    app.log_in_test_user().await;
+    suppress(&app, "ursula_le_guin@gmail.com").await;
+    let response = app
+        .post_admin_issue(&serde_json::json!({
+            "title": "Newsletter title",
+            "content": { "markdown": "Newsletter body" }
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 201);
+    let issue: serde_json::Value = response.json().await.unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+    let response = app
+        .post_test_issue(
+            issue["newsletter_issue_id"].as_str().unwrap(),
+            &serde_json::json!({ "recipients": ["ursula_le_guin@gmail.com"] }),
+        )
+        .await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
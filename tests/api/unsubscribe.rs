@@ -0,0 +1,178 @@
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{spawn_app, TestingApp};
+
+async fn get_unsubscribe_token(app: &TestingApp) -> String {
+    sqlx::query!("SELECT unsubscribe_token FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap()
+        .unsubscribe_token
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content": {
+            "plain_text": "Newsletter body",
+            "html": "<p>Newsletter body</p>"
+        }
+    })
+}
+
+#[actix_web::test]
+async fn test_unsubscribe_page_renders_a_confirmation_form() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+
+    let response = app.get_unsubscribe_page(&unsubscribe_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&format!(
+        r#"action="/subscriptions/unsubscribe?token={}""#,
+        unsubscribe_token
+    )));
+}
+
+#[actix_web::test]
+async fn test_unsubscribe_page_does_not_change_the_subscription() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+
+    app.get_unsubscribe_page(&unsubscribe_token).await;
+
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "confirmed");
+}
+
+#[actix_web::test]
+async fn test_one_click_unsubscribe_marks_the_subscriber_as_unsubscribed() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+
+    let response = app.post_one_click_unsubscribe(&unsubscribe_token).await;
+
+    assert_eq!(response.status().as_u16(), 200);
+    let subscriber = sqlx::query!("SELECT status FROM subscriptions")
+        .fetch_one(&app.db_connection_pool)
+        .await
+        .unwrap();
+    assert_eq!(subscriber.status, "unsubscribed");
+}
+
+#[actix_web::test]
+async fn test_malformed_unsubscribe_token_is_rejected_with_400() {
+    let app = spawn_app().await;
+
+    let response = app.post_one_click_unsubscribe("not-a-token").await;
+
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[actix_web::test]
+async fn test_unknown_unsubscribe_token_is_rejected_with_401() {
+    let app = spawn_app().await;
+
+    let response = app.get_unsubscribe_page(&"a".repeat(32)).await;
+    assert_eq!(response.status().as_u16(), 401);
+
+    let response = app.post_one_click_unsubscribe(&"a".repeat(32)).await;
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[actix_web::test]
+async fn test_newsletter_emails_carry_list_unsubscribe_headers() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_newsletter(newsletter_body()).await;
+    app.dispatch_all_pending_emails().await;
+
+    let email_request = app
+        .mock_email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let request_body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(
+        request_body["Headers"],
+        serde_json::json!([
+            {
+                "Name": "List-Unsubscribe",
+                "Value": format!(
+                    "<{}/subscriptions/unsubscribe?token={}>",
+                    app.server_address, unsubscribe_token
+                )
+            },
+            {
+                "Name": "List-Unsubscribe-Post",
+                "Value": "List-Unsubscribe=One-Click"
+            }
+        ])
+    );
+}
+
+#[actix_web::test]
+async fn test_unsubscribed_subscribers_do_not_receive_newsletters() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+    app.post_one_click_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    let response = app.send_newsletter(newsletter_body()).await;
+
+    assert_eq!(response.status().as_u16(), 202);
+    app.dispatch_all_pending_emails().await;
+}
+
+#[actix_web::test]
+async fn test_subscribers_leaving_after_publication_do_not_receive_the_issue() {
+    let app = spawn_app().await;
+    app.create_confirmed_subscriber().await;
+    let unsubscribe_token = get_unsubscribe_token(&app).await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.mock_email_server)
+        .await;
+
+    app.send_newsletter(newsletter_body())
+        .await
+        .error_for_status()
+        .unwrap();
+    app.post_one_click_unsubscribe(&unsubscribe_token)
+        .await
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+}